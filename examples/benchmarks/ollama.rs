@@ -6,6 +6,7 @@ use std::fs::File;
 use std::io::Read;
 
 /// A `println!` macro that only prints when the `debug_assertions` flag is set, i.e. it wont print when `--release` is used.
+#[allow(unused_macros)]
 macro_rules! debug_println {
     ($($arg:tt)*) => (if ::std::cfg!(debug_assertions) { ::std::println!($($arg)*); })
 }
@@ -68,7 +69,7 @@ async fn main() {
                     results.push(result);
                     break;
                 } else {
-                    println!("{}: {}", "Warn".yellow(), "Could not get final data.");
+                    println!("{}: Could not get final data.", "Warn".yellow());
                 }
             }
         }
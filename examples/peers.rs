@@ -3,7 +3,8 @@ use tokio_util::sync::CancellationToken;
 
 #[tokio::main]
 async fn main() {
-    let node = DriaComputeNode::new(DriaComputeNodeConfig::new(), CancellationToken::default());
+    let config = DriaComputeNodeConfig::new_from_env().unwrap();
+    let node = DriaComputeNode::new(config, CancellationToken::default());
     let waku = node.waku;
 
     let peers = waku.peers().await.unwrap();
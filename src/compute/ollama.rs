@@ -1,5 +1,3 @@
-use std::env;
-
 use ollama_rs::{
     error::OllamaError,
     generation::completion::{request::GenerationRequest, GenerationResponse},
@@ -7,10 +5,31 @@ use ollama_rs::{
 };
 use tokio_util::sync::CancellationToken;
 
-use crate::compute::constants::{
-    DEFAULT_DKN_OLLAMA_HOST, DEFAULT_DKN_OLLAMA_MODEL, DEFAULT_DKN_OLLAMA_PORT,
+use crate::{
+    compute::constants::{
+        DEFAULT_DKN_OLLAMA_HOST, DEFAULT_DKN_OLLAMA_MODEL, DEFAULT_DKN_OLLAMA_PORT,
+    },
+    errors::{NodeError, NodeResult},
 };
 
+/// Configuration of the Ollama server, and the model that runs the tasks.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OllamaConfig {
+    pub host: String,
+    pub port: u16,
+    pub model: String,
+}
+
+impl Default for OllamaConfig {
+    fn default() -> Self {
+        Self {
+            host: DEFAULT_DKN_OLLAMA_HOST.to_string(),
+            port: DEFAULT_DKN_OLLAMA_PORT,
+            model: DEFAULT_DKN_OLLAMA_MODEL.to_string(),
+        }
+    }
+}
+
 /// A wrapper for the Ollama API.
 #[derive(Debug, Clone)]
 pub struct OllamaClient {
@@ -29,27 +48,11 @@ impl Default for OllamaClient {
 }
 
 impl OllamaClient {
-    /// Creates a new Ollama client.
-    ///
-    /// Reads `DKN_OLLAMA_HOST`, `DKN_OLLAMA_PORT` and `DKN_OLLAMA_MODEL` from the environment, and defaults if not provided.
+    /// Creates a new Ollama client, with the defaults for the values that are not provided.
     pub fn new(host: Option<String>, port: Option<u16>, model: Option<String>) -> Self {
-        let host = host.unwrap_or_else(|| {
-            env::var("DKN_OLLAMA_HOST").unwrap_or(DEFAULT_DKN_OLLAMA_HOST.to_string())
-        });
-
-        let port = port.unwrap_or_else(|| {
-            env::var("DKN_OLLAMA_PORT")
-                .and_then(|port_str| {
-                    port_str
-                        .parse::<u16>()
-                        .map_err(|_| env::VarError::NotPresent)
-                })
-                .unwrap_or(DEFAULT_DKN_OLLAMA_PORT)
-        });
-
-        let model = model.unwrap_or_else(|| {
-            env::var("DKN_OLLAMA_MODEL").unwrap_or(DEFAULT_DKN_OLLAMA_MODEL.to_string())
-        });
+        let host = host.unwrap_or_else(|| DEFAULT_DKN_OLLAMA_HOST.to_string());
+        let port = port.unwrap_or(DEFAULT_DKN_OLLAMA_PORT);
+        let model = model.unwrap_or_else(|| DEFAULT_DKN_OLLAMA_MODEL.to_string());
 
         let client = Ollama::new(host, port);
         log::info!("Ollama URL: {}", client.uri());
@@ -58,15 +61,34 @@ impl OllamaClient {
         Self { client, model }
     }
 
+    /// Creates a client for the configured server and model.
+    pub fn new_from_config(config: &OllamaConfig) -> Self {
+        Self::new(
+            Some(config.host.clone()),
+            Some(config.port),
+            Some(config.model.clone()),
+        )
+    }
+
+    /// Returns a client for the same server, using the given model instead.
+    pub fn with_model(&self, model: &str) -> Self {
+        Self {
+            client: self.client.clone(),
+            model: model.to_string(),
+        }
+    }
+
     /// Lists local models for diagnostic, and pulls the configured model.
-    pub async fn setup(&self, cancellation: CancellationToken) -> Result<(), OllamaError> {
+    ///
+    /// Pulling is retried a few times, unless the error is not retryable.
+    pub async fn setup(&self, cancellation: CancellationToken) -> NodeResult<()> {
         log::info!("Checking local models");
         let local_models = self.client.list_local_models().await?;
         let num_local_modals = local_models.len();
         if num_local_modals == 0 {
             log::info!("No local models found.");
         } else {
-            let mut message = format!("{}{}", num_local_modals, " local models found:".to_string());
+            let mut message = format!("{} local models found:", num_local_modals);
             for model in local_models.iter() {
                 message.push_str(format!("\n{}", model.name).as_str())
             }
@@ -75,12 +97,9 @@ impl OllamaClient {
 
         log::info!("Pulling model: {}, this may take a while...", self.model);
         let mut retry_count = 0; // retry count for edge case
-        while let Err(e) = self.client.pull_model((&self.model).into(), false).await {
-            // edge case: invalid model is given
-            if e.to_string().contains("file does not exist") {
-                return Err(OllamaError::from(
-                    "Invalid Ollama model, please check your environment variables.".to_string(),
-                ));
+        while let Err(e) = self.pull_model().await {
+            if !e.is_retryable() {
+                return Err(e);
             } else if retry_count < 3 {
                 log::error!("Error setting up Ollama: {}\nRetrying in 5 seconds.", e);
                 tokio::select! {
@@ -93,9 +112,7 @@ impl OllamaClient {
             } else {
                 // Handling the case when maximum retries are exceeded
                 log::error!("Maximum retry attempts exceeded, stopping retries.");
-                return Err(OllamaError::from(
-                    "Maximum retry attempts exceeded.".to_string(),
-                ));
+                return Err(e);
             }
         }
         log::info!("Pulled {}", self.model);
@@ -103,12 +120,37 @@ impl OllamaClient {
         Ok(())
     }
 
+    /// Pulls the configured model, where an invalid model is a configuration error.
+    async fn pull_model(&self) -> NodeResult<()> {
+        match self.client.pull_model((&self.model).into(), false).await {
+            Ok(_) => Ok(()),
+            // edge case: invalid model is given
+            Err(e) if e.to_string().contains("file does not exist") => {
+                Err(NodeError::Config(format!(
+                    "Invalid Ollama model {}, please check DKN_OLLAMA_MODEL (ollama.model) in your config.",
+                    self.model
+                )))
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Returns the names of the models that are pulled.
+    pub async fn local_models(&self) -> Result<Vec<String>, OllamaError> {
+        let models = self.client.list_local_models().await?;
+        Ok(models.into_iter().map(|model| model.name).collect())
+    }
+
     /// Generates a result using the local LLM.
-    pub async fn generate(&self, prompt: String) -> Result<GenerationResponse, String> {
+    pub async fn generate(&self, prompt: String) -> NodeResult<GenerationResponse> {
         log::debug!("Generating with prompt: {}", prompt);
 
         let gen_req = GenerationRequest::new(self.model.clone(), prompt);
-        let gen_res = self.client.generate(gen_req).await?;
+        let gen_res = self
+            .client
+            .generate(gen_req)
+            .await
+            .map_err(|e| NodeError::Llm(e.into()))?;
 
         log::debug!("Generated response: {}", gen_res.response);
         Ok(gen_res)
@@ -146,13 +188,19 @@ mod tests {
 
     #[test]
     fn test_ollama_config() {
-        env::set_var("DKN_OLLAMA_HOST", "im-a-host");
-        env::set_var("DKN_OLLAMA_MODEL", "phi3");
-        env::remove_var("DKN_OLLAMA_PORT");
-
-        // will use default port, but read host and model from env
-        let ollama = OllamaClient::new(None, None, None);
-        assert_eq!(ollama.client.uri(), "im-a-host:11434");
+        let config = OllamaConfig {
+            host: "http://im-a-host".to_string(),
+            model: "phi3".to_string(),
+            ..Default::default()
+        };
+
+        // will use default port, but read host and model from the config
+        let ollama = OllamaClient::new_from_config(&config);
+        assert_eq!(ollama.client.uri(), "http://im-a-host:11434");
         assert_eq!(ollama.model, "phi3");
+
+        let embedder = ollama.with_model("nomic-embed-text");
+        assert_eq!(embedder.client.uri(), "http://im-a-host:11434");
+        assert_eq!(embedder.model, "nomic-embed-text");
     }
 }
@@ -1,66 +1,140 @@
-use crate::compute::search::config::{PersonaPool, Persona};
-use crate::compute::search::utils::prompt::create_system_prompt;
-use ollama_rs::{
-    generation::functions::tools::{Scraper, DDGSearcher, StockScraper},
-    generation::functions::{FunctionCallRequest, NousFunctionCall},
-    generation::chat::ChatMessage,
-    generation::chat::request::ChatMessageRequest,
-    Ollama,
+use langchain_rust::{embedding::embedder_trait::Embedder, tools::Tool};
+use ollama_rs::generation::{
+    chat::{request::ChatMessageRequest, ChatMessage},
+    options::GenerationOptions,
 };
-use std::sync::Arc;
-use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{path::PathBuf, sync::Arc};
+use tokio_util::sync::CancellationToken;
 
-#[derive(Debug, Clone)]
-pub struct Agent{
-    persona: Persona,
-    react: Arc<RwLock<Ollama>>,
-    observer: Ollama,
-}
+use crate::{
+    compute::{
+        ollama::OllamaClient,
+        search::{
+            answer::{format_sources, parse_citations, Citation},
+            config::{Persona, PersonaPool},
+            tools::{scraper::ScrapedPage, PageSearcher},
+            utils::prompt::{
+                create_answer_prompt, create_summarization_prompt, create_system_prompt,
+            },
+            vectorstore::{Similarity, VectorStore},
+        },
+    },
+    errors::{NodeError, NodeResult},
+};
 
-impl Default for Agent {
-    fn default() -> Self {
-        Self::new()
-    }
+/// Default number of thought/action/observation steps before the agent is asked for a final answer.
+pub const DEFAULT_MAX_ITERATIONS: usize = 6;
+
+/// Generation stops at this marker, so that the model does not make up observations itself.
+const OBSERVATION_MARKER: &str = "Observation:";
+
+/// Maximum number of characters of a tool output that is fed back to the model.
+const MAX_OBSERVATION_CHARS: usize = 4000;
+
+/// Maximum number of characters of a scraped page that is summarized, when no embedder is set.
+const MAX_SUMMARIZED_CHARS: usize = 16000;
+
+/// Number of chunks of a scraped page that are fed back to the model, when an embedder is set.
+const RETRIEVED_CHUNKS: usize = 3;
+
+/// Number of chunks given to the model as sources for the final answer.
+const ANSWER_CHUNKS: usize = 6;
+
+/// A single step of the agent, as recorded in the transcript.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum Step {
+    Thought { content: String },
+    Action { tool: String, input: String },
+    Observation { content: String },
+    Answer { content: String },
 }
 
-impl Agent {
-    pub fn new() -> Self {
-
-        let observer = Ollama::default();
-        let react = Arc::new(RwLock::new(Ollama::new_default_with_history(30)));
-        let pool = PersonaPool::new_from_file("data/personas.json");
-        let persona: Persona = match pool.get_random_agent() {
-            Some(agent) => agent.clone(),
-            None => Persona::default()
-        };
+/// A record of everything the agent did for a task.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct Transcript {
+    pub model: String,
+    pub persona: String,
+    pub steps: Vec<Step>,
+}
 
-        let system_prompt = create_system_prompt(
-            "Search for information on the topic of 'Machine Learning'.",
-            None,
-            None,
-            &Persona::default(),
-        );
+/// The final answer of the agent, the sources it cites, and the transcript that led to it.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct AgentOutput {
+    pub answer: String,
+    pub citations: Vec<Citation>,
+    pub transcript: Transcript,
+}
 
-        react.write().set_system_response(
-            "default".to_string(),
-            system_prompt,
-        );
+/// # Search Agent
+///
+/// A ReAct agent that answers a task by alternating between reasoning and calling one of its tools,
+/// until it arrives at a final answer or runs out of iterations.
+///
+/// If an embedder is set, scraped pages are indexed into a vector store that lives for a single task.
+/// Only the chunks most relevant to the task are then fed back to the model, and the agent is given
+/// a tool to search across every page it has scraped. Once the agent has a draft answer, the final
+/// answer is written from the most relevant chunks, citing them by number. Without an embedder,
+/// scraped pages are summarized with respect to the task instead.
+#[derive(Clone)]
+pub struct Agent {
+    ollama: OllamaClient,
+    tools: Vec<Arc<dyn Tool>>,
+    embedder: Option<Arc<dyn Embedder>>,
+    persona: Persona,
+    /// Directory of prompts that override the built-in prompts.
+    prompts_dir: Option<PathBuf>,
+    max_iterations: usize,
+}
 
+impl Agent {
+    /// Creates a new agent, using the model of the given Ollama client.
+    pub fn new(ollama: OllamaClient, tools: Vec<Arc<dyn Tool>>, persona: Persona) -> Self {
         Self {
+            ollama,
+            tools,
+            embedder: None,
             persona,
-            react,
-            observer
+            prompts_dir: None,
+            max_iterations: DEFAULT_MAX_ITERATIONS,
         }
     }
 
-    pub fn new_from_persona(query: &str) -> Self {
-        let decide = Ollama::default();
-        let react = Arc::new(RwLock::new(Ollama::new_default_with_history(30)));
-        Self {
-            persona,
-            react,
-            observer
-        }
+    /// Creates a new agent with a persona picked from the pool, at random or from the given seed.
+    ///
+    /// The same seed over the same pool always results in the same persona, see
+    /// [`PersonaPool::get_seeded_agent`].
+    pub fn new_from_pool(
+        ollama: OllamaClient,
+        tools: Vec<Arc<dyn Tool>>,
+        pool: &PersonaPool,
+        seed: Option<u64>,
+    ) -> Self {
+        let persona = match seed {
+            Some(seed) => pool.get_seeded_agent(seed),
+            None => pool.get_random_agent(),
+        };
+        Self::new(ollama, tools, persona.cloned().unwrap_or_default())
+    }
+
+    /// Sets the maximum number of tool calls the agent can make.
+    pub fn with_max_iterations(mut self, max_iterations: usize) -> Self {
+        self.max_iterations = max_iterations;
+        self
+    }
+
+    /// Overrides the built-in prompts with those within the given directory.
+    pub fn with_prompts_dir(mut self, dir: PathBuf) -> Self {
+        self.prompts_dir = Some(dir);
+        self
+    }
+
+    /// Sets the embedder used to index scraped pages.
+    pub fn with_embedder(mut self, embedder: Arc<dyn Embedder>) -> Self {
+        self.embedder = Some(embedder);
+        self
     }
 
     pub fn get_persona(&self) -> &Persona {
@@ -71,34 +145,554 @@ impl Agent {
         self.persona = persona;
     }
 
-    pub async fn react_response(&self, message: String) -> String {
+    /// Returns the name of the model used by the agent.
+    #[inline]
+    pub fn model(&self) -> &str {
+        &self.ollama.model
+    }
+
+    /// Runs the agent for the given task, and answers it with citations if pages were indexed.
+    ///
+    /// Tool failures are given back to the model as observations, whereas LLM failures and
+    /// cancellation stop the agent with an error.
+    pub async fn run(
+        &self,
+        task: &str,
+        cancellation: &CancellationToken,
+    ) -> NodeResult<AgentOutput> {
+        let mut transcript = Transcript {
+            model: self.ollama.model.clone(),
+            persona: self.persona.name.clone(),
+            steps: Vec::new(),
+        };
+
+        // the store and the tool to search it only live for this task
+        let store = self
+            .embedder
+            .clone()
+            .map(|embedder| Arc::new(VectorStore::new(embedder, Similarity::Cosine)));
+        let mut tools = self.tools.clone();
+        if let Some(store) = &store {
+            tools.push(Arc::new(PageSearcher::new(store.clone())));
+        }
+
+        let draft = self
+            .research(
+                task,
+                &tools,
+                store.as_deref(),
+                &mut transcript,
+                cancellation,
+            )
+            .await?;
+
+        let (answer, citations) = match &store {
+            Some(store) if !store.is_empty() => {
+                self.answer_from_sources(task, &draft, store, cancellation)
+                    .await?
+            }
+            _ => (draft, Vec::new()),
+        };
+
+        Ok(AgentOutput {
+            answer,
+            citations,
+            transcript,
+        })
+    }
+
+    /// Runs the thought/action/observation loop, and returns the answer that the agent arrives at.
+    async fn research(
+        &self,
+        task: &str,
+        tools: &[Arc<dyn Tool>],
+        store: Option<&VectorStore>,
+        transcript: &mut Transcript,
+        cancellation: &CancellationToken,
+    ) -> NodeResult<String> {
+        let mut messages = vec![
+            ChatMessage::system(create_system_prompt(
+                self.prompts_dir.as_deref(),
+                task,
+                Some(tools),
+                None,
+                &self.persona,
+            )),
+            ChatMessage::user(format!("Question: {}", task)),
+        ];
+
+        for iteration in 0..self.max_iterations {
+            let reply = self.chat(&messages, cancellation).await?;
+            log::debug!("Agent step {}: {}", iteration, reply);
+            messages.push(ChatMessage::assistant(reply.clone()));
+
+            let parsed = parse_reply(&reply);
+            if let Some(thought) = parsed.thought {
+                transcript.steps.push(Step::Thought { content: thought });
+            }
+
+            let observation = match parsed.decision {
+                Decision::Answer(answer) => {
+                    transcript.steps.push(Step::Answer {
+                        content: answer.clone(),
+                    });
+                    return Ok(answer);
+                }
+                Decision::Action { tool, input } => {
+                    transcript.steps.push(Step::Action {
+                        tool: tool.clone(),
+                        input: input.clone(),
+                    });
+                    let observation = call_tool(tools, &tool, &input, cancellation).await?;
+                    match store {
+                        Some(store) => index_page(store, observation, task).await,
+                        None => self.summarize_page(observation, task, cancellation).await?,
+                    }
+                }
+                Decision::Invalid => format!(
+                    "Invalid format. Either give an `Action` with an `Action Input`, or a `Final Answer`. Available tools are: {}.",
+                    tool_names(tools).join(", ")
+                ),
+            };
+
+            transcript.steps.push(Step::Observation {
+                content: observation.clone(),
+            });
+            messages.push(ChatMessage::user(format!(
+                "{} {}",
+                OBSERVATION_MARKER, observation
+            )));
+        }
+
+        // out of iterations, ask for an answer with whatever has been observed so far
+        log::warn!(
+            "Agent reached {} iterations, asking for a final answer.",
+            self.max_iterations
+        );
+        messages.push(ChatMessage::user(
+            "You have used all of your steps. Give your Final Answer now, using only what you have observed so far."
+                .to_string(),
+        ));
+        let reply = self.chat(&messages, cancellation).await?;
+        let answer = match parse_reply(&reply).decision {
+            Decision::Answer(answer) => answer,
+            _ => reply.trim().to_string(),
+        };
+        transcript.steps.push(Step::Answer {
+            content: answer.clone(),
+        });
+
+        Ok(answer)
+    }
+
+    /// If the observation is a scraped page, returns its summary with respect to the task. Any other
+    /// observation is truncated.
+    async fn summarize_page(
+        &self,
+        observation: String,
+        task: &str,
+        cancellation: &CancellationToken,
+    ) -> NodeResult<String> {
+        let Ok(page) = serde_json::from_str::<ScrapedPage>(&observation) else {
+            return Ok(truncate(&observation, MAX_OBSERVATION_CHARS));
+        };
 
-        let user_message = ChatMessage::user(message.clone());
+        let prompt = create_summarization_prompt(
+            self.prompts_dir.as_deref(),
+            task,
+            &truncate(&page.chunks.join("\n\n"), MAX_SUMMARIZED_CHARS),
+            None,
+            &self.persona,
+        );
+        let summary = self
+            .chat(&[ChatMessage::user(prompt)], cancellation)
+            .await?;
 
-        let result = self.react.write().send_chat_messages_with_history(
-            ChatMessageRequest::new(
-                "adrienbrault/nous-hermes2theta-llama3-8b:q8_0".to_string(),
-                vec![user_message]
-            ),
-            "default".to_string()
-        ).await.unwrap();
+        let observation = format!(
+            "Scraped {} ({}), summary:\n{}",
+            page.metadata.title.as_deref().unwrap_or("untitled page"),
+            page.url,
+            summary.trim()
+        );
+        Ok(truncate(&observation, MAX_OBSERVATION_CHARS))
+    }
+
+    /// Answers the task from the chunks most relevant to it, citing them by number.
+    ///
+    /// If no chunks can be retrieved, the draft answer is returned without citations.
+    async fn answer_from_sources(
+        &self,
+        task: &str,
+        draft: &str,
+        store: &VectorStore,
+        cancellation: &CancellationToken,
+    ) -> NodeResult<(String, Vec<Citation>)> {
+        let chunks = match store.search(task, ANSWER_CHUNKS).await {
+            Ok(chunks) if !chunks.is_empty() => chunks,
+            Ok(_) => return Ok((draft.to_string(), Vec::new())),
+            Err(e) => {
+                log::warn!("Could not retrieve sources: {}", e);
+                return Ok((draft.to_string(), Vec::new()));
+            }
+        };
 
-        result.message.unwrap().content
+        let prompt = create_answer_prompt(
+            self.prompts_dir.as_deref(),
+            task,
+            draft,
+            &format_sources(&chunks),
+            None,
+            &self.persona,
+        );
+        let reply = self
+            .chat(&[ChatMessage::user(prompt)], cancellation)
+            .await?;
+        let answer = reply
+            .trim()
+            .trim_start_matches("Final Answer:")
+            .trim()
+            .to_string();
+        let citations = parse_citations(&answer, &chunks);
+
+        Ok((answer, citations))
     }
 
-    pub async fn function_call(&self, query: &str)-> String{
-        let parser = Arc::new(NousFunctionCall {});
-        let scraper_tool = Arc::new(Scraper {});
-        let ddg_search_tool = Arc::new(DDGSearcher::new());
-        let stock_scraper = Arc::new(StockScraper::new());
-        let result  = self.observer.send_function_call(
-            FunctionCallRequest::new(
-                "adrienbrault/nous-hermes2theta-llama3-8b:q8_0".to_string(),
-            vec![stock_scraper.clone(), ddg_search_tool.clone(), scraper_tool.clone()],
-            vec![ChatMessage::user(query.to_string())]
-        ),
-            parser.clone()).await.unwrap();
+    /// Sends the conversation so far to the model, and returns its reply.
+    async fn chat(
+        &self,
+        messages: &[ChatMessage],
+        cancellation: &CancellationToken,
+    ) -> NodeResult<String> {
+        let request = ChatMessageRequest::new(self.ollama.model.clone(), messages.to_vec())
+            .options(GenerationOptions::default().stop(vec![OBSERVATION_MARKER.to_string()]));
+
+        let response = tokio::select! {
+            _ = cancellation.cancelled() => return Err(NodeError::Cancelled),
+            response = self.ollama.client.send_chat_messages(request) => response?,
+        };
 
-        result.message.unwrap().content
+        response
+            .message
+            .map(|message| message.content)
+            .ok_or_else(|| NodeError::Llm("Model returned an empty message.".into()))
     }
-}
\ No newline at end of file
+}
+
+/// Calls the tool with the given name, and returns its output or error as an observation.
+async fn call_tool(
+    tools: &[Arc<dyn Tool>],
+    name: &str,
+    input: &str,
+    cancellation: &CancellationToken,
+) -> NodeResult<String> {
+    let Some(tool) = tools
+        .iter()
+        .find(|tool| tool.name().eq_ignore_ascii_case(name))
+    else {
+        return Ok(format!(
+            "Unknown tool {}. Available tools are: {}.",
+            name,
+            tool_names(tools).join(", ")
+        ));
+    };
+
+    let observation = tokio::select! {
+        _ = cancellation.cancelled() => return Err(NodeError::Cancelled),
+        result = tool.call(input) => match result {
+            Ok(output) => output,
+            Err(e) => format!("Error: {}", e),
+        },
+    };
+
+    Ok(observation)
+}
+
+/// If the observation is a scraped page, indexes it and returns only its chunks that are most
+/// relevant to the task. Any other observation, or a page that can not be indexed, is truncated.
+async fn index_page(store: &VectorStore, observation: String, task: &str) -> String {
+    let Ok(page) = serde_json::from_str::<ScrapedPage>(&observation) else {
+        return truncate(&observation, MAX_OBSERVATION_CHARS);
+    };
+
+    let relevant = match store.add_page(&page).await {
+        Ok(_) => store.search(task, store.len()).await,
+        Err(e) => Err(e),
+    };
+    let chunks: Vec<String> = match relevant {
+        Ok(chunks) => chunks
+            .into_iter()
+            .filter(|scored| scored.chunk.url == page.url)
+            .take(RETRIEVED_CHUNKS)
+            .map(|scored| scored.chunk.text)
+            .collect(),
+        Err(e) => {
+            log::warn!("Could not index {}: {}", page.url, e);
+            return truncate(&observation, MAX_OBSERVATION_CHARS);
+        }
+    };
+
+    let observation = format!(
+        "Scraped {} ({}) into {} chunks, use the Page Search tool to find more. Most relevant chunks:\n{}",
+        page.metadata.title.as_deref().unwrap_or("untitled page"),
+        page.url,
+        page.chunks.len(),
+        chunks.join("\n---\n")
+    );
+    truncate(&observation, MAX_OBSERVATION_CHARS)
+}
+
+#[inline]
+fn tool_names(tools: &[Arc<dyn Tool>]) -> Vec<String> {
+    tools.iter().map(|tool| tool.name()).collect()
+}
+
+/// What the model decided to do in a reply.
+#[derive(Debug, Clone, PartialEq)]
+enum Decision {
+    Action { tool: String, input: String },
+    Answer(String),
+    Invalid,
+}
+
+/// A parsed reply of the model.
+#[derive(Debug, Clone, PartialEq)]
+struct Reply {
+    thought: Option<String>,
+    decision: Decision,
+}
+
+/// Parses a ReAct-formatted reply of the model.
+///
+/// A `Final Answer` takes precedence over an `Action`, and anything after the final answer marker
+/// is considered to be the answer.
+fn parse_reply(reply: &str) -> Reply {
+    let thought = find_field(reply, "Thought:");
+
+    let decision = if let Some(index) = reply.find("Final Answer:") {
+        Decision::Answer(reply[index + "Final Answer:".len()..].trim().to_string())
+    } else if let Some(tool) = find_field(reply, "Action:") {
+        let input = find_field(reply, "Action Input:").unwrap_or_default();
+        Decision::Action {
+            tool: tool.trim_matches(|c| c == '`' || c == '"').to_string(),
+            input: normalize_input(&input),
+        }
+    } else {
+        Decision::Invalid
+    };
+
+    Reply { thought, decision }
+}
+
+/// Returns the text after the given label until the next label, if the label exists.
+fn find_field(reply: &str, label: &str) -> Option<String> {
+    const LABELS: [&str; 4] = ["Thought:", "Action:", "Action Input:", "Final Answer:"];
+
+    let mut lines = reply
+        .lines()
+        .map(str::trim_start)
+        .skip_while(|line| !line.starts_with(label));
+
+    let mut value = vec![lines.next()?.strip_prefix(label)?];
+    value.extend(lines.take_while(|line| !LABELS.iter().any(|l| line.starts_with(l))));
+
+    Some(value.join("\n").trim().to_string())
+}
+
+/// Strips code fences around a tool input, and compacts it if it is valid JSON.
+fn normalize_input(input: &str) -> String {
+    let input = input
+        .trim()
+        .trim_start_matches("```json")
+        .trim_start_matches("```")
+        .trim_end_matches("```")
+        .trim();
+
+    match serde_json::from_str::<Value>(input) {
+        Ok(value) => value.to_string(),
+        Err(_) => input.to_string(),
+    }
+}
+
+/// Truncates the text to at most `max_chars` characters.
+fn truncate(text: &str, max_chars: usize) -> String {
+    match text.char_indices().nth(max_chars) {
+        Some((index, _)) => format!("{}...", &text[..index]),
+        None => text.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_action() {
+        let reply = "Thought: I should search for this.\nAction: DDG Searcher\nAction Input: ```json\n{\"query\": \"who built llama3\"}\n```";
+        let parsed = parse_reply(reply);
+
+        assert_eq!(parsed.thought.as_deref(), Some("I should search for this."));
+        assert_eq!(
+            parsed.decision,
+            Decision::Action {
+                tool: "DDG Searcher".to_string(),
+                input: r#"{"query":"who built llama3"}"#.to_string()
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_final_answer() {
+        let reply = "Thought: I now know the final answer\nFinal Answer: Llama 3 was built by Meta.\nIt was released in 2024.";
+        let parsed = parse_reply(reply);
+
+        assert_eq!(
+            parsed.decision,
+            Decision::Answer("Llama 3 was built by Meta.\nIt was released in 2024.".to_string())
+        );
+    }
+
+    #[test]
+    fn test_parse_invalid() {
+        let parsed = parse_reply("I am not sure what to do.");
+        assert_eq!(parsed.thought, None);
+        assert_eq!(parsed.decision, Decision::Invalid);
+    }
+
+    #[test]
+    fn test_truncate() {
+        assert_eq!(truncate("hello", 10), "hello");
+        assert_eq!(truncate("hello world", 5), "hello...");
+        assert_eq!(truncate("çğüşöı", 3), "çğü...");
+    }
+
+    #[tokio::test]
+    async fn test_index_page() {
+        use crate::compute::search::{
+            utils::extract::PageMetadata, vectorstore::tests::KeywordEmbedder,
+        };
+
+        let store = VectorStore::new(Arc::new(KeywordEmbedder), Similarity::Cosine);
+        let page = ScrapedPage {
+            url: "https://ai.meta.com/blog/meta-llama-3/".to_string(),
+            metadata: PageMetadata {
+                title: Some("Introducing Meta Llama 3".to_string()),
+                ..Default::default()
+            },
+            chunks: vec![
+                "Cookies help us deliver our services.".to_string(),
+                "Meta Llama 3 is the next generation of Llama.".to_string(),
+            ],
+        };
+
+        let observation = serde_json::to_string(&page).expect("Should serialize");
+        let observation = index_page(&store, observation, "Who built Llama 3?").await;
+        assert_eq!(store.len(), 2);
+        assert!(observation.starts_with("Scraped Introducing Meta Llama 3"));
+        assert!(observation.contains("Most relevant chunks:\nMeta Llama 3 is"));
+
+        // other observations are left as they are
+        let observation = index_page(&store, "[]".to_string(), "Who built Llama 3?").await;
+        assert_eq!(observation, "[]");
+    }
+
+    #[tokio::test]
+    async fn test_summarize_page() {
+        use crate::compute::search::utils::extract::PageMetadata;
+
+        let mut server = mockito::Server::new_async().await;
+        let summarization = server
+            .mock("POST", "/api/chat")
+            .match_body(mockito::Matcher::Regex(
+                "Summarize the content below.*Meta Llama 3 is the next generation".to_string(),
+            ))
+            .with_body(r#"{"model": "phi3", "created_at": "2024-05-01T00:00:00Z", "message": {"role": "assistant", "content": "Meta built Llama 3."}, "done": true}"#)
+            .create_async()
+            .await;
+        let ollama = OllamaClient::new(
+            Some("http://127.0.0.1".to_string()),
+            Some(server.socket_address().port()),
+            Some("phi3".to_string()),
+        );
+        let agent = Agent::new(ollama, vec![], Persona::default());
+
+        let page = ScrapedPage {
+            url: "https://ai.meta.com/blog/meta-llama-3/".to_string(),
+            metadata: PageMetadata {
+                title: Some("Introducing Meta Llama 3".to_string()),
+                ..Default::default()
+            },
+            chunks: vec!["Meta Llama 3 is the next generation of Llama.".to_string()],
+        };
+        let observation = serde_json::to_string(&page).expect("Should serialize");
+        let observation = agent
+            .summarize_page(observation, "Who built Llama 3?", &CancellationToken::new())
+            .await
+            .expect("Should summarize");
+        assert_eq!(
+            observation,
+            "Scraped Introducing Meta Llama 3 (https://ai.meta.com/blog/meta-llama-3/), summary:\nMeta built Llama 3."
+        );
+        summarization.assert_async().await;
+
+        // other observations are left as they are
+        let observation = agent
+            .summarize_page(
+                "[]".to_string(),
+                "Who built Llama 3?",
+                &CancellationToken::new(),
+            )
+            .await
+            .expect("Should not summarize");
+        assert_eq!(observation, "[]");
+    }
+
+    #[test]
+    fn test_seeded_persona() {
+        let pool = PersonaPool::new(
+            ["a", "b", "c"]
+                .into_iter()
+                .map(|name| Persona {
+                    name: name.to_string(),
+                    ..Default::default()
+                })
+                .collect(),
+        )
+        .expect("Should create pool");
+
+        let agent = |seed| Agent::new_from_pool(OllamaClient::default(), vec![], &pool, seed);
+        for seed in 0..8 {
+            let name = agent(Some(seed)).get_persona().name.clone();
+            assert_eq!(agent(Some(seed)).get_persona().name, name);
+            assert_eq!(
+                pool.get_seeded_agent(seed).map(|persona| &persona.name),
+                Some(&name)
+            );
+        }
+        assert!(pool.get_agent(&agent(None).get_persona().name).is_some());
+    }
+
+    #[test]
+    fn test_transcript_serialization() {
+        let output = AgentOutput {
+            answer: "42".to_string(),
+            citations: Vec::new(),
+            transcript: Transcript {
+                model: "llama3".to_string(),
+                persona: "Researcher".to_string(),
+                steps: vec![
+                    Step::Action {
+                        tool: "DDG Searcher".to_string(),
+                        input: "{}".to_string(),
+                    },
+                    Step::Answer {
+                        content: "42".to_string(),
+                    },
+                ],
+            },
+        };
+
+        let json = serde_json::to_value(&output).expect("Should serialize");
+        assert_eq!(json["transcript"]["steps"][0]["type"], "action");
+        assert_eq!(json["transcript"]["steps"][1]["content"], "42");
+    }
+}
@@ -0,0 +1,126 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+
+use super::vectorstore::ScoredChunk;
+
+/// Maximum number of characters of a source kept within its citation.
+const MAX_SNIPPET_CHARS: usize = 300;
+
+/// A source cited within the answer, as `[number]`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Citation {
+    pub number: usize,
+    pub url: String,
+    pub title: Option<String>,
+    pub snippet: String,
+}
+
+/// Formats the chunks as sources numbered from 1, each with its title and URL.
+pub fn format_sources(chunks: &[ScoredChunk]) -> String {
+    chunks
+        .iter()
+        .enumerate()
+        .map(|(index, scored)| {
+            format!(
+                "[{}] {} ({})\n{}",
+                index + 1,
+                scored.chunk.title.as_deref().unwrap_or("Untitled"),
+                scored.chunk.url,
+                scored.chunk.text.trim()
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n\n")
+}
+
+/// Returns a citation for each source that is referenced within the answer, in order of number.
+///
+/// References to sources that do not exist are ignored.
+pub fn parse_citations(answer: &str, chunks: &[ScoredChunk]) -> Vec<Citation> {
+    let numbers: BTreeSet<usize> = answer
+        .split('[')
+        .skip(1)
+        .filter_map(|rest| rest.split_once(']'))
+        .flat_map(|(inside, _)| inside.split(','))
+        .filter_map(|number| number.trim().parse::<usize>().ok())
+        .filter(|number| (1..=chunks.len()).contains(number))
+        .collect();
+
+    numbers
+        .into_iter()
+        .map(|number| {
+            let chunk = &chunks[number - 1].chunk;
+            Citation {
+                number,
+                url: chunk.url.clone(),
+                title: chunk.title.clone(),
+                snippet: snippet(&chunk.text),
+            }
+        })
+        .collect()
+}
+
+/// Collapses whitespace and truncates the text to at most `MAX_SNIPPET_CHARS` characters.
+fn snippet(text: &str) -> String {
+    let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    match text.char_indices().nth(MAX_SNIPPET_CHARS) {
+        Some((index, _)) => format!("{}...", &text[..index]),
+        None => text,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compute::search::vectorstore::Chunk;
+
+    fn chunks() -> Vec<ScoredChunk> {
+        [
+            (
+                "https://ai.meta.com",
+                "Meta released Llama 3 on April 18, 2024.",
+            ),
+            (
+                "https://en.wikipedia.org/wiki/Llama",
+                "Llama is a family of\nlanguage models.",
+            ),
+        ]
+        .into_iter()
+        .map(|(url, text)| ScoredChunk {
+            chunk: Chunk {
+                text: text.to_string(),
+                url: url.to_string(),
+                title: None,
+                index: 0,
+            },
+            score: 1.0,
+        })
+        .collect()
+    }
+
+    #[test]
+    fn test_format_sources() {
+        let sources = format_sources(&chunks());
+        assert_eq!(
+            sources,
+            "[1] Untitled (https://ai.meta.com)\nMeta released Llama 3 on April 18, 2024.\n\n[2] Untitled (https://en.wikipedia.org/wiki/Llama)\nLlama is a family of\nlanguage models."
+        );
+    }
+
+    #[test]
+    fn test_parse_citations() {
+        let answer =
+            "Llama 3 is a language model [2] released by Meta in 2024 [1, 2][7]. See [note].";
+        let citations = parse_citations(answer, &chunks());
+
+        assert_eq!(citations.len(), 2);
+        assert_eq!(citations[0].number, 1);
+        assert_eq!(citations[0].url, "https://ai.meta.com");
+        assert_eq!(
+            citations[1].snippet,
+            "Llama is a family of language models."
+        );
+
+        assert!(parse_citations("No sources here.", &chunks()).is_empty());
+    }
+}
@@ -0,0 +1,80 @@
+use std::{fs, path::Path};
+
+use super::PersonaPool;
+use crate::errors::{NodeError, NodeResult};
+
+/// Built-in persona pool, embedded in the binary and used when no file is configured.
+const DEFAULT_PERSONAS: &str = include_str!("data.json");
+
+impl Default for PersonaPool {
+    fn default() -> Self {
+        Self::new_from_str(DEFAULT_PERSONAS).expect("Should parse built-in personas.")
+    }
+}
+
+impl PersonaPool {
+    /// Loads and validates a pool from a JSON file.
+    pub fn new_from_file(path: impl AsRef<Path>) -> NodeResult<Self> {
+        let path = path.as_ref();
+        let contents = fs::read_to_string(path).map_err(|e| {
+            NodeError::Config(format!(
+                "Could not read personas from {}: {}",
+                path.display(),
+                e
+            ))
+        })?;
+
+        let pool = Self::new_from_str(&contents)?;
+        log::info!(
+            "Loaded {} personas from {}",
+            pool.personas().len(),
+            path.display()
+        );
+        Ok(pool)
+    }
+
+    /// Parses and validates a pool from a JSON string.
+    pub fn new_from_str(contents: &str) -> NodeResult<Self> {
+        let pool: PersonaPool = serde_json::from_str(contents)?;
+        pool.validate()?;
+        Ok(pool)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_pool() {
+        let pool = PersonaPool::default();
+        assert!(pool.personas().len() > 1);
+        assert!(pool.validate().is_ok());
+    }
+
+    #[test]
+    fn test_pool_from_file() {
+        let path =
+            std::env::temp_dir().join(format!("dkn-test-personas-{}.json", std::process::id()));
+        fs::write(
+            &path,
+            r#"{"personas": [{"name": "Analyst", "background": "A financial analyst.", "search_style": "Data first."}]}"#,
+        )
+        .expect("Should write file");
+
+        let pool = PersonaPool::new_from_file(&path).expect("Should load pool");
+        let persona = pool.get_agent("Analyst").expect("Should have persona");
+        assert!(persona.preferred_sources.is_empty());
+        assert_eq!(persona.weight, 1.0);
+
+        fs::remove_file(&path).expect("Should remove file");
+        assert!(PersonaPool::new_from_file(&path).is_err());
+    }
+
+    #[test]
+    fn test_invalid_pool() {
+        assert!(PersonaPool::new_from_str(r#"{"personas": []}"#).is_err());
+        assert!(PersonaPool::new_from_str(r#"{"personas": [{"name": "x"}]}"#).is_err());
+        assert!(PersonaPool::new_from_str("not json").is_err());
+    }
+}
@@ -0,0 +1,262 @@
+mod import;
+
+use rand::{distributions::WeightedIndex, prelude::Distribution, rngs::StdRng, Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+use std::{collections::HashSet, path::PathBuf};
+
+use super::{
+    utils::{
+        cache::CacheConfig, fetcher::FetcherConfig, policy::UrlPolicy, provider::ProviderConfig,
+    },
+    vectorstore::DEFAULT_DKN_SEARCH_EMBEDDING_MODEL,
+};
+use crate::errors::{NodeError, NodeResult};
+
+/// # Search Config
+///
+/// Configuration of the search tools, along with the personas and prompts that are shared with
+/// synthesis.
+#[derive(Debug, Clone)]
+pub struct SearchConfig {
+    pub personas: PersonaPool,
+    /// Directory of prompts that override the built-in prompts.
+    pub prompts_dir: Option<PathBuf>,
+    /// Ollama model used to index scraped pages.
+    pub embedding_model: String,
+    /// Search providers in order of failover.
+    pub providers: Vec<ProviderConfig>,
+    pub fetcher: FetcherConfig,
+    pub policy: UrlPolicy,
+    pub cache: CacheConfig,
+}
+
+impl Default for SearchConfig {
+    fn default() -> Self {
+        Self {
+            personas: PersonaPool::default(),
+            prompts_dir: None,
+            embedding_model: DEFAULT_DKN_SEARCH_EMBEDDING_MODEL.to_string(),
+            providers: vec![ProviderConfig::DuckDuckGo],
+            fetcher: FetcherConfig::default(),
+            policy: UrlPolicy::default(),
+            cache: CacheConfig::default(),
+        }
+    }
+}
+
+/// # Persona
+///
+/// A persona describes how the search agent should approach a task, so that different nodes
+/// produce diverse search behaviour for the same input.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Persona {
+    /// A unique name of the persona.
+    pub name: String,
+    /// Who the persona is, and what it knows.
+    pub background: String,
+    /// How the persona searches for information, e.g. broad-first or source-first.
+    pub search_style: String,
+    /// Domains or kinds of sources the persona prefers, e.g. `arxiv.org` or "official documentation".
+    #[serde(default)]
+    pub preferred_sources: Vec<String>,
+    /// Relative weight of the persona when it is picked at random from a pool.
+    #[serde(default = "default_weight")]
+    pub weight: f64,
+}
+
+#[inline]
+fn default_weight() -> f64 {
+    1.0
+}
+
+impl Default for Persona {
+    fn default() -> Self {
+        Self {
+            name: "Researcher".to_string(),
+            background: "A meticulous generalist researcher who cross-checks facts across several independent sources.".to_string(),
+            search_style: "Start with a broad web search, then read the most relevant pages in detail before answering.".to_string(),
+            preferred_sources: Vec::new(),
+            weight: default_weight(),
+        }
+    }
+}
+
+impl Persona {
+    /// Checks that the persona is well-formed.
+    pub fn validate(&self) -> NodeResult<()> {
+        if self.name.trim().is_empty() {
+            return Err(NodeError::Config(
+                "Persona name can not be empty.".to_string(),
+            ));
+        }
+        if self.background.trim().is_empty() {
+            return Err(NodeError::Config(format!(
+                "Persona {} has an empty background.",
+                self.name
+            )));
+        }
+        if self.search_style.trim().is_empty() {
+            return Err(NodeError::Config(format!(
+                "Persona {} has an empty search style.",
+                self.name
+            )));
+        }
+        if !self.weight.is_finite() || self.weight <= 0.0 {
+            return Err(NodeError::Config(format!(
+                "Persona {} has an invalid weight {}, must be positive.",
+                self.name, self.weight
+            )));
+        }
+
+        Ok(())
+    }
+}
+
+/// # Persona Pool
+///
+/// A collection of personas, from which the search agent picks one for each task.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct PersonaPool {
+    personas: Vec<Persona>,
+}
+
+impl PersonaPool {
+    /// Creates a pool from the given personas, and validates it.
+    pub fn new(personas: Vec<Persona>) -> NodeResult<Self> {
+        let pool = Self { personas };
+        pool.validate()?;
+        Ok(pool)
+    }
+
+    /// Checks that the pool is not empty, that every persona is valid and that names are unique.
+    pub fn validate(&self) -> NodeResult<()> {
+        if self.personas.is_empty() {
+            return Err(NodeError::Config(
+                "Persona pool can not be empty.".to_string(),
+            ));
+        }
+
+        let mut names = HashSet::new();
+        for persona in &self.personas {
+            persona.validate()?;
+            if !names.insert(persona.name.as_str()) {
+                return Err(NodeError::Config(format!(
+                    "Persona {} is defined more than once.",
+                    persona.name
+                )));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Returns all personas within the pool.
+    #[inline]
+    pub fn personas(&self) -> &[Persona] {
+        &self.personas
+    }
+
+    /// Returns the persona with the given name, if it exists.
+    pub fn get_agent(&self, name: &str) -> Option<&Persona> {
+        self.personas.iter().find(|persona| persona.name == name)
+    }
+
+    /// Picks a persona at random, with respect to their weights.
+    pub fn get_random_agent(&self) -> Option<&Persona> {
+        self.get_random_agent_with(&mut rand::thread_rng())
+    }
+
+    /// Picks a persona deterministically from the given seed, with respect to their weights.
+    ///
+    /// The same seed over the same pool always results in the same persona.
+    pub fn get_seeded_agent(&self, seed: u64) -> Option<&Persona> {
+        self.get_random_agent_with(&mut StdRng::seed_from_u64(seed))
+    }
+
+    /// Picks a persona using the given random number generator, with respect to their weights.
+    pub fn get_random_agent_with<R: Rng + ?Sized>(&self, rng: &mut R) -> Option<&Persona> {
+        let weights = self.personas.iter().map(|persona| persona.weight);
+        let index = WeightedIndex::new(weights).ok()?;
+
+        self.personas.get(index.sample(rng))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn persona(name: &str, weight: f64) -> Persona {
+        Persona {
+            name: name.to_string(),
+            weight,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_persona_validation() {
+        assert!(Persona::default().validate().is_ok());
+        assert!(persona("", 1.0).validate().is_err());
+        assert!(persona("zero", 0.0).validate().is_err());
+        assert!(persona("negative", -1.0).validate().is_err());
+        assert!(persona("nan", f64::NAN).validate().is_err());
+    }
+
+    #[test]
+    fn test_pool_validation() {
+        assert!(PersonaPool::new(vec![]).is_err(), "Empty pool should fail");
+        assert!(
+            PersonaPool::new(vec![persona("a", 1.0), persona("a", 2.0)]).is_err(),
+            "Duplicate names should fail"
+        );
+        assert!(PersonaPool::new(vec![persona("a", 1.0), persona("b", 2.0)]).is_ok());
+    }
+
+    #[test]
+    fn test_seeded_selection() {
+        let pool = PersonaPool::new(vec![
+            persona("a", 1.0),
+            persona("b", 2.0),
+            persona("c", 3.0),
+        ])
+        .expect("Should create pool");
+        let pick = |seed| {
+            pool.get_seeded_agent(seed)
+                .expect("Should pick")
+                .name
+                .as_str()
+        };
+
+        // a seed always picks the same persona
+        for seed in 0..64 {
+            assert_eq!(pick(seed), pick(seed));
+        }
+
+        // and personas are picked across seeds with respect to their weights
+        let draws = 3000;
+        for (name, expected) in [("a", 1.0 / 6.0), ("b", 2.0 / 6.0), ("c", 3.0 / 6.0)] {
+            let count = (0..draws).filter(|&seed| pick(seed) == name).count();
+            let frequency = count as f64 / draws as f64;
+            assert!(
+                (frequency - expected).abs() < 0.05,
+                "Picked {} with frequency {}",
+                name,
+                frequency
+            );
+        }
+    }
+
+    #[test]
+    fn test_weighted_selection() {
+        let pool = PersonaPool::new(vec![persona("rare", 1.0), persona("common", 99.0)])
+            .expect("Should create pool");
+
+        let mut rng = StdRng::seed_from_u64(42);
+        let common_count = (0..1000)
+            .filter_map(|_| pool.get_random_agent_with(&mut rng))
+            .filter(|persona| persona.name == "common")
+            .count();
+        assert!(common_count > 950, "Picked common {} times", common_count);
+    }
+}
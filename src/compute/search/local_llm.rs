@@ -15,9 +15,7 @@ impl LocalLLM {
                     .with_api_key("ollama"),
             )
             .with_model(model_name);
-        Self {
-            model: ollama
-        }
+        Self { model: ollama }
     }
 
     pub async fn generate(&self, prompt: &str) -> Result<String, String> {
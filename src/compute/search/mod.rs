@@ -1,3 +1,7 @@
+pub mod agent;
+pub mod answer;
+pub mod config;
 pub mod local_llm;
+pub mod tools;
+pub mod utils;
 pub mod vectorstore;
-pub mod tools;
\ No newline at end of file
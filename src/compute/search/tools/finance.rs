@@ -0,0 +1,844 @@
+use async_trait::async_trait;
+use langchain_rust::tools::Tool;
+use reqwest::Client;
+use scraper::{Html, Selector};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use std::{error::Error, sync::Arc, time::Duration};
+
+use super::parse_tool_input;
+use crate::{
+    compute::search::utils::{
+        cache::ContentCache,
+        policy::{guarded_client_builder, UrlPolicy},
+    },
+    errors::{NodeError, NodeResult},
+};
+
+/// Yahoo Finance chart API, the ticker is appended to this URL.
+const YAHOO_CHART_URL: &str = "https://query1.finance.yahoo.com/v8/finance/chart";
+
+/// Yahoo Finance quote page, the ticker is appended to this URL.
+const YAHOO_QUOTE_URL: &str = "https://finance.yahoo.com/quote";
+
+/// Market data goes stale quickly, so it is cached for at most this long.
+const QUOTE_CACHE_TTL: Duration = Duration::from_secs(60);
+
+/// Ranges of history that can be requested, the first is used for quotes.
+const RANGES: [&str; 7] = ["1d", "5d", "1mo", "3mo", "6mo", "1y", "5y"];
+const DEFAULT_RANGE: &str = "1mo";
+
+/// Intervals between the candles of a history.
+const INTERVALS: [&str; 3] = ["1d", "1wk", "1mo"];
+const DEFAULT_INTERVAL: &str = "1d";
+
+/// Maximum number of candles returned, the latest ones are kept.
+const MAX_CANDLES: usize = 60;
+
+/// Maximum length of a ticker symbol.
+const MAX_TICKER_LEN: usize = 12;
+
+/// Kind of market data requested from the tool.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum StockData {
+    /// Latest price and daily trading data.
+    #[default]
+    Quote,
+    /// Open, high, low and close prices over a range.
+    History,
+    /// Valuation metrics such as market cap and P/E ratio.
+    Fundamentals,
+}
+
+impl StockData {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Quote => "quote",
+            Self::History => "history",
+            Self::Fundamentals => "fundamentals",
+        }
+    }
+}
+
+/// A validated request to the tool.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StockRequest {
+    /// Upper-case ticker symbol.
+    pub ticker: String,
+    pub data: StockData,
+    /// Range of the history, one of `RANGES`.
+    pub range: String,
+    /// Interval of the history, one of `INTERVALS`.
+    pub interval: String,
+}
+
+impl StockRequest {
+    /// Validates the tool input against the parameter schema, and fills in the defaults.
+    pub fn from_input(input: &Value) -> NodeResult<Self> {
+        let ticker = input["ticker"]
+            .as_str()
+            .map(|ticker| ticker.trim().to_uppercase())
+            .ok_or_else(|| NodeError::tool("Ticker symbol is required"))?;
+        if ticker.is_empty()
+            || ticker.len() > MAX_TICKER_LEN
+            || !ticker
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || ".-^=".contains(c))
+        {
+            return Err(NodeError::tool(format!(
+                "Invalid ticker symbol {:?}",
+                ticker
+            )));
+        }
+
+        let data = match input.get("data") {
+            None | Some(Value::Null) => StockData::default(),
+            Some(data) => serde_json::from_value(data.clone()).map_err(|_| {
+                NodeError::tool(format!(
+                    "Invalid data {}, expected one of quote, history or fundamentals",
+                    data
+                ))
+            })?,
+        };
+
+        let range = choice(input, "range", &RANGES, DEFAULT_RANGE)?;
+        let interval = choice(input, "interval", &INTERVALS, DEFAULT_INTERVAL)?;
+
+        Ok(Self {
+            ticker,
+            data,
+            range,
+            interval,
+        })
+    }
+}
+
+/// Reads an optional string parameter that must be one of the given choices.
+fn choice(input: &Value, key: &str, choices: &[&str], default: &str) -> NodeResult<String> {
+    match input.get(key) {
+        None | Some(Value::Null) => Ok(default.to_string()),
+        Some(Value::String(value)) if choices.contains(&value.as_str()) => Ok(value.clone()),
+        Some(value) => Err(NodeError::tool(format!(
+            "Invalid {} {}, expected one of {}",
+            key,
+            value,
+            choices.join(", ")
+        ))),
+    }
+}
+
+/// Latest price and daily trading data of a stock.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Quote {
+    pub symbol: String,
+    pub currency: Option<String>,
+    pub exchange: Option<String>,
+    pub price: f64,
+    pub previous_close: Option<f64>,
+    pub change: Option<f64>,
+    pub change_percent: Option<f64>,
+    pub day_high: Option<f64>,
+    pub day_low: Option<f64>,
+    pub volume: Option<u64>,
+    pub fifty_two_week_high: Option<f64>,
+    pub fifty_two_week_low: Option<f64>,
+    /// Time of the price, in RFC 3339.
+    pub market_time: Option<String>,
+}
+
+/// Prices of a stock within a single interval.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Candle {
+    /// Start of the interval, as `YYYY-MM-DD`.
+    pub date: String,
+    pub open: f64,
+    pub high: f64,
+    pub low: f64,
+    pub close: f64,
+    pub volume: Option<u64>,
+}
+
+/// Candles of a stock over a range, oldest first.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct History {
+    pub symbol: String,
+    pub currency: Option<String>,
+    pub range: String,
+    pub interval: String,
+    pub candles: Vec<Candle>,
+}
+
+/// Valuation metrics of a stock, any of which may be missing.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct Fundamentals {
+    pub symbol: String,
+    pub market_cap: Option<f64>,
+    pub pe_ratio: Option<f64>,
+    pub eps: Option<f64>,
+    pub beta: Option<f64>,
+    pub dividend_rate: Option<f64>,
+    /// Dividend yield, in percent.
+    pub dividend_yield: Option<f64>,
+    pub fifty_two_week_low: Option<f64>,
+    pub fifty_two_week_high: Option<f64>,
+    pub target_price: Option<f64>,
+}
+
+/// Output of the tool, tagged by the kind of data.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "data", rename_all = "snake_case")]
+pub enum StockReport {
+    Quote(Quote),
+    History(History),
+    Fundamentals(Fundamentals),
+}
+
+/// A tool that returns quotes, price history and fundamentals of a stock from Yahoo Finance.
+#[derive(Debug, Clone)]
+pub struct StockScraper {
+    client: Client,
+    chart_url: String,
+    quote_url: String,
+    cache: Option<Arc<ContentCache>>,
+}
+
+impl Default for StockScraper {
+    fn default() -> Self {
+        Self::new(UrlPolicy::default()).expect("Should create HTTP client.")
+    }
+}
+
+impl StockScraper {
+    /// The URL policy is enforced on redirects and resolved addresses.
+    pub fn new(policy: UrlPolicy) -> NodeResult<Self> {
+        Self::with_urls(policy, YAHOO_CHART_URL, YAHOO_QUOTE_URL)
+    }
+
+    fn with_urls(policy: UrlPolicy, chart_url: &str, quote_url: &str) -> NodeResult<Self> {
+        Ok(Self {
+            client: guarded_client_builder(Arc::new(policy)).build()?,
+            chart_url: chart_url.to_string(),
+            quote_url: quote_url.to_string(),
+            cache: None,
+        })
+    }
+
+    /// Caches the market data of each request within the given cache.
+    pub fn with_cache(mut self, cache: Arc<ContentCache>) -> Self {
+        self.cache = Some(cache);
+        self
+    }
+
+    /// Returns the requested market data, from the cache if possible.
+    pub async fn report(&self, request: &StockRequest) -> NodeResult<StockReport> {
+        let key = format!(
+            "stock:{}:{}:{}:{}",
+            request.data.as_str(),
+            request.ticker,
+            request.range,
+            request.interval
+        );
+        if let Some(cache) = &self.cache {
+            if let Some(report) = cache.get(&key).await {
+                if let Ok(report) = serde_json::from_str(&report) {
+                    return Ok(report);
+                }
+            }
+        }
+
+        let report = match request.data {
+            StockData::Quote => StockReport::Quote(self.quote(&request.ticker).await?),
+            StockData::History => StockReport::History(
+                self.history(&request.ticker, &request.range, &request.interval)
+                    .await?,
+            ),
+            StockData::Fundamentals => {
+                StockReport::Fundamentals(self.fundamentals(&request.ticker).await?)
+            }
+        };
+
+        if let Some(cache) = &self.cache {
+            let ttl = cache.ttl().min(QUOTE_CACHE_TTL);
+            cache
+                .insert(&key, serde_json::to_string(&report)?, ttl)
+                .await;
+        }
+
+        Ok(report)
+    }
+
+    /// Returns the latest quote of the ticker.
+    pub async fn quote(&self, ticker: &str) -> NodeResult<Quote> {
+        let chart = self.chart(ticker, RANGES[0], DEFAULT_INTERVAL).await?;
+        parse_chart_quote(chart)
+    }
+
+    /// Returns the candles of the ticker over the range, at most `MAX_CANDLES` of the latest.
+    pub async fn history(&self, ticker: &str, range: &str, interval: &str) -> NodeResult<History> {
+        let chart = self.chart(ticker, range, interval).await?;
+        Ok(parse_chart_history(chart, range, interval))
+    }
+
+    /// Returns the fundamentals of the ticker, scraped from its quote page.
+    pub async fn fundamentals(&self, ticker: &str) -> NodeResult<Fundamentals> {
+        let url = format!("{}/{}/", self.quote_url, urlencoding::encode(ticker));
+        let response = self
+            .client
+            .get(&url)
+            .header("Accept", "text/html")
+            .send()
+            .await?;
+        if !response.status().is_success() {
+            return Err(NodeError::tool(format!(
+                "Yahoo Finance responded with {} for the quote page of {}",
+                response.status(),
+                ticker
+            )));
+        }
+
+        let body = response.text().await?;
+        parse_fundamentals(&body, ticker)
+            .ok_or_else(|| NodeError::tool(format!("No fundamentals found for {}", ticker)))
+    }
+
+    async fn chart(&self, ticker: &str, range: &str, interval: &str) -> NodeResult<ChartResult> {
+        let url = format!("{}/{}", self.chart_url, urlencoding::encode(ticker));
+        let response = self
+            .client
+            .get(&url)
+            .query(&[("range", range), ("interval", interval)])
+            .header("Accept", "application/json")
+            .send()
+            .await?;
+
+        let status = response.status();
+        let body = response.text().await?;
+        let chart = serde_json::from_str::<ChartResponse>(&body).map(|response| response.chart);
+
+        // an error object explains more than the status, so the status is reported only without one
+        if !status.is_success() && chart.as_ref().map_or(true, |chart| chart.error.is_none()) {
+            return Err(NodeError::tool(format!(
+                "Yahoo Finance responded with {} for {}",
+                status, ticker
+            )));
+        }
+
+        let chart = chart.map_err(|e| {
+            NodeError::tool(format!("Unexpected chart response for {}: {}", ticker, e))
+        })?;
+        parse_chart(chart, ticker)
+    }
+}
+
+#[async_trait]
+impl Tool for StockScraper {
+    fn name(&self) -> String {
+        "Stock Scraper".to_string()
+    }
+
+    fn description(&self) -> String {
+        "Returns market data of a stock given its ticker symbol: the latest quote, the price history, or fundamentals such as market cap and P/E ratio."
+            .to_string()
+    }
+
+    fn parameters(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "ticker": {
+                    "type": "string",
+                    "pattern": "^[A-Za-z0-9.^=-]{1,12}$",
+                    "description": "The ticker symbol of the stock, e.g. AAPL"
+                },
+                "data": {
+                    "type": "string",
+                    "enum": ["quote", "history", "fundamentals"],
+                    "default": "quote",
+                    "description": "The latest quote, the price history, or the fundamentals"
+                },
+                "range": {
+                    "type": "string",
+                    "enum": RANGES,
+                    "default": DEFAULT_RANGE,
+                    "description": "Range of the price history"
+                },
+                "interval": {
+                    "type": "string",
+                    "enum": INTERVALS,
+                    "default": DEFAULT_INTERVAL,
+                    "description": "Interval between the prices of the history"
+                }
+            },
+            "required": ["ticker"],
+            "additionalProperties": false
+        })
+    }
+
+    async fn parse_input(&self, input: &str) -> Value {
+        parse_tool_input(input, "ticker")
+    }
+
+    async fn run(&self, input: Value) -> Result<String, Box<dyn Error>> {
+        let request = StockRequest::from_input(&input)?;
+        let report = self.report(&request).await?;
+
+        Ok(serde_json::to_string(&report)?)
+    }
+}
+
+#[derive(Deserialize)]
+struct ChartResponse {
+    chart: Chart,
+}
+
+#[derive(Deserialize)]
+struct Chart {
+    result: Option<Vec<ChartResult>>,
+    error: Option<ChartError>,
+}
+
+#[derive(Deserialize)]
+struct ChartError {
+    code: String,
+    description: String,
+}
+
+#[derive(Deserialize)]
+struct ChartResult {
+    meta: ChartMeta,
+    #[serde(default)]
+    timestamp: Vec<i64>,
+    indicators: ChartIndicators,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ChartMeta {
+    symbol: String,
+    currency: Option<String>,
+    exchange_name: Option<String>,
+    regular_market_price: Option<f64>,
+    previous_close: Option<f64>,
+    chart_previous_close: Option<f64>,
+    regular_market_day_high: Option<f64>,
+    regular_market_day_low: Option<f64>,
+    regular_market_volume: Option<u64>,
+    fifty_two_week_high: Option<f64>,
+    fifty_two_week_low: Option<f64>,
+    regular_market_time: Option<i64>,
+}
+
+#[derive(Deserialize)]
+struct ChartIndicators {
+    #[serde(default)]
+    quote: Vec<ChartQuote>,
+}
+
+#[derive(Deserialize, Default)]
+struct ChartQuote {
+    #[serde(default)]
+    open: Vec<Option<f64>>,
+    #[serde(default)]
+    high: Vec<Option<f64>>,
+    #[serde(default)]
+    low: Vec<Option<f64>>,
+    #[serde(default)]
+    close: Vec<Option<f64>>,
+    #[serde(default)]
+    volume: Vec<Option<u64>>,
+}
+
+/// Returns the result of a chart, where an error object or a missing result is an error.
+fn parse_chart(chart: Chart, ticker: &str) -> NodeResult<ChartResult> {
+    if let Some(error) = chart.error {
+        return Err(NodeError::tool(format!(
+            "Yahoo Finance error for {}: {}: {}",
+            ticker, error.code, error.description
+        )));
+    }
+
+    chart
+        .result
+        .and_then(|results| results.into_iter().next())
+        .ok_or_else(|| NodeError::tool(format!("No market data found for {}", ticker)))
+}
+
+fn parse_chart_quote(chart: ChartResult) -> NodeResult<Quote> {
+    let meta = chart.meta;
+    let price = meta
+        .regular_market_price
+        .ok_or_else(|| NodeError::tool(format!("No price found for {}", meta.symbol)))?;
+    let previous_close = meta.previous_close.or(meta.chart_previous_close);
+    let change = previous_close.map(|previous| round(price - previous));
+    let change_percent = previous_close
+        .filter(|previous| *previous != 0.0)
+        .map(|previous| round((price - previous) / previous * 100.0));
+
+    Ok(Quote {
+        symbol: meta.symbol,
+        currency: meta.currency,
+        exchange: meta.exchange_name,
+        price,
+        previous_close,
+        change,
+        change_percent,
+        day_high: meta.regular_market_day_high,
+        day_low: meta.regular_market_day_low,
+        volume: meta.regular_market_volume,
+        fifty_two_week_high: meta.fifty_two_week_high,
+        fifty_two_week_low: meta.fifty_two_week_low,
+        market_time: meta
+            .regular_market_time
+            .and_then(|time| chrono::DateTime::from_timestamp(time, 0))
+            .map(|time| time.to_rfc3339()),
+    })
+}
+
+/// Zips the chart indicators into candles, skipping those with a missing price.
+fn parse_chart_history(chart: ChartResult, range: &str, interval: &str) -> History {
+    let quote = chart
+        .indicators
+        .quote
+        .into_iter()
+        .next()
+        .unwrap_or_default();
+    let mut candles: Vec<Candle> = chart
+        .timestamp
+        .iter()
+        .enumerate()
+        .filter_map(|(i, &time)| {
+            Some(Candle {
+                date: chrono::DateTime::from_timestamp(time, 0)?
+                    .format("%Y-%m-%d")
+                    .to_string(),
+                open: round((*quote.open.get(i)?)?),
+                high: round((*quote.high.get(i)?)?),
+                low: round((*quote.low.get(i)?)?),
+                close: round((*quote.close.get(i)?)?),
+                volume: quote.volume.get(i).copied().flatten(),
+            })
+        })
+        .collect();
+
+    if candles.len() > MAX_CANDLES {
+        candles.drain(..candles.len() - MAX_CANDLES);
+    }
+
+    History {
+        symbol: chart.meta.symbol,
+        currency: chart.meta.currency,
+        range: range.to_string(),
+        interval: interval.to_string(),
+        candles,
+    }
+}
+
+/// Rounds prices to 4 decimals, as the API returns them as imprecise floats.
+#[inline]
+fn round(value: f64) -> f64 {
+    (value * 10_000.0).round() / 10_000.0
+}
+
+/// Parses the `fin-streamer` elements of a Yahoo Finance quote page that belong to the given ticker.
+///
+/// Each element is keyed by its `data-field` attribute, e.g. `regularMarketPrice`, and its value is
+/// read from the `data-value` attribute, falling back to the element text.
+pub(crate) fn parse_quote(html: &str, ticker: &str) -> Map<String, Value> {
+    let document = Html::parse_document(html);
+    let selector = Selector::parse("fin-streamer[data-field]").expect("Should parse selector");
+
+    let mut fields = Map::new();
+    for element in document.select(&selector) {
+        if element.value().attr("data-symbol") != Some(ticker) {
+            continue;
+        }
+
+        let Some(field) = element.value().attr("data-field") else {
+            continue;
+        };
+
+        // keep the first occurrence, the quote header comes before the other widgets
+        if fields.contains_key(field) {
+            continue;
+        }
+
+        let value = match element.value().attr("data-value") {
+            Some(value) => value.to_string(),
+            None => element.text().collect::<String>().trim().to_string(),
+        };
+        fields.insert(field.to_string(), Value::String(value));
+    }
+
+    fields
+}
+
+/// Parses the fundamentals from the market cap of the quote header and the statistics table of a
+/// Yahoo Finance quote page, returning `None` if the page has no data for the ticker.
+pub(crate) fn parse_fundamentals(html: &str, ticker: &str) -> Option<Fundamentals> {
+    let fields = parse_quote(html, ticker);
+    if fields.is_empty() {
+        return None;
+    }
+
+    let document = Html::parse_document(html);
+    let statistic = |name: &str| {
+        let selector = Selector::parse(&format!("[data-test=\"{}-value\"]", name))
+            .expect("Should parse selector");
+        document
+            .select(&selector)
+            .next()
+            .map(|element| element.text().collect::<String>().trim().to_string())
+    };
+
+    // dividends are given as "0.96 (0.51%)" and the yearly range as "164.08 - 199.62"
+    let dividend = statistic("DIVIDEND_AND_YIELD").unwrap_or_default();
+    let (dividend_rate, dividend_yield) = dividend.split_once('(').unwrap_or((&dividend, ""));
+    let range = statistic("FIFTY_TWO_WK_RANGE").unwrap_or_default();
+    let (low, high) = range.split_once(" - ").unwrap_or_default();
+
+    Some(Fundamentals {
+        symbol: ticker.to_string(),
+        market_cap: fields
+            .get("marketCap")
+            .and_then(Value::as_str)
+            .and_then(parse_number),
+        pe_ratio: statistic("PE_RATIO").as_deref().and_then(parse_number),
+        eps: statistic("EPS_RATIO").as_deref().and_then(parse_number),
+        beta: statistic("BETA_5Y").as_deref().and_then(parse_number),
+        dividend_rate: parse_number(dividend_rate),
+        dividend_yield: parse_number(dividend_yield.trim_end_matches(')')),
+        fifty_two_week_low: parse_number(low),
+        fifty_two_week_high: parse_number(high),
+        target_price: statistic("ONE_YEAR_TARGET_PRICE")
+            .as_deref()
+            .and_then(parse_number),
+    })
+}
+
+/// Parses a number such as `1,234.5` or `0.51%`, where values such as `N/A` are `None`.
+fn parse_number(text: &str) -> Option<f64> {
+    text.trim()
+        .trim_end_matches('%')
+        .replace(',', "")
+        .parse::<f64>()
+        .ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const QUOTE_HTML: &str = include_str!(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/fixtures/yahoo_quote.html"
+    ));
+    const CHART_JSON: &str = include_str!(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/fixtures/yahoo_chart.json"
+    ));
+    const CHART_ERROR_JSON: &str = include_str!(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/fixtures/yahoo_chart_error.json"
+    ));
+
+    /// Serves the saved responses, from a local server so private networks must be allowed.
+    fn scraper(server: &mockito::Server) -> StockScraper {
+        let policy = UrlPolicy {
+            allow_private_networks: true,
+            ..Default::default()
+        };
+        StockScraper::with_urls(
+            policy,
+            &format!("{}/chart", server.url()),
+            &format!("{}/quote", server.url()),
+        )
+        .expect("Should create scraper")
+    }
+
+    #[test]
+    fn test_parse_quote() {
+        let fields = parse_quote(QUOTE_HTML, "AAPL");
+        assert_eq!(fields["regularMarketPrice"], "189.98");
+        assert_eq!(fields["regularMarketChange"], "1.22");
+        assert_eq!(fields["regularMarketVolume"], "46422362");
+        assert_eq!(fields["marketCap"], "2913105985536");
+
+        // other tickers in the page are not included
+        assert_eq!(fields.len(), 4);
+    }
+
+    #[test]
+    fn test_parse_unknown_quote() {
+        assert!(parse_quote(QUOTE_HTML, "MSFT").is_empty());
+    }
+
+    #[test]
+    fn test_parse_fundamentals() {
+        let fundamentals = parse_fundamentals(QUOTE_HTML, "AAPL").expect("Should parse");
+        assert_eq!(
+            fundamentals,
+            Fundamentals {
+                symbol: "AAPL".to_string(),
+                market_cap: Some(2913105985536.0),
+                pe_ratio: Some(29.56),
+                eps: Some(6.43),
+                beta: Some(1.26),
+                dividend_rate: Some(0.96),
+                dividend_yield: Some(0.51),
+                fifty_two_week_low: Some(164.08),
+                fifty_two_week_high: Some(199.62),
+                target_price: None,
+            }
+        );
+
+        assert!(parse_fundamentals(QUOTE_HTML, "MSFT").is_none());
+    }
+
+    #[test]
+    fn test_request_validation() {
+        let request = StockRequest::from_input(&json!({ "ticker": " aapl " })).expect("Valid");
+        assert_eq!(
+            request,
+            StockRequest {
+                ticker: "AAPL".to_string(),
+                data: StockData::Quote,
+                range: DEFAULT_RANGE.to_string(),
+                interval: DEFAULT_INTERVAL.to_string(),
+            }
+        );
+
+        let request = StockRequest::from_input(
+            &json!({ "ticker": "BRK-B", "data": "history", "range": "1y", "interval": "1wk" }),
+        )
+        .expect("Valid");
+        assert_eq!(request.data, StockData::History);
+        assert_eq!(request.range, "1y");
+
+        for input in [
+            json!({}),
+            json!({ "ticker": "" }),
+            json!({ "ticker": "AAPL/../../admin" }),
+            json!({ "ticker": "AAPL", "data": "news" }),
+            json!({ "ticker": "AAPL", "range": "10y" }),
+            json!({ "ticker": "AAPL", "interval": 5 }),
+        ] {
+            assert!(
+                StockRequest::from_input(&input).is_err(),
+                "{} should be invalid",
+                input
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_quote_and_history() {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("GET", "/chart/AAPL")
+            .match_query(mockito::Matcher::Any)
+            .with_header("content-type", "application/json")
+            .with_body(CHART_JSON)
+            .expect(2)
+            .create_async()
+            .await;
+        let scraper = scraper(&server);
+
+        let quote = scraper.quote("AAPL").await.expect("Should get quote");
+        assert_eq!(quote.price, 183.38);
+        assert_eq!(quote.previous_close, Some(173.03));
+        assert_eq!(quote.change, Some(10.35));
+        assert_eq!(quote.change_percent, Some(5.9816));
+        assert_eq!(quote.volume, Some(163224109));
+        assert_eq!(quote.exchange.as_deref(), Some("NMS"));
+        assert_eq!(
+            quote.market_time.as_deref(),
+            Some("2024-05-03T20:00:01+00:00")
+        );
+
+        let history = scraper
+            .history("AAPL", "5d", "1d")
+            .await
+            .expect("Should get history");
+        assert_eq!(history.candles.len(), 4, "Candle without a high is skipped");
+        assert_eq!(
+            history.candles[0],
+            Candle {
+                date: "2024-04-29".to_string(),
+                open: 173.37,
+                high: 176.03,
+                low: 173.1,
+                close: 173.5,
+                volume: Some(68169400),
+            }
+        );
+        assert_eq!(history.candles[3].date, "2024-05-03");
+
+        mock.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_tool_output() {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("GET", "/quote/AAPL/")
+            .with_header("content-type", "text/html")
+            .with_body(QUOTE_HTML)
+            .create_async()
+            .await;
+        let scraper = scraper(&server).with_cache(Arc::new(ContentCache::default()));
+
+        let input = json!({ "ticker": "aapl", "data": "fundamentals" });
+        let output = scraper.run(input.clone()).await.expect("Should run");
+        let report: Value = serde_json::from_str(&output).expect("Should parse");
+        assert_eq!(report["data"], "fundamentals");
+        assert_eq!(report["pe_ratio"], 29.56);
+
+        // the second call is served from the cache
+        scraper.run(input).await.expect("Should run");
+        mock.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_provider_errors() {
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock("GET", "/chart/NOPE")
+            .match_query(mockito::Matcher::Any)
+            .with_status(404)
+            .with_header("content-type", "application/json")
+            .with_body(CHART_ERROR_JSON)
+            .create_async()
+            .await;
+        server
+            .mock("GET", "/chart/AAPL")
+            .match_query(mockito::Matcher::Any)
+            .with_status(429)
+            .with_body("Too Many Requests")
+            .create_async()
+            .await;
+        server
+            .mock("GET", "/quote/AAPL/")
+            .with_status(503)
+            .create_async()
+            .await;
+        let scraper = scraper(&server);
+
+        let error = scraper.quote("NOPE").await.expect_err("Should fail");
+        assert!(
+            error.to_string().contains("Not Found: No data found"),
+            "{}",
+            error
+        );
+
+        let error = scraper.quote("AAPL").await.expect_err("Should fail");
+        assert!(
+            error.to_string().contains("429 Too Many Requests"),
+            "{}",
+            error
+        );
+
+        let error = scraper.fundamentals("AAPL").await.expect_err("Should fail");
+        assert!(error.to_string().contains("503"), "{}", error);
+    }
+}
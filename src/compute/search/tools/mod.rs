@@ -0,0 +1,40 @@
+pub mod finance;
+pub mod page_search;
+pub mod scraper;
+pub mod search_ddg;
+
+pub use self::finance::StockScraper;
+pub use self::page_search::PageSearcher;
+pub use self::scraper::Scraper;
+pub use self::search_ddg::DDGSearcher;
+
+use serde_json::{json, Value};
+
+/// Parses the raw tool input given by the LLM into a JSON object.
+///
+/// Models do not always respect the parameter schema, so a plain string input is wrapped
+/// as the value of the given `key` instead of being rejected.
+pub(crate) fn parse_tool_input(input: &str, key: &str) -> Value {
+    match serde_json::from_str::<Value>(input) {
+        Ok(Value::Object(object)) => Value::Object(object),
+        Ok(Value::String(value)) => json!({ key: value }),
+        _ => json!({ key: input.trim() }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_tool_input() {
+        let input = parse_tool_input(r#"{"query": "llama3"}"#, "query");
+        assert_eq!(input["query"], "llama3");
+
+        let input = parse_tool_input(r#""llama3""#, "query");
+        assert_eq!(input["query"], "llama3");
+
+        let input = parse_tool_input(" who built llama3? ", "query");
+        assert_eq!(input["query"], "who built llama3?");
+    }
+}
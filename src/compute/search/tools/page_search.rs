@@ -0,0 +1,118 @@
+use async_trait::async_trait;
+use langchain_rust::tools::Tool;
+use serde_json::{json, Value};
+use std::error::Error;
+use std::sync::Arc;
+
+use super::parse_tool_input;
+use crate::compute::search::vectorstore::VectorStore;
+
+/// Default number of passages returned for a query.
+const DEFAULT_TOP_K: usize = 4;
+
+/// A tool that searches the pages scraped so far within a task, and returns the passages most
+/// relevant to a query along with their sources.
+#[derive(Clone)]
+pub struct PageSearcher {
+    store: Arc<VectorStore>,
+    top_k: usize,
+}
+
+impl PageSearcher {
+    pub fn new(store: Arc<VectorStore>) -> Self {
+        Self {
+            store,
+            top_k: DEFAULT_TOP_K,
+        }
+    }
+
+    /// Sets the number of passages returned for a query.
+    pub fn with_top_k(mut self, top_k: usize) -> Self {
+        self.top_k = top_k;
+        self
+    }
+}
+
+#[async_trait]
+impl Tool for PageSearcher {
+    fn name(&self) -> String {
+        "Page Search".to_string()
+    }
+
+    fn description(&self) -> String {
+        "Searches every page scraped so far and returns the passages most relevant to a query, along with their URLs."
+            .to_string()
+    }
+
+    fn parameters(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "query": {
+                    "type": "string",
+                    "description": "What to look for within the scraped pages"
+                }
+            },
+            "required": ["query"]
+        })
+    }
+
+    async fn parse_input(&self, input: &str) -> Value {
+        parse_tool_input(input, "query")
+    }
+
+    async fn run(&self, input: Value) -> Result<String, Box<dyn Error>> {
+        let query = input["query"].as_str().ok_or("Search query is required")?;
+        if self.store.is_empty() {
+            return Ok("No pages have been scraped yet, scrape a website first.".to_string());
+        }
+
+        let results = self.store.search(query, self.top_k).await?;
+        Ok(serde_json::to_string(&results)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compute::search::vectorstore::{tests::KeywordEmbedder, Chunk, Similarity};
+
+    #[tokio::test]
+    async fn test_page_search() {
+        let store = Arc::new(VectorStore::new(
+            Arc::new(KeywordEmbedder),
+            Similarity::Cosine,
+        ));
+        let searcher = PageSearcher::new(store.clone()).with_top_k(1);
+
+        let output = searcher
+            .run(json!({ "query": "llama" }))
+            .await
+            .expect("Should run");
+        assert!(output.starts_with("No pages"));
+
+        store
+            .add_chunks(vec![
+                Chunk {
+                    text: "Tokio is an async runtime for Rust.".to_string(),
+                    url: "https://tokio.rs".to_string(),
+                    title: Some("Tokio".to_string()),
+                    index: 0,
+                },
+                Chunk {
+                    text: "Llama 3 was released by Meta.".to_string(),
+                    url: "https://ai.meta.com".to_string(),
+                    title: Some("Llama 3".to_string()),
+                    index: 0,
+                },
+            ])
+            .await
+            .expect("Should add chunks");
+
+        let output = searcher.call("who made llama").await.expect("Should run");
+        let results: Value = serde_json::from_str(&output).expect("Should parse");
+        assert_eq!(results.as_array().map(Vec::len), Some(1));
+        assert_eq!(results[0]["url"], "https://ai.meta.com");
+        assert_eq!(results[0]["title"], "Llama 3");
+    }
+}
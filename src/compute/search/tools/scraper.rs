@@ -0,0 +1,163 @@
+use async_trait::async_trait;
+use langchain_rust::tools::Tool;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::error::Error;
+use std::sync::Arc;
+use text_splitter::MarkdownSplitter;
+
+use super::parse_tool_input;
+use crate::compute::search::utils::{
+    extract::{extract, ExtractedPage, PageMetadata},
+    fetcher::{HttpFetcher, Page, PageFetcher},
+    policy::UrlPolicy,
+};
+
+/// Maximum number of characters within a single chunk of scraped text.
+const CHUNK_SIZE: usize = 1000;
+
+/// The scraped content of a website, as returned to the agent.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ScrapedPage {
+    pub url: String,
+    #[serde(flatten)]
+    pub metadata: PageMetadata,
+    pub chunks: Vec<String>,
+}
+
+/// A tool that scrapes the main content of a website, using the configured page fetcher.
+#[derive(Clone)]
+pub struct Scraper {
+    fetcher: Arc<dyn PageFetcher>,
+}
+
+impl Default for Scraper {
+    /// Fetches pages directly over HTTP with the default limits and policy, see
+    /// [`fetcher_from_config`] for the configured fetcher.
+    ///
+    /// [`fetcher_from_config`]: crate::compute::search::utils::fetcher::fetcher_from_config
+    fn default() -> Self {
+        let fetcher = HttpFetcher::new(Default::default(), UrlPolicy::default())
+            .expect("Should create HTTP fetcher.");
+
+        Self::new(Arc::new(fetcher))
+    }
+}
+
+impl Scraper {
+    pub fn new(fetcher: Arc<dyn PageFetcher>) -> Self {
+        Self { fetcher }
+    }
+}
+
+#[async_trait]
+impl Tool for Scraper {
+    fn name(&self) -> String {
+        "Website Scraper".to_string()
+    }
+
+    fn description(&self) -> String {
+        "Scrapes the main content of a website as Markdown split into chunks, along with its title, canonical URL and publish date."
+            .to_string()
+    }
+
+    fn parameters(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "website": {
+                    "type": "string",
+                    "description": "The URL of the website to scrape"
+                }
+            },
+            "required": ["website"]
+        })
+    }
+
+    async fn parse_input(&self, input: &str) -> Value {
+        parse_tool_input(input, "website")
+    }
+
+    async fn run(&self, input: Value) -> Result<String, Box<dyn Error>> {
+        let website = input["website"].as_str().ok_or("Website URL is required")?;
+        let page = self.fetcher.fetch(website).await?;
+
+        Ok(serde_json::to_string(&scrape_page(page))?)
+    }
+}
+
+/// Extracts the content of a fetched page, and splits it into chunks.
+///
+/// Only HTML is extracted, other content such as plain text or JSON is chunked as is.
+pub(crate) fn scrape_page(page: Page) -> ScrapedPage {
+    let extracted = match page.content_type.as_str() {
+        "text/plain" | "application/json" => ExtractedPage {
+            markdown: page.body,
+            ..Default::default()
+        },
+        _ => extract(&page.body, Some(&page.url)),
+    };
+
+    ScrapedPage {
+        url: page.url,
+        metadata: extracted.metadata,
+        chunks: chunk_text(&extracted.markdown),
+    }
+}
+
+/// Splits the Markdown into chunks of at most `CHUNK_SIZE` characters, along its structure.
+pub(crate) fn chunk_text(markdown: &str) -> Vec<String> {
+    MarkdownSplitter::new(CHUNK_SIZE)
+        .chunks(markdown)
+        .map(|s| s.to_string())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ARTICLE_HTML: &str = include_str!(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/fixtures/article.html"
+    ));
+
+    fn page(body: &str) -> Page {
+        Page {
+            url: "https://ai.meta.com/blog/meta-llama-3/".to_string(),
+            content_type: "text/html".to_string(),
+            body: body.to_string(),
+            cache_control: None,
+        }
+    }
+
+    #[test]
+    fn test_scrape_page() {
+        let scraped = scrape_page(page(ARTICLE_HTML));
+
+        assert_eq!(
+            scraped.metadata.title.as_deref(),
+            Some("Introducing Meta Llama 3: The most capable openly available LLM to date")
+        );
+        assert_eq!(scraped.chunks.len(), 1);
+
+        let text = &scraped.chunks[0];
+        assert!(text.starts_with("# Introducing Meta Llama 3"));
+        assert!(text.contains("8B and 70B parameters"));
+        // scripts, navigation links and footers are not content
+        assert!(!text.contains("window.dataLayer"));
+        assert!(!text.contains("Sign in"));
+        assert!(!text.contains("Copyright"));
+    }
+
+    #[test]
+    fn test_chunk_text() {
+        let text = extract(ARTICLE_HTML, None).markdown.repeat(10);
+        let chunks = chunk_text(&text);
+
+        assert!(chunks.len() > 1);
+        assert!(chunks
+            .iter()
+            .all(|chunk| chunk.chars().count() <= CHUNK_SIZE));
+    }
+}
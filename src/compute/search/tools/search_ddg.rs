@@ -0,0 +1,153 @@
+use async_trait::async_trait;
+use langchain_rust::tools::Tool;
+use serde_json::{json, Value};
+use std::{error::Error, sync::Arc};
+
+use super::parse_tool_input;
+use crate::compute::search::utils::{
+    cache::{normalize_query, ContentCache},
+    policy::UrlPolicy,
+    provider::{DuckDuckGoProvider, SearchProvider, SearchResult},
+};
+
+/// A tool that searches the web, using the configured search provider.
+#[derive(Clone)]
+pub struct DDGSearcher {
+    provider: Arc<dyn SearchProvider>,
+    cache: Option<Arc<ContentCache>>,
+}
+
+impl Default for DDGSearcher {
+    /// Uses DuckDuckGo with the default policy, see [`provider_from_config`] for the configured
+    /// providers.
+    ///
+    /// [`provider_from_config`]: crate::compute::search::utils::provider::provider_from_config
+    fn default() -> Self {
+        let provider = DuckDuckGoProvider::new(None, UrlPolicy::default())
+            .expect("Should create DuckDuckGo provider.");
+
+        Self::new(Arc::new(provider))
+    }
+}
+
+impl DDGSearcher {
+    pub fn new(provider: Arc<dyn SearchProvider>) -> Self {
+        Self {
+            provider,
+            cache: None,
+        }
+    }
+
+    /// Caches the results of each query within the given cache.
+    pub fn with_cache(mut self, cache: Arc<ContentCache>) -> Self {
+        self.cache = Some(cache);
+        self
+    }
+
+    /// Searches the web for the given query, and returns the ranked results.
+    ///
+    /// Results are served from the cache if possible, and only non-empty results are cached.
+    pub async fn search(&self, query: &str) -> Result<Vec<SearchResult>, Box<dyn Error>> {
+        let key = format!("search:{}", normalize_query(query));
+        if let Some(cache) = &self.cache {
+            if let Some(results) = cache.get(&key).await {
+                if let Ok(results) = serde_json::from_str(&results) {
+                    return Ok(results);
+                }
+            }
+        }
+
+        let results = self.provider.search(query).await?;
+        if let Some(cache) = &self.cache {
+            if !results.is_empty() {
+                cache
+                    .insert(&key, serde_json::to_string(&results)?, cache.ttl())
+                    .await;
+            }
+        }
+
+        Ok(results)
+    }
+}
+
+#[async_trait]
+impl Tool for DDGSearcher {
+    fn name(&self) -> String {
+        "DDG Searcher".to_string()
+    }
+
+    fn description(&self) -> String {
+        "Searches the web and returns the title, URL and snippet of the top results, in order of rank."
+            .to_string()
+    }
+
+    fn parameters(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "query": {
+                    "type": "string",
+                    "description": "The web search query"
+                }
+            },
+            "required": ["query"]
+        })
+    }
+
+    async fn parse_input(&self, input: &str) -> Value {
+        parse_tool_input(input, "query")
+    }
+
+    async fn run(&self, input: Value) -> Result<String, Box<dyn Error>> {
+        let query = input["query"].as_str().ok_or("Search query is required")?;
+        let results = self.search(query).await?;
+
+        Ok(serde_json::to_string(&results)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::errors::NodeResult;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// Returns a single result for any query, and counts how many times it was called.
+    #[derive(Default)]
+    struct StubProvider {
+        calls: AtomicUsize,
+    }
+
+    #[async_trait]
+    impl SearchProvider for StubProvider {
+        fn name(&self) -> &'static str {
+            "stub"
+        }
+
+        async fn search(&self, query: &str) -> NodeResult<Vec<SearchResult>> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            Ok(vec![SearchResult {
+                title: query.to_string(),
+                url: "https://llama.meta.com/llama3/".to_string(),
+                snippet: String::new(),
+                rank: 1,
+            }])
+        }
+    }
+
+    #[tokio::test]
+    async fn test_cached_search() {
+        let provider = Arc::new(StubProvider::default());
+        let searcher = DDGSearcher::new(provider.clone())
+            .with_cache(Arc::new(ContentCache::new(Default::default())));
+
+        let output = searcher.call("Llama 3").await.expect("Should run");
+        let results: Value = serde_json::from_str(&output).expect("Should parse");
+        assert_eq!(results[0]["url"], "https://llama.meta.com/llama3/");
+        assert_eq!(results[0]["rank"], 1);
+
+        // the same query up to case and whitespace is served from the cache
+        searcher.call("  llama   3 ").await.expect("Should run");
+        assert_eq!(provider.calls.load(Ordering::SeqCst), 1);
+    }
+}
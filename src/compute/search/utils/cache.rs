@@ -0,0 +1,487 @@
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{
+    collections::HashMap,
+    fmt,
+    path::PathBuf,
+    sync::atomic::{AtomicU64, Ordering},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use url::Url;
+
+/// Default time-to-live of a cached entry, in seconds.
+pub const DEFAULT_DKN_SEARCH_CACHE_TTL: u64 = 3600;
+
+/// Default size of the in-memory tier, in bytes.
+pub const DEFAULT_DKN_SEARCH_CACHE_MAX_BYTES: usize = 64 * 1024 * 1024;
+
+/// Query parameters that only track the visitor, and are dropped from cache keys.
+const TRACKING_PARAMS: [&str; 4] = ["fbclid", "gclid", "msclkid", "mc_cid"];
+
+/// Configuration of the content cache.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CacheConfig {
+    /// Longest time that an entry is kept for, a zero duration disables the cache.
+    pub ttl: Duration,
+    /// Maximum total size of the values within the in-memory tier.
+    pub max_bytes: usize,
+    /// Directory of the on-disk tier, which is disabled if not given.
+    pub dir: Option<PathBuf>,
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        Self {
+            ttl: Duration::from_secs(DEFAULT_DKN_SEARCH_CACHE_TTL),
+            max_bytes: DEFAULT_DKN_SEARCH_CACHE_MAX_BYTES,
+            dir: None,
+        }
+    }
+}
+
+/// Hit and miss counts of the cache, along with the size of its in-memory tier.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+pub struct CacheStats {
+    /// Lookups served from memory.
+    pub hits: u64,
+    /// Lookups served from disk.
+    pub disk_hits: u64,
+    pub misses: u64,
+    /// Entries removed from memory to stay within the size limit.
+    pub evictions: u64,
+    pub entries: usize,
+    pub bytes: usize,
+}
+
+impl CacheStats {
+    /// Ratio of lookups served from either tier.
+    pub fn hit_rate(&self) -> f64 {
+        let hits = self.hits + self.disk_hits;
+        let lookups = hits + self.misses;
+        if lookups == 0 {
+            0.0
+        } else {
+            hits as f64 / lookups as f64
+        }
+    }
+}
+
+impl fmt::Display for CacheStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} hits ({} from disk), {} misses, {:.1}% hit rate, {} entries in {} bytes, {} evictions",
+            self.hits + self.disk_hits,
+            self.disk_hits,
+            self.misses,
+            self.hit_rate() * 100.0,
+            self.entries,
+            self.bytes,
+            self.evictions
+        )
+    }
+}
+
+/// A cached value along with its key, so that files of the on-disk tier are self-describing.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct Entry {
+    key: String,
+    value: String,
+    /// Expiry time as milliseconds since the Unix epoch.
+    expires_at: u128,
+}
+
+impl Entry {
+    #[inline]
+    fn is_expired(&self) -> bool {
+        self.expires_at <= now_millis()
+    }
+}
+
+/// In-memory tier, evicting the least recently used entries first.
+#[derive(Debug, Default)]
+struct MemoryTier {
+    /// Entries along with the tick at which they were last used.
+    entries: HashMap<String, (Entry, u64)>,
+    bytes: usize,
+    tick: u64,
+}
+
+impl MemoryTier {
+    fn get(&mut self, key: &str) -> Option<String> {
+        self.tick += 1;
+        let tick = self.tick;
+
+        let (entry, last_used) = self.entries.get_mut(key)?;
+        if entry.is_expired() {
+            self.remove(key);
+            return None;
+        }
+
+        *last_used = tick;
+        Some(entry.value.clone())
+    }
+
+    fn remove(&mut self, key: &str) {
+        if let Some((entry, _)) = self.entries.remove(key) {
+            self.bytes -= entry.value.len();
+        }
+    }
+
+    /// Inserts the entry, and returns the number of entries evicted to make room for it.
+    fn insert(&mut self, entry: Entry, max_bytes: usize) -> u64 {
+        self.remove(&entry.key);
+        if entry.value.len() > max_bytes {
+            return 0;
+        }
+
+        let mut evictions = 0;
+        while self.bytes + entry.value.len() > max_bytes {
+            let Some(oldest) = self
+                .entries
+                .iter()
+                .min_by_key(|(_, (_, last_used))| *last_used)
+                .map(|(key, _)| key.clone())
+            else {
+                break;
+            };
+            self.remove(&oldest);
+            evictions += 1;
+        }
+
+        self.tick += 1;
+        self.bytes += entry.value.len();
+        self.entries.insert(entry.key.clone(), (entry, self.tick));
+        evictions
+    }
+}
+
+/// # Content Cache
+///
+/// A cache of fetched pages and search results, shared by the search tools across tasks. Entries are
+/// kept in a size-bounded in-memory tier, and optionally in an on-disk tier that survives restarts.
+#[derive(Debug)]
+pub struct ContentCache {
+    config: CacheConfig,
+    memory: Mutex<MemoryTier>,
+    hits: AtomicU64,
+    disk_hits: AtomicU64,
+    misses: AtomicU64,
+    evictions: AtomicU64,
+}
+
+impl Default for ContentCache {
+    fn default() -> Self {
+        Self::new(CacheConfig::default())
+    }
+}
+
+impl ContentCache {
+    pub fn new(config: CacheConfig) -> Self {
+        Self {
+            config,
+            memory: Mutex::new(MemoryTier::default()),
+            hits: AtomicU64::new(0),
+            disk_hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+            evictions: AtomicU64::new(0),
+        }
+    }
+
+    /// Longest time that an entry is kept for.
+    #[inline]
+    pub fn ttl(&self) -> Duration {
+        self.config.ttl
+    }
+
+    /// Returns the value of the key if it is cached and not expired.
+    pub async fn get(&self, key: &str) -> Option<String> {
+        let value = self.memory.lock().get(key);
+        if let Some(value) = value {
+            self.hits.fetch_add(1, Ordering::Relaxed);
+            return Some(value);
+        }
+
+        if let Some(entry) = self.read_disk(key).await {
+            self.disk_hits.fetch_add(1, Ordering::Relaxed);
+            let value = entry.value.clone();
+            self.insert_memory(entry);
+            return Some(value);
+        }
+
+        self.misses.fetch_add(1, Ordering::Relaxed);
+        None
+    }
+
+    /// Caches the value for the given duration, capped by the configured TTL.
+    ///
+    /// Nothing is cached if the duration is zero.
+    pub async fn insert(&self, key: &str, value: String, ttl: Duration) {
+        let ttl = ttl.min(self.config.ttl);
+        if ttl.is_zero() {
+            return;
+        }
+
+        let entry = Entry {
+            key: key.to_string(),
+            value,
+            expires_at: now_millis() + ttl.as_millis(),
+        };
+        self.write_disk(&entry).await;
+        self.insert_memory(entry);
+    }
+
+    /// Returns the statistics of the cache so far.
+    pub fn stats(&self) -> CacheStats {
+        let memory = self.memory.lock();
+        CacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            disk_hits: self.disk_hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            evictions: self.evictions.load(Ordering::Relaxed),
+            entries: memory.entries.len(),
+            bytes: memory.bytes,
+        }
+    }
+
+    fn insert_memory(&self, entry: Entry) {
+        let evictions = self.memory.lock().insert(entry, self.config.max_bytes);
+        self.evictions.fetch_add(evictions, Ordering::Relaxed);
+    }
+
+    /// Path of the file of the key within the on-disk tier, if it is enabled.
+    fn disk_path(&self, key: &str) -> Option<PathBuf> {
+        let dir = self.config.dir.as_ref()?;
+        Some(dir.join(format!("{}.json", hex::encode(Sha256::digest(key)))))
+    }
+
+    async fn read_disk(&self, key: &str) -> Option<Entry> {
+        let path = self.disk_path(key)?;
+        let contents = tokio::fs::read(&path).await.ok()?;
+
+        match serde_json::from_slice::<Entry>(&contents) {
+            Ok(entry) if entry.key == key && !entry.is_expired() => Some(entry),
+            _ => {
+                // expired or corrupted, either way it is of no use
+                let _ = tokio::fs::remove_file(&path).await;
+                None
+            }
+        }
+    }
+
+    async fn write_disk(&self, entry: &Entry) {
+        let Some(path) = self.disk_path(&entry.key) else {
+            return;
+        };
+
+        let result = async {
+            if let Some(dir) = path.parent() {
+                tokio::fs::create_dir_all(dir).await?;
+            }
+            tokio::fs::write(&path, serde_json::to_vec(entry)?).await
+        };
+        if let Err(e) = result.await {
+            log::warn!("Could not write cache entry {}: {}", path.display(), e);
+        }
+    }
+}
+
+/// Returns the URL as a cache key, ignoring its fragment, tracking parameters and the order of
+/// its query parameters.
+pub fn normalize_url(url: &str) -> String {
+    let Ok(mut parsed) = Url::parse(url.trim()) else {
+        return url.trim().to_string();
+    };
+    parsed.set_fragment(None);
+
+    let mut params: Vec<(String, String)> = parsed
+        .query_pairs()
+        .filter(|(key, _)| !key.starts_with("utm_") && !TRACKING_PARAMS.contains(&key.as_ref()))
+        .map(|(key, value)| (key.into_owned(), value.into_owned()))
+        .collect();
+    params.sort();
+
+    if params.is_empty() {
+        parsed.set_query(None);
+    } else {
+        parsed.query_pairs_mut().clear().extend_pairs(params);
+    }
+
+    parsed.to_string()
+}
+
+/// Returns the query as a cache key, ignoring case and extra whitespace.
+pub fn normalize_query(query: &str) -> String {
+    query
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase()
+}
+
+/// Returns how long a response can be cached for, given its `Cache-Control` header.
+///
+/// `no-store` and `no-cache` mean that it is not cached at all, and `s-maxage` or `max-age` are used
+/// if given. Otherwise, the default is used.
+pub fn cache_control_ttl(cache_control: Option<&str>, default: Duration) -> Duration {
+    let Some(cache_control) = cache_control else {
+        return default;
+    };
+
+    let directives: Vec<String> = cache_control
+        .split(',')
+        .map(|directive| directive.trim().to_lowercase())
+        .collect();
+
+    if directives
+        .iter()
+        .any(|directive| directive == "no-store" || directive == "no-cache")
+    {
+        return Duration::ZERO;
+    }
+
+    ["s-maxage=", "max-age="]
+        .iter()
+        .find_map(|prefix| {
+            directives
+                .iter()
+                .find_map(|directive| directive.strip_prefix(prefix))
+                .and_then(|seconds| seconds.trim_matches('"').parse::<u64>().ok())
+        })
+        .map(Duration::from_secs)
+        .unwrap_or(default)
+}
+
+#[inline]
+fn now_millis() -> u128 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("Time went backwards")
+        .as_millis()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HOUR: Duration = Duration::from_secs(3600);
+
+    #[tokio::test]
+    async fn test_memory_tier() {
+        let cache = ContentCache::default();
+
+        assert_eq!(cache.get("a").await, None);
+        cache.insert("a", "apple".to_string(), HOUR).await;
+        assert_eq!(cache.get("a").await.as_deref(), Some("apple"));
+
+        // zero TTL is not cached, short TTL expires
+        cache
+            .insert("b", "banana".to_string(), Duration::ZERO)
+            .await;
+        cache
+            .insert("c", "cherry".to_string(), Duration::from_millis(10))
+            .await;
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert_eq!(cache.get("b").await, None);
+        assert_eq!(cache.get("c").await, None);
+
+        let stats = cache.stats();
+        assert_eq!((stats.hits, stats.misses), (1, 3));
+        assert_eq!((stats.entries, stats.bytes), (1, 5));
+    }
+
+    #[tokio::test]
+    async fn test_eviction() {
+        let cache = ContentCache::new(CacheConfig {
+            max_bytes: 10,
+            ..Default::default()
+        });
+
+        cache.insert("a", "aaaa".to_string(), HOUR).await;
+        cache.insert("b", "bbbb".to_string(), HOUR).await;
+        // "a" is used more recently than "b", so "b" is evicted
+        assert!(cache.get("a").await.is_some());
+        cache.insert("c", "cccc".to_string(), HOUR).await;
+
+        assert!(cache.get("a").await.is_some());
+        assert!(cache.get("b").await.is_none());
+        assert!(cache.get("c").await.is_some());
+
+        // larger than the entire tier
+        cache.insert("d", "d".repeat(11), HOUR).await;
+        assert!(cache.get("d").await.is_none());
+
+        let stats = cache.stats();
+        assert_eq!(stats.evictions, 1);
+        assert_eq!(stats.bytes, 8);
+    }
+
+    #[tokio::test]
+    async fn test_disk_tier() {
+        let dir = std::env::temp_dir().join(format!("dkn-test-cache-{}", std::process::id()));
+        let config = CacheConfig {
+            dir: Some(dir.clone()),
+            ..Default::default()
+        };
+
+        ContentCache::new(config.clone())
+            .insert("page:https://example.com/", "hello".to_string(), HOUR)
+            .await;
+
+        // a new cache, as if the node restarted
+        let cache = ContentCache::new(config);
+        assert_eq!(
+            cache.get("page:https://example.com/").await.as_deref(),
+            Some("hello")
+        );
+        assert_eq!(
+            cache.get("page:https://example.com/").await.as_deref(),
+            Some("hello")
+        );
+
+        let stats = cache.stats();
+        assert_eq!((stats.hits, stats.disk_hits), (1, 1));
+
+        tokio::fs::remove_dir_all(&dir)
+            .await
+            .expect("Should remove dir");
+    }
+
+    #[test]
+    fn test_normalize() {
+        assert_eq!(
+            normalize_url("https://Example.com:443/a?b=2&utm_source=x&a=1#top"),
+            "https://example.com/a?a=1&b=2"
+        );
+        assert_eq!(
+            normalize_url("https://example.com/a?fbclid=123"),
+            "https://example.com/a"
+        );
+        assert_eq!(
+            normalize_query("  Who built   LLAMA 3? "),
+            "who built llama 3?"
+        );
+    }
+
+    #[test]
+    fn test_cache_control_ttl() {
+        let cases = [
+            (None, HOUR),
+            (Some("public, max-age=60"), Duration::from_secs(60)),
+            (Some("max-age=60, s-maxage=120"), Duration::from_secs(120)),
+            (Some("no-store"), Duration::ZERO),
+            (Some("private, no-cache"), Duration::ZERO),
+            (Some("public"), HOUR),
+        ];
+
+        for (cache_control, expected) in cases {
+            assert_eq!(
+                cache_control_ttl(cache_control, HOUR),
+                expected,
+                "{:?}",
+                cache_control
+            );
+        }
+    }
+}
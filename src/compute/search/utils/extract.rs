@@ -0,0 +1,548 @@
+use scraper::{node::Text, ElementRef, Html, Node, Selector};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use url::Url;
+
+/// Width at which lines are wrapped, large enough to keep each paragraph on a single line.
+const WRAP_WIDTH: usize = 10_000;
+
+/// Paragraphs shorter than this many characters do not count towards the content score.
+const MIN_PARAGRAPH_CHARS: usize = 25;
+
+/// Elements that are never part of the main content.
+const BOILERPLATE_TAGS: &str =
+    "script, style, noscript, template, iframe, svg, canvas, nav, footer, aside, form, button, dialog";
+
+/// Elements that are hidden, or marked as navigation, site header, site footer or pop-up.
+const BOILERPLATE_ATTRIBUTES: &str = r#"[hidden], [aria-hidden="true"], [role="navigation"], [role="banner"], [role="contentinfo"], [role="complementary"], [role="dialog"], [role="alertdialog"]"#;
+
+/// Class or id fragments that indicate boilerplate, such as cookie banners and sidebars.
+const UNLIKELY_FRAGMENTS: [&str; 20] = [
+    "cookie",
+    "consent",
+    "gdpr",
+    "banner",
+    "newsletter",
+    "subscribe",
+    "sidebar",
+    "breadcrumb",
+    "popup",
+    "modal",
+    "advert",
+    "promo",
+    "sponsor",
+    "social",
+    "share",
+    "comment",
+    "related",
+    "menu",
+    "navbar",
+    "footer",
+];
+
+/// Class or id fragments that indicate content, these take precedence over the unlikely ones.
+const LIKELY_FRAGMENTS: [&str; 8] = [
+    "article", "content", "main", "post", "entry", "story", "text", "prose",
+];
+
+/// Metadata of a page, read from its `<head>` and structured data.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct PageMetadata {
+    pub title: Option<String>,
+    pub canonical_url: Option<String>,
+    pub published: Option<String>,
+}
+
+/// The main content of a page as Markdown, along with its metadata.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct ExtractedPage {
+    pub metadata: PageMetadata,
+    pub markdown: String,
+}
+
+/// Extracts the main content of an HTML document as Markdown, along with its metadata.
+///
+/// Boilerplate such as navigation, footers and cookie banners is removed, and the element with the
+/// highest readability score is taken as the main content. Relative links are resolved against `url`.
+pub fn extract(html: &str, url: Option<&str>) -> ExtractedPage {
+    let mut document = Html::parse_document(html);
+    let base_url = url.and_then(|url| Url::parse(url).ok());
+
+    let metadata = extract_metadata(&document, base_url.as_ref());
+
+    remove_boilerplate(&mut document);
+    let markdown = to_markdown(&mut document, base_url.as_ref());
+
+    ExtractedPage { metadata, markdown }
+}
+
+/// Reads the title, canonical URL and publish date of the document.
+fn extract_metadata(document: &Html, base_url: Option<&Url>) -> PageMetadata {
+    let title = first_attr(
+        document,
+        &[
+            (r#"meta[property="og:title"]"#, "content"),
+            (r#"meta[name="twitter:title"]"#, "content"),
+        ],
+    )
+    .or_else(|| first_text(document, "title"))
+    .or_else(|| first_text(document, "h1"));
+
+    let canonical_url = first_attr(
+        document,
+        &[
+            (r#"link[rel="canonical"]"#, "href"),
+            (r#"meta[property="og:url"]"#, "content"),
+        ],
+    )
+    .map(|href| resolve_url(&href, base_url));
+
+    let published = first_attr(
+        document,
+        &[
+            (r#"meta[property="article:published_time"]"#, "content"),
+            (r#"meta[itemprop="datePublished"]"#, "content"),
+            (r#"meta[name="date"]"#, "content"),
+            (r#"meta[name="pubdate"]"#, "content"),
+            (r#"meta[name="publish-date"]"#, "content"),
+        ],
+    )
+    .or_else(|| json_ld_date(document))
+    .or_else(|| {
+        first_attr(
+            document,
+            &[
+                (r#"time[itemprop="datePublished"]"#, "datetime"),
+                ("time[datetime]", "datetime"),
+            ],
+        )
+    });
+
+    PageMetadata {
+        title,
+        canonical_url,
+        published,
+    }
+}
+
+/// Returns the first non-empty attribute among the given selector and attribute pairs.
+fn first_attr(document: &Html, candidates: &[(&str, &str)]) -> Option<String> {
+    candidates.iter().find_map(|(selector, attr)| {
+        let selector = Selector::parse(selector).expect("Should parse selector");
+        document
+            .select(&selector)
+            .filter_map(|element| element.attr(attr))
+            .map(|value| value.trim().to_string())
+            .find(|value| !value.is_empty())
+    })
+}
+
+/// Returns the text of the first element matching the selector, if it is not empty.
+fn first_text(document: &Html, selector: &str) -> Option<String> {
+    let selector = Selector::parse(selector).expect("Should parse selector");
+    document
+        .select(&selector)
+        .next()
+        .map(|element| collapse_whitespace(&element.text().collect::<String>()))
+        .filter(|text| !text.is_empty())
+}
+
+/// Finds `datePublished` within the JSON-LD structured data of the document.
+fn json_ld_date(document: &Html) -> Option<String> {
+    fn find_date(value: &Value) -> Option<String> {
+        match value {
+            Value::Object(map) => map
+                .get("datePublished")
+                .and_then(Value::as_str)
+                .map(str::to_string)
+                .or_else(|| map.values().find_map(find_date)),
+            Value::Array(values) => values.iter().find_map(find_date),
+            _ => None,
+        }
+    }
+
+    let selector =
+        Selector::parse(r#"script[type="application/ld+json"]"#).expect("Should parse selector");
+    document
+        .select(&selector)
+        .filter_map(|script| serde_json::from_str::<Value>(&script.text().collect::<String>()).ok())
+        .find_map(|value| find_date(&value))
+}
+
+/// Removes elements that are not part of the main content.
+fn remove_boilerplate(document: &mut Html) {
+    let tags = Selector::parse(BOILERPLATE_TAGS).expect("Should parse selector");
+    let attributes = Selector::parse(BOILERPLATE_ATTRIBUTES).expect("Should parse selector");
+    let all = Selector::parse("*").expect("Should parse selector");
+
+    let ids: Vec<_> = document
+        .select(&tags)
+        .chain(document.select(&attributes))
+        .chain(
+            document
+                .select(&all)
+                .filter(|element| is_unlikely(*element)),
+        )
+        .filter(|element| !matches!(element.value().name(), "html" | "body" | "article" | "main"))
+        .map(|element| element.id())
+        .collect();
+
+    for id in ids {
+        if let Some(mut node) = document.tree.get_mut(id) {
+            node.detach();
+        }
+    }
+}
+
+/// Returns the lowercase class and id of the element.
+fn class_and_id(element: ElementRef) -> String {
+    let value = element.value();
+    format!(
+        "{} {}",
+        value.attr("class").unwrap_or_default(),
+        value.id().unwrap_or_default()
+    )
+    .to_lowercase()
+}
+
+/// Returns `true` if the class or id of the element indicates boilerplate, and not content.
+fn is_unlikely(element: ElementRef) -> bool {
+    let names = class_and_id(element);
+    UNLIKELY_FRAGMENTS.iter().any(|f| names.contains(f))
+        && !LIKELY_FRAGMENTS.iter().any(|f| names.contains(f))
+}
+
+/// Returns the initial score of a candidate, from its tag, class and id.
+fn initial_score(element: ElementRef) -> f64 {
+    let tag_score = match element.value().name() {
+        "article" | "main" => 10.0,
+        "div" => 5.0,
+        "pre" | "td" | "blockquote" => 3.0,
+        "ol" | "ul" | "li" | "form" => -3.0,
+        "h1" | "h2" | "h3" | "h4" | "h5" | "h6" | "th" => -5.0,
+        _ => 0.0,
+    };
+
+    let names = class_and_id(element);
+    let mut class_score = 0.0;
+    if UNLIKELY_FRAGMENTS.iter().any(|f| names.contains(f)) {
+        class_score -= 25.0;
+    }
+    if LIKELY_FRAGMENTS.iter().any(|f| names.contains(f)) {
+        class_score += 25.0;
+    }
+
+    tag_score + class_score
+}
+
+/// Returns the ratio of link text to all text within the element.
+fn link_density(element: ElementRef) -> f64 {
+    let text_length = element.text().map(|t| t.trim().len()).sum::<usize>();
+    if text_length == 0 {
+        return 0.0;
+    }
+
+    let links = Selector::parse("a").expect("Should parse selector");
+    let link_length = element
+        .select(&links)
+        .flat_map(|link| link.text())
+        .map(|t| t.trim().len())
+        .sum::<usize>();
+
+    link_length as f64 / text_length as f64
+}
+
+/// Finds the element that holds the main content, using readability-style scoring.
+///
+/// Each paragraph adds to the score of its parent, and half as much to its grandparent. The scores
+/// are then penalized by link density, and the best candidate is widened to its parent while the
+/// parent scores nearly as well, which happens when the content is split into sibling sections.
+fn find_content(document: &Html) -> Option<ElementRef<'_>> {
+    let paragraphs = Selector::parse("p, pre, td").expect("Should parse selector");
+
+    let mut scores = HashMap::new();
+    for paragraph in document.select(&paragraphs) {
+        let text = collapse_whitespace(&paragraph.text().collect::<String>());
+        let length = text.chars().count();
+        if length < MIN_PARAGRAPH_CHARS {
+            continue;
+        }
+
+        let score = 1.0 + text.matches(',').count() as f64 + (length / 100).min(3) as f64;
+        for (ancestor, divider) in paragraph
+            .ancestors()
+            .filter_map(ElementRef::wrap)
+            .take(2)
+            .zip([1.0, 2.0])
+        {
+            *scores
+                .entry(ancestor.id())
+                .or_insert_with(|| initial_score(ancestor)) += score / divider;
+        }
+    }
+
+    let final_score = |element: ElementRef| -> Option<f64> {
+        scores
+            .get(&element.id())
+            .map(|score| score * (1.0 - link_density(element)))
+    };
+
+    let best = scores
+        .keys()
+        .filter_map(|id| document.tree.get(*id).and_then(ElementRef::wrap))
+        .filter_map(|element| final_score(element).map(|score| (element, score)))
+        .max_by(|a, b| a.1.total_cmp(&b.1));
+
+    let Some((mut content, mut score)) = best else {
+        // nothing that looks like a paragraph, so take the whole body
+        let body = Selector::parse("body").expect("Should parse selector");
+        return document.select(&body).next();
+    };
+
+    while let Some(parent) = content.parent().and_then(ElementRef::wrap) {
+        match final_score(parent) {
+            Some(parent_score) if parent_score >= score * 0.75 => {
+                content = parent;
+                score = parent_score;
+            }
+            _ => break,
+        }
+    }
+
+    Some(content)
+}
+
+/// Finds the content element and converts it to Markdown.
+///
+/// Tables and code blocks are converted separately, and swapped in place of placeholders after the
+/// rest of the content is converted with `html2text`.
+fn to_markdown(document: &mut Html, base_url: Option<&Url>) -> String {
+    let Some(content) = find_content(document) else {
+        return String::new();
+    };
+    let content_id = content.id();
+    let blocks_selector = Selector::parse("table, pre").expect("Should parse selector");
+
+    // only the outermost blocks are converted, anything nested is part of its block
+    let blocks: Vec<_> = content
+        .select(&blocks_selector)
+        .filter(|block| {
+            !block
+                .ancestors()
+                .take_while(|ancestor| ancestor.id() != content_id)
+                .filter_map(ElementRef::wrap)
+                .any(|ancestor| matches!(ancestor.value().name(), "table" | "pre"))
+        })
+        .map(|block| match block.value().name() {
+            "table" => (block.id(), table_to_markdown(block)),
+            _ => (block.id(), code_to_markdown(block)),
+        })
+        .collect();
+
+    for (index, (id, _)) in blocks.iter().enumerate() {
+        if let Some(mut node) = document.tree.get_mut(*id) {
+            node.insert_before(Node::Text(Text {
+                text: placeholder(index).into(),
+            }));
+            node.detach();
+        }
+    }
+
+    let html = document
+        .tree
+        .get(content_id)
+        .and_then(ElementRef::wrap)
+        .map(|content| content.html())
+        .unwrap_or_default();
+    let mut markdown = html2text::from_read(html.as_bytes(), WRAP_WIDTH);
+
+    for (index, (_, block)) in blocks.iter().enumerate() {
+        markdown = markdown.replace(&placeholder(index), &format!("\n\n{}\n\n", block));
+    }
+
+    tidy(&markdown, base_url)
+}
+
+#[inline]
+fn placeholder(index: usize) -> String {
+    format!("@@block{}@@", index)
+}
+
+/// Converts a table to a Markdown table, taking the first row as the header.
+fn table_to_markdown(table: ElementRef) -> String {
+    let rows_selector = Selector::parse("tr").expect("Should parse selector");
+
+    let rows: Vec<Vec<String>> = table
+        .select(&rows_selector)
+        // rows of nested tables belong to those tables
+        .filter(|row| {
+            row.ancestors()
+                .filter_map(ElementRef::wrap)
+                .find(|ancestor| ancestor.value().name() == "table")
+                .map(|ancestor| ancestor.id())
+                == Some(table.id())
+        })
+        .map(|row| {
+            row.child_elements()
+                .filter(|cell| matches!(cell.value().name(), "th" | "td"))
+                .map(|cell| {
+                    collapse_whitespace(&cell.text().collect::<String>()).replace('|', "\\|")
+                })
+                .collect::<Vec<_>>()
+        })
+        .filter(|cells| !cells.is_empty())
+        .collect();
+
+    let columns = rows.iter().map(Vec::len).max().unwrap_or_default();
+    if columns == 0 {
+        return String::new();
+    }
+
+    let format_row = |cells: &[String]| {
+        let mut cells = cells.to_vec();
+        cells.resize(columns, String::new());
+        format!("| {} |", cells.join(" | "))
+    };
+
+    let mut lines = vec![
+        format_row(&rows[0]),
+        format_row(&vec!["---".to_string(); columns]),
+    ];
+    lines.extend(rows[1..].iter().map(|row| format_row(row)));
+    lines.join("\n")
+}
+
+/// Converts a preformatted block to a fenced code block, keeping the language if it is given.
+fn code_to_markdown(pre: ElementRef) -> String {
+    let code = Selector::parse("code").expect("Should parse selector");
+    let language = std::iter::once(pre)
+        .chain(pre.select(&code).take(1))
+        .filter_map(|element| element.value().attr("class"))
+        .flat_map(str::split_whitespace)
+        .find_map(|class| {
+            class
+                .strip_prefix("language-")
+                .or_else(|| class.strip_prefix("lang-"))
+        })
+        .unwrap_or_default();
+
+    let text = pre.text().collect::<String>();
+    format!("```{}\n{}\n```", language, text.trim_matches('\n'))
+}
+
+/// Resolves link references against the base URL, trims lines and collapses blank lines.
+fn tidy(markdown: &str, base_url: Option<&Url>) -> String {
+    let mut lines: Vec<String> = Vec::new();
+    let mut in_code = false;
+
+    for line in markdown.lines() {
+        if line.trim_start().starts_with("```") {
+            in_code = !in_code;
+        }
+
+        let line = if in_code {
+            line.to_string()
+        } else {
+            resolve_reference(line.trim_end(), base_url)
+        };
+
+        if !in_code && line.is_empty() && lines.last().is_none_or(String::is_empty) {
+            continue;
+        }
+        lines.push(line);
+    }
+
+    lines.join("\n").trim().to_string()
+}
+
+/// Resolves the URL of a link reference line such as `[1]: /about`.
+fn resolve_reference(line: &str, base_url: Option<&Url>) -> String {
+    let reference = line
+        .strip_prefix('[')
+        .and_then(|rest| rest.split_once("]: "))
+        .filter(|(label, _)| !label.is_empty() && label.chars().all(|c| c.is_ascii_digit()));
+
+    match reference {
+        Some((label, href)) => format!("[{}]: {}", label, resolve_url(href, base_url)),
+        None => line.to_string(),
+    }
+}
+
+/// Resolves a possibly relative URL against the base URL, if there is one.
+fn resolve_url(href: &str, base_url: Option<&Url>) -> String {
+    base_url
+        .and_then(|base| base.join(href).ok())
+        .map(|url| url.to_string())
+        .unwrap_or_else(|| href.to_string())
+}
+
+#[inline]
+fn collapse_whitespace(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DOCS_HTML: &str = include_str!(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/fixtures/docs_page.html"
+    ));
+
+    #[test]
+    fn test_extract_metadata() {
+        let page = extract(
+            DOCS_HTML,
+            Some("https://docs.example.org/guide/tokio?ref=ddg"),
+        );
+
+        assert_eq!(
+            page.metadata,
+            PageMetadata {
+                title: Some("Getting started with Tokio".to_string()),
+                canonical_url: Some("https://docs.example.org/guide/tokio".to_string()),
+                published: Some("2024-03-12T09:30:00Z".to_string()),
+            }
+        );
+    }
+
+    #[test]
+    fn test_extract_markdown() {
+        let page = extract(DOCS_HTML, Some("https://docs.example.org/guide/tokio"));
+        let markdown = page.markdown;
+
+        // structure is preserved
+        assert!(
+            markdown.starts_with("# Getting started with Tokio"),
+            "{}",
+            markdown
+        );
+        assert!(markdown.contains("## Installation"));
+        assert!(markdown.contains("* `macros` enables"));
+        assert!(markdown.contains(
+            "| Feature | Description |\n| --- | --- |\n| rt | Single-threaded runtime |"
+        ));
+        assert!(markdown.contains("```rust\n#[tokio::main]\nasync fn main() {"));
+        assert!(markdown.contains("[1]: https://docs.example.org/guide/runtime"));
+
+        // boilerplate is removed
+        for boilerplate in [
+            "Accept all cookies",
+            "Sign in",
+            "Related posts",
+            "All rights reserved",
+            "console.log",
+        ] {
+            assert!(!markdown.contains(boilerplate), "Found {}", boilerplate);
+        }
+    }
+
+    #[test]
+    fn test_extract_without_paragraphs() {
+        let page = extract("<html><body><div>Just a line</div></body></html>", None);
+        assert_eq!(page.markdown, "Just a line");
+        assert_eq!(page.metadata, PageMetadata::default());
+    }
+}
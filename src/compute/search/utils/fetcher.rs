@@ -0,0 +1,666 @@
+use async_trait::async_trait;
+use reqwest::{
+    header::{CACHE_CONTROL, CONTENT_TYPE},
+    Client, Response,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::{error::Error, fmt, sync::Arc, time::Duration};
+
+use super::{
+    cache::{cache_control_ttl, normalize_url, ContentCache},
+    policy::{guarded_client_builder, UrlPolicy},
+};
+use crate::errors::{NodeError, NodeResult};
+
+/// Default Browserless URL, used when only the token is configured.
+pub const DEFAULT_DKN_BROWSERLESS_URL: &str = "http://127.0.0.1:3000";
+
+/// Default timeout of a single fetch, in seconds.
+pub const DEFAULT_DKN_SEARCH_FETCH_TIMEOUT: u64 = 30;
+
+/// Default maximum size of a fetched page, in bytes.
+pub const DEFAULT_DKN_SEARCH_FETCH_MAX_BYTES: usize = 5 * 1024 * 1024;
+
+/// Header with the final URL of the page that Browserless loaded, after redirects.
+const BROWSERLESS_URL_HEADER: &str = "x-response-url";
+
+/// Header with the address that Browserless loaded the final page from.
+const BROWSERLESS_IP_HEADER: &str = "x-response-ip";
+
+/// Content types that can be fetched, anything else is rejected.
+const ALLOWED_CONTENT_TYPES: [&str; 4] = [
+    "text/html",
+    "text/plain",
+    "application/xhtml+xml",
+    "application/json",
+];
+
+/// Limits that apply to every fetch, regardless of the backend.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FetchLimits {
+    /// Timeout of the entire request, including reading the body.
+    pub timeout: Duration,
+    /// Maximum number of bytes to read from the body.
+    pub max_bytes: usize,
+}
+
+impl Default for FetchLimits {
+    fn default() -> Self {
+        Self {
+            timeout: Duration::from_secs(DEFAULT_DKN_SEARCH_FETCH_TIMEOUT),
+            max_bytes: DEFAULT_DKN_SEARCH_FETCH_MAX_BYTES,
+        }
+    }
+}
+
+/// Configuration of the page fetcher.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FetcherConfig {
+    pub limits: FetchLimits,
+    /// Fetches pages with Browserless if given, and directly over HTTP otherwise.
+    pub browserless: Option<BrowserlessConfig>,
+}
+
+/// Endpoint and token of Browserless, see [`BrowserlessFetcher`].
+#[derive(Clone, Default, PartialEq)]
+pub struct BrowserlessConfig {
+    /// Defaults to [`DEFAULT_DKN_BROWSERLESS_URL`] if not given.
+    pub url: Option<String>,
+    pub token: Option<String>,
+}
+
+impl fmt::Debug for BrowserlessConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BrowserlessConfig")
+            .field("url", &self.url)
+            .field("token", &self.token.as_ref().map(|_| "<redacted>"))
+            .finish()
+    }
+}
+
+/// A fetched page.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Page {
+    /// Final URL of the page, after redirections if there were any.
+    pub url: String,
+    /// Content type of the page, without parameters such as `charset`.
+    pub content_type: String,
+    /// Body of the page, decoded as UTF-8 lossily.
+    pub body: String,
+    /// `Cache-Control` header of the response, if there was one.
+    #[serde(default)]
+    pub cache_control: Option<String>,
+}
+
+/// A backend that fetches the contents of a web page.
+#[async_trait]
+pub trait PageFetcher: Send + Sync {
+    /// Name of the backend, for logging.
+    fn name(&self) -> &'static str;
+
+    /// Fetches the page at the given URL.
+    async fn fetch(&self, url: &str) -> NodeResult<Page>;
+}
+
+/// Creates the configured fetcher.
+///
+/// Either way, URLs are checked against the policy, and Browserless also requires an allow-list of
+/// domains, see [`BrowserlessFetcher`].
+pub fn fetcher_from_config(
+    config: &FetcherConfig,
+    policy: &UrlPolicy,
+) -> NodeResult<Arc<dyn PageFetcher>> {
+    let limits = config.limits.clone();
+    let policy = policy.clone();
+
+    let fetcher: Arc<dyn PageFetcher> = match &config.browserless {
+        Some(browserless) => Arc::new(BrowserlessFetcher::new(
+            browserless.url.clone(),
+            browserless.token.clone(),
+            limits,
+            policy,
+        )?),
+        None => Arc::new(HttpFetcher::new(limits, policy)?),
+    };
+
+    log::info!("Page fetcher: {}", fetcher.name());
+    Ok(fetcher)
+}
+
+/// Fetches pages directly with an HTTP GET request, without rendering JavaScript.
+///
+/// The policy is enforced on the URL, on each redirect and on the resolved addresses.
+#[derive(Debug, Clone)]
+pub struct HttpFetcher {
+    client: Client,
+    policy: Arc<UrlPolicy>,
+    limits: FetchLimits,
+}
+
+impl HttpFetcher {
+    pub fn new(limits: FetchLimits, policy: UrlPolicy) -> NodeResult<Self> {
+        let policy = Arc::new(policy);
+        let client = guarded_client_builder(policy.clone())
+            .timeout(limits.timeout)
+            .user_agent(concat!("dkn-compute/", env!("CARGO_PKG_VERSION")))
+            .build()?;
+
+        Ok(Self {
+            client,
+            policy,
+            limits,
+        })
+    }
+}
+
+#[async_trait]
+impl PageFetcher for HttpFetcher {
+    fn name(&self) -> &'static str {
+        "http"
+    }
+
+    async fn fetch(&self, url: &str) -> NodeResult<Page> {
+        let url = self.policy.check_url(url)?;
+        let response = self
+            .client
+            .get(url)
+            .header("Accept", "text/html, text/plain;q=0.9, */*;q=0.8")
+            .send()
+            .await
+            .map_err(describe_error)?
+            .error_for_status()?;
+
+        let url = response.url().to_string();
+        read_page(response, url, &self.limits).await
+    }
+}
+
+/// Fetches pages through a [Browserless](https://www.browserless.io/) instance, which renders
+/// JavaScript before returning the HTML.
+///
+/// Browserless itself usually runs on a private address, and the browser follows redirects on its
+/// own, so they can not be checked as they happen. The policy is enforced on the page URL before the
+/// request, and on the final URL and address that Browserless reports afterwards; a page without a
+/// final URL is refused. As a redirect may still pass through a private address, Browserless is only
+/// allowed along with an allow-list of domains, or when private networks are allowed anyway.
+#[derive(Debug, Clone)]
+pub struct BrowserlessFetcher {
+    client: Client,
+    base_url: String,
+    token: Option<String>,
+    policy: UrlPolicy,
+    limits: FetchLimits,
+}
+
+impl BrowserlessFetcher {
+    /// Creates a new Browserless fetcher, the URL defaults to `DEFAULT_DKN_BROWSERLESS_URL`.
+    pub fn new(
+        base_url: Option<String>,
+        token: Option<String>,
+        limits: FetchLimits,
+        policy: UrlPolicy,
+    ) -> NodeResult<Self> {
+        if policy.allowed_domains.is_empty() && !policy.allow_private_networks {
+            return Err(NodeError::Config(
+                "Browserless requires DKN_SEARCH_ALLOWED_DOMAINS, as its redirects can not be checked."
+                    .to_string(),
+            ));
+        }
+
+        let base_url = base_url
+            .unwrap_or(DEFAULT_DKN_BROWSERLESS_URL.to_string())
+            .trim_end_matches('/')
+            .to_string();
+        let client = Client::builder().timeout(limits.timeout).build()?;
+
+        Ok(Self {
+            client,
+            base_url,
+            token,
+            policy,
+            limits,
+        })
+    }
+}
+
+#[async_trait]
+impl PageFetcher for BrowserlessFetcher {
+    fn name(&self) -> &'static str {
+        "browserless"
+    }
+
+    async fn fetch(&self, url: &str) -> NodeResult<Page> {
+        self.policy.check(url).await?;
+        let mut request = self
+            .client
+            .post(format!("{}/content", self.base_url))
+            .header("Cache-Control", "no-cache")
+            .json(&json!({ "url": url }));
+        if let Some(token) = &self.token {
+            request = request.query(&[("token", token)]);
+        }
+
+        // the response URL is that of Browserless along with the token, so the final page URL is
+        // taken from the headers instead, and checked again as the browser may have been redirected
+        let response = request.send().await?.error_for_status()?;
+        let final_url = response
+            .headers()
+            .get(BROWSERLESS_URL_HEADER)
+            .and_then(|value| value.to_str().ok())
+            .ok_or_else(|| {
+                NodeError::tool(format!(
+                    "Browserless did not report the final URL of {}",
+                    url
+                ))
+            })?
+            .to_string();
+        self.policy.check(&final_url).await?;
+
+        if let Some(ip) = response
+            .headers()
+            .get(BROWSERLESS_IP_HEADER)
+            .and_then(|value| value.to_str().ok())
+        {
+            let ip = ip.trim_start_matches('[').trim_end_matches(']');
+            let ip = ip.parse().map_err(|_| {
+                NodeError::tool(format!("Browserless reported an invalid address {}", ip))
+            })?;
+            self.policy.check_ip(ip)?;
+        }
+
+        read_page(response, final_url, &self.limits).await
+    }
+}
+
+/// Serves pages from the cache when possible, and caches the pages fetched by another fetcher
+/// with respect to their `Cache-Control` header.
+///
+/// The policy is checked before the cache, including the addresses that the domain resolves to, so
+/// that a cached page is not served for a URL that has since been blocked.
+pub struct CachedFetcher {
+    inner: Arc<dyn PageFetcher>,
+    cache: Arc<ContentCache>,
+    policy: UrlPolicy,
+}
+
+impl CachedFetcher {
+    pub fn new(inner: Arc<dyn PageFetcher>, cache: Arc<ContentCache>, policy: UrlPolicy) -> Self {
+        Self {
+            inner,
+            cache,
+            policy,
+        }
+    }
+}
+
+#[async_trait]
+impl PageFetcher for CachedFetcher {
+    fn name(&self) -> &'static str {
+        self.inner.name()
+    }
+
+    async fn fetch(&self, url: &str) -> NodeResult<Page> {
+        self.policy.check(url).await?;
+
+        let key = format!("page:{}", normalize_url(url));
+        if let Some(page) = self.cache.get(&key).await {
+            if let Ok(page) = serde_json::from_str::<Page>(&page) {
+                return Ok(page);
+            }
+        }
+
+        let page = self.inner.fetch(url).await?;
+        let ttl = cache_control_ttl(page.cache_control.as_deref(), self.cache.ttl());
+        self.cache
+            .insert(&key, serde_json::to_string(&page)?, ttl)
+            .await;
+
+        Ok(page)
+    }
+}
+
+/// Includes the causes of a request error, such as a blocked address, which `reqwest` omits.
+fn describe_error(error: reqwest::Error) -> NodeError {
+    let mut message = error.to_string();
+    let mut source = error.source();
+    while let Some(cause) = source {
+        message.push_str(&format!(": {}", cause));
+        source = cause.source();
+    }
+
+    NodeError::Tool {
+        message,
+        source: Some(Box::new(error)),
+    }
+}
+
+/// Checks the content type of the response, and reads at most `max_bytes` of its body.
+///
+/// The given `url` is the URL of the page itself, which may differ from that of the response.
+async fn read_page(mut response: Response, url: String, limits: &FetchLimits) -> NodeResult<Page> {
+    let content_type = response
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.split(';').next())
+        .map(|value| value.trim().to_lowercase())
+        .unwrap_or_default();
+
+    // a missing content type is tolerated, as some servers omit it for HTML
+    if !content_type.is_empty() && !ALLOWED_CONTENT_TYPES.contains(&content_type.as_str()) {
+        return Err(NodeError::tool(format!(
+            "Unsupported content type {} at {}",
+            content_type, url
+        )));
+    }
+
+    let cache_control = response
+        .headers()
+        .get(CACHE_CONTROL)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_string);
+
+    if let Some(length) = response.content_length() {
+        if length > limits.max_bytes as u64 {
+            return Err(NodeError::tool(format!(
+                "Page at {} is {} bytes, larger than the limit of {} bytes",
+                url, length, limits.max_bytes
+            )));
+        }
+    }
+
+    let mut body = Vec::new();
+    while let Some(chunk) = response.chunk().await? {
+        if body.len() + chunk.len() > limits.max_bytes {
+            return Err(NodeError::tool(format!(
+                "Page at {} is larger than the limit of {} bytes",
+                url, limits.max_bytes
+            )));
+        }
+        body.extend_from_slice(&chunk);
+    }
+
+    Ok(Page {
+        url,
+        content_type,
+        body: String::from_utf8_lossy(&body).into_owned(),
+        cache_control,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HTML: &str = "<html><body><p>Hello world</p></body></html>";
+
+    /// The mock server is local, so private networks must be allowed.
+    fn local_policy() -> UrlPolicy {
+        UrlPolicy {
+            allow_private_networks: true,
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_http_fetcher() {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("GET", "/page")
+            .with_header("content-type", "text/html; charset=utf-8")
+            .with_body(HTML)
+            .create_async()
+            .await;
+
+        let fetcher = HttpFetcher::new(FetchLimits::default(), local_policy())
+            .expect("Should create fetcher");
+        let page = fetcher
+            .fetch(&format!("{}/page", server.url()))
+            .await
+            .expect("Should fetch page");
+
+        assert_eq!(page.body, HTML);
+        assert_eq!(page.content_type, "text/html");
+        mock.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_browserless_fetcher() {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("POST", "/content")
+            .match_query(mockito::Matcher::UrlEncoded(
+                "token".into(),
+                "secret".into(),
+            ))
+            .match_body(mockito::Matcher::Json(
+                json!({ "url": "http://127.0.0.1:8080/page" }),
+            ))
+            .with_header("content-type", "text/html")
+            .with_header("x-response-url", "http://127.0.0.1:8080/redirected")
+            .with_header("x-response-ip", "127.0.0.1")
+            .with_body(HTML)
+            .create_async()
+            .await;
+
+        let fetcher = BrowserlessFetcher::new(
+            Some(server.url()),
+            Some("secret".to_string()),
+            FetchLimits::default(),
+            local_policy(),
+        )
+        .expect("Should create fetcher");
+        let page = fetcher
+            .fetch("http://127.0.0.1:8080/page")
+            .await
+            .expect("Should fetch page");
+
+        assert_eq!(page.body, HTML);
+        assert_eq!(page.url, "http://127.0.0.1:8080/redirected");
+        mock.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_browserless_redirects() {
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock("POST", "/content")
+            .match_body(mockito::Matcher::Json(
+                json!({ "url": "http://localhost/redirect" }),
+            ))
+            .with_header("content-type", "text/html")
+            .with_header("x-response-url", "http://169.254.169.254/latest/meta-data")
+            .with_body(HTML)
+            .create_async()
+            .await;
+        server
+            .mock("POST", "/content")
+            .match_body(mockito::Matcher::Json(
+                json!({ "url": "http://localhost/unreported" }),
+            ))
+            .with_header("content-type", "text/html")
+            .with_body(HTML)
+            .create_async()
+            .await;
+
+        // without an allow-list, a redirect could reach any address
+        assert!(BrowserlessFetcher::new(
+            Some(server.url()),
+            None,
+            FetchLimits::default(),
+            UrlPolicy::default(),
+        )
+        .is_err());
+
+        let policy = UrlPolicy {
+            allowed_domains: vec!["localhost".to_string()],
+            ..local_policy()
+        };
+        let fetcher =
+            BrowserlessFetcher::new(Some(server.url()), None, FetchLimits::default(), policy)
+                .expect("Should create fetcher");
+
+        let err = fetcher
+            .fetch("http://localhost/redirect")
+            .await
+            .expect_err("Should block redirected page");
+        assert!(err.to_string().contains("169.254.169.254"), "{}", err);
+
+        let err = fetcher
+            .fetch("http://localhost/unreported")
+            .await
+            .expect_err("Should refuse page without final URL");
+        assert!(err.to_string().contains("did not report"), "{}", err);
+    }
+
+    #[tokio::test]
+    async fn test_fetch_limits() {
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock("GET", "/image")
+            .with_header("content-type", "image/png")
+            .with_body([0u8; 16])
+            .create_async()
+            .await;
+        server
+            .mock("GET", "/large")
+            .with_header("content-type", "text/html")
+            .with_body("a".repeat(1024))
+            .create_async()
+            .await;
+
+        let limits = FetchLimits {
+            max_bytes: 512,
+            ..Default::default()
+        };
+        let fetcher = HttpFetcher::new(limits, local_policy()).expect("Should create fetcher");
+
+        let err = fetcher
+            .fetch(&format!("{}/image", server.url()))
+            .await
+            .expect_err("Should reject content type");
+        assert!(err.to_string().contains("image/png"));
+
+        let err = fetcher
+            .fetch(&format!("{}/large", server.url()))
+            .await
+            .expect_err("Should reject large page");
+        assert!(err.to_string().contains("512"));
+    }
+
+    #[tokio::test]
+    async fn test_policy_enforced() {
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock("GET", "/redirect")
+            .with_status(302)
+            .with_header("location", "http://evil.example/secret")
+            .create_async()
+            .await;
+
+        let policy = UrlPolicy {
+            blocked_domains: vec!["evil.example".to_string()],
+            ..local_policy()
+        };
+        let fetcher =
+            HttpFetcher::new(FetchLimits::default(), policy).expect("Should create fetcher");
+        let err = fetcher
+            .fetch(&format!("{}/redirect", server.url()))
+            .await
+            .expect_err("Should block redirect");
+        assert!(
+            err.to_string().contains("evil.example is blocked"),
+            "{}",
+            err
+        );
+
+        // by default the mock server itself is blocked
+        let fetcher = HttpFetcher::new(FetchLimits::default(), UrlPolicy::default())
+            .expect("Should create fetcher");
+        let err = fetcher
+            .fetch(&format!("{}/redirect", server.url()))
+            .await
+            .expect_err("Should block local address");
+        assert!(err.to_string().contains("blocked"), "{}", err);
+
+        let policy = UrlPolicy {
+            allowed_domains: vec!["localhost".to_string()],
+            ..Default::default()
+        };
+        let fetcher =
+            BrowserlessFetcher::new(Some(server.url()), None, FetchLimits::default(), policy)
+                .expect("Should create fetcher");
+        let err = fetcher
+            .fetch("http://localhost:8645/admin/v1/peers")
+            .await
+            .expect_err("Should block localhost");
+        assert!(err.to_string().contains("blocked"), "{}", err);
+    }
+
+    #[tokio::test]
+    async fn test_cached_fetcher() {
+        let mut server = mockito::Server::new_async().await;
+        let cached = server
+            .mock("GET", "/cached")
+            .with_header("content-type", "text/html")
+            .with_header("cache-control", "public, max-age=600")
+            .with_body(HTML)
+            .expect(1)
+            .create_async()
+            .await;
+        let uncached = server
+            .mock("GET", "/uncached")
+            .with_header("content-type", "text/html")
+            .with_header("cache-control", "no-store")
+            .with_body(HTML)
+            .expect(2)
+            .create_async()
+            .await;
+
+        let cache = Arc::new(ContentCache::default());
+        let inner = HttpFetcher::new(FetchLimits::default(), local_policy())
+            .expect("Should create fetcher");
+        let fetcher = CachedFetcher::new(Arc::new(inner), cache.clone(), local_policy());
+
+        for _ in 0..2 {
+            let page = fetcher
+                .fetch(&format!("{}/cached#section", server.url()))
+                .await
+                .expect("Should fetch page");
+            assert_eq!(page.body, HTML);
+            fetcher
+                .fetch(&format!("{}/uncached", server.url()))
+                .await
+                .expect("Should fetch page");
+        }
+
+        cached.assert_async().await;
+        uncached.assert_async().await;
+        assert_eq!(cache.stats().hits, 1);
+
+        // a cached page is not served once its domain resolves to a blocked address
+        let url = "http://localhost:8645/cached";
+        let page = Page {
+            url: url.to_string(),
+            content_type: "text/html".to_string(),
+            body: HTML.to_string(),
+            cache_control: None,
+        };
+        cache
+            .insert(
+                &format!("page:{}", normalize_url(url)),
+                serde_json::to_string(&page).expect("Should serialize"),
+                cache.ttl(),
+            )
+            .await;
+        let inner = HttpFetcher::new(FetchLimits::default(), UrlPolicy::default())
+            .expect("Should create fetcher");
+        let fetcher = CachedFetcher::new(Arc::new(inner), cache.clone(), UrlPolicy::default());
+        let err = fetcher
+            .fetch(url)
+            .await
+            .expect_err("Should block localhost");
+        assert!(err.to_string().contains("blocked"), "{}", err);
+    }
+}
@@ -1,8 +1,6 @@
-pub mod scraper;
-pub mod finance;
-pub mod search_ddg;
-
-
-pub use self::finance::StockScraper;
-pub use self::search_ddg::DDGSearcher;
-pub use self::scraper::Scraper;
+pub mod cache;
+pub mod extract;
+pub mod fetcher;
+pub mod policy;
+pub mod prompt;
+pub mod provider;
@@ -0,0 +1,351 @@
+use reqwest::{
+    dns::{Addrs, Name, Resolve, Resolving},
+    redirect, ClientBuilder,
+};
+use std::{
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    sync::Arc,
+};
+use url::{Host, Url};
+
+use crate::errors::{NodeError, NodeResult};
+
+/// Maximum number of redirects followed by a guarded client.
+const MAX_REDIRECTS: usize = 10;
+
+/// # URL Policy
+///
+/// Decides which URLs the search tools are allowed to fetch. The language model chooses what to fetch,
+/// so without this policy it could reach services on the operator's machine or network, such as
+/// the Waku REST API, Ollama or cloud metadata endpoints.
+///
+/// By default, only `http` and `https` URLs are allowed, and hosts that resolve to loopback, private,
+/// link-local or otherwise non-public addresses are blocked.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct UrlPolicy {
+    /// If not empty, only these domains and their subdomains are allowed.
+    pub allowed_domains: Vec<String>,
+    /// These domains and their subdomains are blocked.
+    pub blocked_domains: Vec<String>,
+    /// Allows non-public addresses, only meant for testing and trusted setups.
+    pub allow_private_networks: bool,
+}
+
+impl UrlPolicy {
+    /// Creates a policy with the given domain lists, which are normalized.
+    pub fn new(
+        allowed_domains: &[String],
+        blocked_domains: &[String],
+        allow_private_networks: bool,
+    ) -> Self {
+        let normalize = |domains: &[String]| -> Vec<String> {
+            domains
+                .iter()
+                .map(|domain| normalize_domain(domain))
+                .filter(|domain| !domain.is_empty())
+                .collect()
+        };
+
+        Self {
+            allowed_domains: normalize(allowed_domains),
+            blocked_domains: normalize(blocked_domains),
+            allow_private_networks,
+        }
+    }
+
+    /// Parses the URL and checks its scheme, its domain and its address if the host is an IP.
+    ///
+    /// An IP host is also checked against the domain lists, so that it can not bypass an allow-list.
+    /// This does not resolve domains, see [`UrlPolicy::check`] for that.
+    pub fn check_url(&self, url: &str) -> NodeResult<Url> {
+        let parsed =
+            Url::parse(url).map_err(|e| NodeError::tool(format!("Invalid URL {}: {}", url, e)))?;
+
+        if !matches!(parsed.scheme(), "http" | "https") {
+            return Err(NodeError::tool(format!(
+                "URL scheme {} is not allowed: {}",
+                parsed.scheme(),
+                url
+            )));
+        }
+
+        match parsed.host() {
+            Some(Host::Domain(domain)) => self.check_domain(domain)?,
+            Some(Host::Ipv4(ip)) => {
+                self.check_ip(IpAddr::V4(ip))?;
+                self.check_domain(&ip.to_string())?;
+            }
+            Some(Host::Ipv6(ip)) => {
+                self.check_ip(IpAddr::V6(ip))?;
+                self.check_domain(&ip.to_string())?;
+            }
+            None => return Err(NodeError::tool(format!("URL has no host: {}", url))),
+        };
+
+        Ok(parsed)
+    }
+
+    /// Checks the URL, and resolves its domain to check every address that it points to.
+    pub async fn check(&self, url: &str) -> NodeResult<Url> {
+        let parsed = self.check_url(url)?;
+
+        if let Some(Host::Domain(domain)) = parsed.host() {
+            let port = parsed.port_or_known_default().unwrap_or(80);
+            let addrs = tokio::net::lookup_host((domain, port))
+                .await
+                .map_err(|e| NodeError::tool(format!("Could not resolve {}: {}", domain, e)))?;
+            for addr in addrs {
+                self.check_ip(addr.ip()).map_err(|_| {
+                    NodeError::tool(format!("{} resolves to a blocked address", domain))
+                })?;
+            }
+        }
+
+        Ok(parsed)
+    }
+
+    /// Checks the domain against the allowed and blocked domains.
+    pub fn check_domain(&self, domain: &str) -> NodeResult<()> {
+        let domain = normalize_domain(domain);
+
+        if self
+            .blocked_domains
+            .iter()
+            .any(|blocked| is_subdomain(&domain, blocked))
+        {
+            return Err(NodeError::tool(format!("Domain {} is blocked", domain)));
+        }
+
+        if !self.allowed_domains.is_empty()
+            && !self
+                .allowed_domains
+                .iter()
+                .any(|allowed| is_subdomain(&domain, allowed))
+        {
+            return Err(NodeError::tool(format!(
+                "Domain {} is not in the allowed domains",
+                domain
+            )));
+        }
+
+        Ok(())
+    }
+
+    /// Checks that the address is public, unless private networks are allowed.
+    pub fn check_ip(&self, ip: IpAddr) -> NodeResult<()> {
+        if !self.allow_private_networks && !is_public_ip(ip) {
+            return Err(NodeError::tool(format!("Address {} is blocked", ip)));
+        }
+
+        Ok(())
+    }
+}
+
+/// Returns a client builder that enforces the policy on every redirect, and on every address that a
+/// domain resolves to at connection time.
+///
+/// The URL of the first request is not checked by the client, see [`UrlPolicy::check_url`] for that.
+pub fn guarded_client_builder(policy: Arc<UrlPolicy>) -> ClientBuilder {
+    let redirect_policy = policy.clone();
+    ClientBuilder::new()
+        .dns_resolver(Arc::new(PolicyResolver::new(policy)))
+        .redirect(redirect::Policy::custom(move |attempt| {
+            if attempt.previous().len() >= MAX_REDIRECTS {
+                return attempt.error(format!(
+                    "Too many redirects, at most {} are followed",
+                    MAX_REDIRECTS
+                ));
+            }
+
+            match redirect_policy.check_url(attempt.url().as_str()) {
+                Ok(_) => attempt.follow(),
+                Err(e) => attempt.error(format!("Redirect blocked: {}", e)),
+            }
+        }))
+}
+
+/// A DNS resolver that drops addresses blocked by the policy, so that the addresses are checked
+/// again at connection time; a domain may resolve differently than it did when the URL was checked.
+struct PolicyResolver {
+    policy: Arc<UrlPolicy>,
+}
+
+impl PolicyResolver {
+    fn new(policy: Arc<UrlPolicy>) -> Self {
+        Self { policy }
+    }
+}
+
+impl Resolve for PolicyResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        let policy = self.policy.clone();
+        Box::pin(async move {
+            let host = name.as_str();
+            let addrs: Vec<SocketAddr> = tokio::net::lookup_host((host, 0))
+                .await?
+                .filter(|addr| policy.check_ip(addr.ip()).is_ok())
+                .collect();
+
+            if addrs.is_empty() {
+                return Err(format!("{} resolves to a blocked address", host).into());
+            }
+
+            Ok(Box::new(addrs.into_iter()) as Addrs)
+        })
+    }
+}
+
+#[inline]
+fn normalize_domain(domain: &str) -> String {
+    domain.trim().trim_end_matches('.').to_lowercase()
+}
+
+/// Returns `true` if the domain is the parent itself, or a subdomain of it.
+#[inline]
+fn is_subdomain(domain: &str, parent: &str) -> bool {
+    domain == parent || domain.ends_with(&format!(".{}", parent))
+}
+
+/// Returns `true` if the address is publicly routable.
+fn is_public_ip(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => is_public_ipv4(ip),
+        IpAddr::V6(ip) => is_public_ipv6(ip),
+    }
+}
+
+fn is_public_ipv4(ip: Ipv4Addr) -> bool {
+    let [a, b, c, _] = ip.octets();
+
+    !(ip.is_unspecified()
+        || ip.is_loopback()
+        || ip.is_private()
+        || ip.is_link_local()
+        || ip.is_broadcast()
+        || ip.is_multicast()
+        || ip.is_documentation()
+        || a == 0 // "this" network
+        || (a == 100 && (64..128).contains(&b)) // shared address space (carrier-grade NAT)
+        || (a == 192 && b == 0 && c == 0) // IETF protocol assignments
+        || (a == 198 && (18..20).contains(&b)) // benchmarking
+        || a >= 240) // reserved
+}
+
+fn is_public_ipv6(ip: Ipv6Addr) -> bool {
+    // addresses that embed an IPv4 address are judged by that address
+    if let Some(ipv4) = ip.to_ipv4_mapped() {
+        return is_public_ipv4(ipv4);
+    }
+
+    let segments = ip.segments();
+    if segments[0] == 0x64 && segments[1] == 0xff9b {
+        // NAT64 well-known prefix
+        let [_, _, _, _, _, _, hi, lo] = segments;
+        return is_public_ipv4(Ipv4Addr::from(((hi as u32) << 16) | lo as u32));
+    }
+
+    !(ip.is_unspecified()
+        || ip.is_loopback()
+        || ip.is_multicast()
+        || (segments[0] & 0xfe00) == 0xfc00 // unique local
+        || (segments[0] & 0xffc0) == 0xfe80 // link-local
+        || (segments[0] == 0x2001 && segments[1] == 0x0db8)) // documentation
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_public_ips() {
+        let cases = [
+            ("8.8.8.8", true),
+            ("1.1.1.1", true),
+            ("127.0.0.1", false),
+            ("10.1.2.3", false),
+            ("172.16.0.1", false),
+            ("192.168.1.1", false),
+            ("169.254.169.254", false), // cloud metadata
+            ("100.64.0.1", false),
+            ("0.0.0.0", false),
+            ("255.255.255.255", false),
+            ("2606:4700:4700::1111", true),
+            ("::1", false),
+            ("::", false),
+            ("fd00::1", false),
+            ("fe80::1", false),
+            ("::ffff:127.0.0.1", false),
+            ("::ffff:8.8.8.8", true),
+            ("64:ff9b::a9fe:a9fe", false), // NAT64 of 169.254.169.254
+        ];
+
+        for (ip, expected) in cases {
+            let ip: IpAddr = ip.parse().expect("Should parse IP");
+            assert_eq!(is_public_ip(ip), expected, "{}", ip);
+        }
+    }
+
+    #[test]
+    fn test_check_url() {
+        let policy = UrlPolicy::default();
+
+        assert!(policy.check_url("https://example.com/page").is_ok());
+        assert!(policy
+            .check_url("http://127.0.0.1:8645/admin/v1/peers")
+            .is_err());
+        assert!(policy.check_url("http://[::1]:11434/api/tags").is_err());
+        assert!(policy
+            .check_url("http://169.254.169.254/latest/meta-data")
+            .is_err());
+        assert!(policy.check_url("file:///etc/passwd").is_err());
+        assert!(policy.check_url("not a url").is_err());
+
+        let policy = UrlPolicy {
+            allow_private_networks: true,
+            ..Default::default()
+        };
+        assert!(policy.check_url("http://127.0.0.1:8645").is_ok());
+    }
+
+    #[test]
+    fn test_domain_lists() {
+        let policy = UrlPolicy {
+            allowed_domains: vec!["wikipedia.org".to_string(), "arxiv.org".to_string()],
+            blocked_domains: vec!["secret.wikipedia.org".to_string()],
+            allow_private_networks: false,
+        };
+
+        assert!(policy.check_url("https://wikipedia.org").is_ok());
+        assert!(policy
+            .check_url("https://en.Wikipedia.org./wiki/Llama")
+            .is_ok());
+        assert!(policy.check_url("https://arxiv.org/abs/2302.13971").is_ok());
+        assert!(policy.check_url("https://secret.wikipedia.org").is_err());
+        assert!(policy.check_url("https://notwikipedia.org").is_err());
+        assert!(policy.check_url("https://example.com").is_err());
+        assert!(policy.check_url("https://8.8.8.8").is_err());
+    }
+
+    #[test]
+    fn test_new_policy() {
+        let policy = UrlPolicy::new(
+            &["Wikipedia.org.".to_string(), "arxiv.org".to_string()],
+            &[],
+            true,
+        );
+        assert_eq!(policy.allowed_domains, vec!["wikipedia.org", "arxiv.org"]);
+        assert!(policy.blocked_domains.is_empty());
+        assert!(policy.allow_private_networks);
+        assert_eq!(UrlPolicy::new(&[], &[], false), UrlPolicy::default());
+    }
+
+    #[tokio::test]
+    async fn test_check_resolves_domain() {
+        let policy = UrlPolicy::default();
+        let err = policy
+            .check("http://localhost:11434/api/tags")
+            .await
+            .expect_err("Should block localhost");
+        assert!(err.to_string().contains("blocked"));
+    }
+}
@@ -0,0 +1,751 @@
+use async_trait::async_trait;
+use reqwest::Client;
+use scraper::{Html, Selector};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{fmt, sync::Arc, time::Duration};
+
+use super::policy::{guarded_client_builder, UrlPolicy};
+use crate::errors::{NodeError, NodeResult};
+
+/// Default search providers, in order of failover.
+pub const DEFAULT_DKN_SEARCH_PROVIDERS: &str = "duckduckgo";
+
+/// DuckDuckGo HTML-only search endpoint, which does not require JavaScript.
+const DDG_SEARCH_URL: &str = "https://html.duckduckgo.com/html/";
+
+/// Brave web search API endpoint.
+const BRAVE_SEARCH_URL: &str = "https://api.search.brave.com/res/v1/web/search";
+
+/// Bing web search API endpoint.
+const BING_SEARCH_URL: &str = "https://api.bing.microsoft.com/v7.0/search";
+
+/// Timeout of a single search request.
+const SEARCH_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// Maximum number of results returned by a provider.
+const MAX_RESULTS: usize = 10;
+
+/// A single web search result, the same regardless of the provider.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SearchResult {
+    pub title: String,
+    pub url: String,
+    pub snippet: String,
+    /// Position of the result within the results of its provider, starting from 1.
+    pub rank: usize,
+}
+
+/// A backend that searches the web.
+#[async_trait]
+pub trait SearchProvider: Send + Sync {
+    /// Name of the backend, for logging.
+    fn name(&self) -> &'static str;
+
+    /// Searches the web for the given query, and returns the results ranked from 1.
+    async fn search(&self, query: &str) -> NodeResult<Vec<SearchResult>>;
+}
+
+/// A configured search provider, see [`provider_from_config`].
+#[derive(Clone, PartialEq)]
+pub enum ProviderConfig {
+    DuckDuckGo,
+    Searxng { url: String },
+    JsonApi { kind: JsonApiKind, api_key: String },
+}
+
+impl fmt::Debug for ProviderConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::DuckDuckGo => write!(f, "DuckDuckGo"),
+            Self::Searxng { url } => f.debug_struct("Searxng").field("url", url).finish(),
+            Self::JsonApi { kind, .. } => f
+                .debug_struct("JsonApi")
+                .field("kind", kind)
+                .field("api_key", &"<redacted>")
+                .finish(),
+        }
+    }
+}
+
+/// Creates the given providers in order of failover, where DuckDuckGo is subject to the policy.
+pub fn provider_from_config(
+    configs: &[ProviderConfig],
+    policy: &UrlPolicy,
+) -> NodeResult<Arc<dyn SearchProvider>> {
+    let mut providers: Vec<Arc<dyn SearchProvider>> = Vec::new();
+    for config in configs {
+        let provider: Arc<dyn SearchProvider> = match config {
+            ProviderConfig::DuckDuckGo => Arc::new(DuckDuckGoProvider::new(None, policy.clone())?),
+            ProviderConfig::Searxng { url } => Arc::new(SearxngProvider::new(url.clone())?),
+            ProviderConfig::JsonApi { kind, api_key } => {
+                Arc::new(JsonApiProvider::new(*kind, None, api_key.clone())?)
+            }
+        };
+        providers.push(provider);
+    }
+
+    if providers.is_empty() {
+        return Err(NodeError::Config(
+            "No search providers are configured.".to_string(),
+        ));
+    }
+
+    let names = providers.iter().map(|p| p.name()).collect::<Vec<_>>();
+    log::info!("Search providers: {}", names.join(", "));
+    if providers.len() == 1 {
+        Ok(providers.remove(0))
+    } else {
+        Ok(Arc::new(FailoverProvider::new(providers)))
+    }
+}
+
+/// Tries each provider in order, and returns the results of the first one that finds any.
+///
+/// An empty result is only returned if no provider found anything, and at least one of them did
+/// not error.
+pub struct FailoverProvider {
+    providers: Vec<Arc<dyn SearchProvider>>,
+}
+
+impl FailoverProvider {
+    pub fn new(providers: Vec<Arc<dyn SearchProvider>>) -> Self {
+        Self { providers }
+    }
+}
+
+#[async_trait]
+impl SearchProvider for FailoverProvider {
+    fn name(&self) -> &'static str {
+        "failover"
+    }
+
+    async fn search(&self, query: &str) -> NodeResult<Vec<SearchResult>> {
+        let mut errors = Vec::new();
+        let mut empty = false;
+        for provider in &self.providers {
+            match provider.search(query).await {
+                Ok(results) if !results.is_empty() => return Ok(results),
+                Ok(_) => {
+                    log::warn!("Search provider {} found no results", provider.name());
+                    empty = true;
+                }
+                Err(e) => {
+                    log::warn!("Search provider {} failed: {}", provider.name(), e);
+                    errors.push(format!("{}: {}", provider.name(), e));
+                }
+            }
+        }
+
+        if empty {
+            return Ok(Vec::new());
+        }
+        Err(NodeError::tool(format!(
+            "All search providers failed ({})",
+            errors.join("; ")
+        )))
+    }
+}
+
+/// Searches the HTML version of DuckDuckGo, skipping ads.
+///
+/// When rate limited, DuckDuckGo responds with a challenge page instead of an error status, so a
+/// page without results is an error unless it is the page that states there are no results. The
+/// policy is enforced on redirects and resolved addresses.
+#[derive(Debug, Clone)]
+pub struct DuckDuckGoProvider {
+    client: Client,
+    base_url: String,
+}
+
+impl DuckDuckGoProvider {
+    /// Creates a new DuckDuckGo provider, the URL defaults to the public HTML endpoint.
+    pub fn new(base_url: Option<String>, policy: UrlPolicy) -> NodeResult<Self> {
+        let client = guarded_client_builder(Arc::new(policy))
+            .timeout(SEARCH_TIMEOUT)
+            .build()?;
+
+        Ok(Self {
+            client,
+            base_url: base_url.unwrap_or(DDG_SEARCH_URL.to_string()),
+        })
+    }
+}
+
+#[async_trait]
+impl SearchProvider for DuckDuckGoProvider {
+    fn name(&self) -> &'static str {
+        "duckduckgo"
+    }
+
+    async fn search(&self, query: &str) -> NodeResult<Vec<SearchResult>> {
+        let response = self
+            .client
+            .get(&self.base_url)
+            .query(&[("q", query)])
+            .header("Accept", "text/html")
+            .send()
+            .await?
+            .error_for_status()?;
+
+        // the challenge page is served with 202 Accepted
+        if response.status() != reqwest::StatusCode::OK {
+            return Err(NodeError::tool(format!(
+                "DuckDuckGo responded with {}, it may be rate limited",
+                response.status()
+            )));
+        }
+
+        let body = response.text().await?;
+        let results = parse_ddg_results(&body);
+        if results.is_empty() && !is_ddg_no_results(&body) {
+            return Err(NodeError::tool(
+                "DuckDuckGo returned a page without results, it may be rate limited",
+            ));
+        }
+
+        Ok(results)
+    }
+}
+
+/// Searches a [SearxNG](https://docs.searxng.org/) instance through its JSON API, which must be
+/// enabled within the `formats` of its settings.
+///
+/// SearxNG is usually self-hosted on a private address, so the URL policy does not apply to it.
+#[derive(Debug, Clone)]
+pub struct SearxngProvider {
+    client: Client,
+    base_url: String,
+}
+
+impl SearxngProvider {
+    pub fn new(base_url: String) -> NodeResult<Self> {
+        let client = Client::builder().timeout(SEARCH_TIMEOUT).build()?;
+
+        Ok(Self {
+            client,
+            base_url: base_url.trim_end_matches('/').to_string(),
+        })
+    }
+}
+
+#[async_trait]
+impl SearchProvider for SearxngProvider {
+    fn name(&self) -> &'static str {
+        "searxng"
+    }
+
+    async fn search(&self, query: &str) -> NodeResult<Vec<SearchResult>> {
+        let body: Value = self
+            .client
+            .get(format!("{}/search", self.base_url))
+            .query(&[("q", query), ("format", "json")])
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        parse_json_results(&body, "/results", ("title", "url", "content"))
+    }
+}
+
+/// A commercial web search API that returns JSON.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JsonApiKind {
+    Brave,
+    Bing,
+}
+
+impl JsonApiKind {
+    fn default_url(&self) -> &'static str {
+        match self {
+            Self::Brave => BRAVE_SEARCH_URL,
+            Self::Bing => BING_SEARCH_URL,
+        }
+    }
+
+    /// Header that carries the API key.
+    fn key_header(&self) -> &'static str {
+        match self {
+            Self::Brave => "X-Subscription-Token",
+            Self::Bing => "Ocp-Apim-Subscription-Key",
+        }
+    }
+
+    /// Pointer to the array of results, and the title, URL and snippet fields of each result.
+    fn fields(&self) -> (&'static str, (&'static str, &'static str, &'static str)) {
+        match self {
+            Self::Brave => ("/web/results", ("title", "url", "description")),
+            Self::Bing => ("/webPages/value", ("name", "url", "snippet")),
+        }
+    }
+}
+
+/// Searches a Brave or Bing style JSON API, authenticated with an API key.
+#[derive(Debug, Clone)]
+pub struct JsonApiProvider {
+    client: Client,
+    kind: JsonApiKind,
+    base_url: String,
+    api_key: String,
+}
+
+impl JsonApiProvider {
+    /// Creates a new API provider, the URL defaults to the public endpoint of the API.
+    pub fn new(kind: JsonApiKind, base_url: Option<String>, api_key: String) -> NodeResult<Self> {
+        let client = Client::builder().timeout(SEARCH_TIMEOUT).build()?;
+
+        Ok(Self {
+            client,
+            kind,
+            base_url: base_url.unwrap_or(kind.default_url().to_string()),
+            api_key,
+        })
+    }
+}
+
+#[async_trait]
+impl SearchProvider for JsonApiProvider {
+    fn name(&self) -> &'static str {
+        match self.kind {
+            JsonApiKind::Brave => "brave",
+            JsonApiKind::Bing => "bing",
+        }
+    }
+
+    async fn search(&self, query: &str) -> NodeResult<Vec<SearchResult>> {
+        let body: Value = self
+            .client
+            .get(&self.base_url)
+            .query(&[("q", query), ("count", &MAX_RESULTS.to_string())])
+            .header(self.kind.key_header(), &self.api_key)
+            .header("Accept", "application/json")
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        let (pointer, fields) = self.kind.fields();
+        parse_json_results(&body, pointer, fields)
+    }
+}
+
+/// Reads the results at the given pointer of a JSON response, along with their title, URL and
+/// snippet fields. Results without a title or URL are skipped, and highlighting markup is removed.
+///
+/// A response without the results array is an error, but a response with an empty one is not.
+fn parse_json_results(
+    body: &Value,
+    pointer: &str,
+    (title, url, snippet): (&str, &str, &str),
+) -> NodeResult<Vec<SearchResult>> {
+    let results = body
+        .pointer(pointer)
+        .and_then(Value::as_array)
+        .ok_or_else(|| NodeError::tool(format!("Search response has no {} array", pointer)))?;
+
+    Ok(results
+        .iter()
+        .filter_map(|result| {
+            Some((
+                strip_markup(result[title].as_str()?),
+                result[url].as_str()?.to_string(),
+                strip_markup(result[snippet].as_str().unwrap_or_default()),
+            ))
+        })
+        .take(MAX_RESULTS)
+        .enumerate()
+        .map(|(index, (title, url, snippet))| SearchResult {
+            title,
+            url,
+            snippet,
+            rank: index + 1,
+        })
+        .collect())
+}
+
+/// Removes tags such as `<strong>` from a snippet, and decodes its entities.
+fn strip_markup(text: &str) -> String {
+    Html::parse_fragment(text)
+        .root_element()
+        .text()
+        .collect::<String>()
+        .trim()
+        .to_string()
+}
+
+/// Parses the organic results from a DuckDuckGo HTML search page, skipping ads.
+pub(crate) fn parse_ddg_results(html: &str) -> Vec<SearchResult> {
+    let document = Html::parse_document(html);
+    let result_selector =
+        Selector::parse("div.result:not(.result--ad)").expect("Should parse selector");
+    let title_selector = Selector::parse("a.result__a").expect("Should parse selector");
+    let snippet_selector = Selector::parse(".result__snippet").expect("Should parse selector");
+
+    document
+        .select(&result_selector)
+        .filter_map(|result| {
+            let anchor = result.select(&title_selector).next()?;
+            let url = resolve_ddg_link(anchor.value().attr("href")?)?;
+            let title = anchor.text().collect::<String>().trim().to_string();
+            let snippet = result
+                .select(&snippet_selector)
+                .next()
+                .map(|s| s.text().collect::<String>().trim().to_string())
+                .unwrap_or_default();
+
+            Some((title, url, snippet))
+        })
+        .take(MAX_RESULTS)
+        .enumerate()
+        .map(|(index, (title, url, snippet))| SearchResult {
+            title,
+            url,
+            snippet,
+            rank: index + 1,
+        })
+        .collect()
+}
+
+/// Returns `true` if the DuckDuckGo page states that there are no results for the query.
+fn is_ddg_no_results(html: &str) -> bool {
+    let selector = Selector::parse(".no-results").expect("Should parse selector");
+    Html::parse_document(html)
+        .select(&selector)
+        .next()
+        .is_some()
+}
+
+/// DuckDuckGo wraps result links in a redirect such as `//duckduckgo.com/l/?uddg=<url>`,
+/// this function returns the actual target of such links.
+fn resolve_ddg_link(href: &str) -> Option<String> {
+    let absolute = if href.starts_with("//") {
+        format!("https:{}", href)
+    } else {
+        href.to_string()
+    };
+
+    let url = url::Url::parse(&absolute).ok()?;
+    if url.path() == "/l/" {
+        url.query_pairs()
+            .find(|(key, _)| key == "uddg")
+            .map(|(_, value)| value.to_string())
+    } else {
+        Some(url.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DDG_HTML: &str = include_str!(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/fixtures/ddg_search.html"
+    ));
+    const SEARXNG_JSON: &str = include_str!(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/fixtures/searxng_search.json"
+    ));
+    const BRAVE_JSON: &str = include_str!(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/fixtures/brave_search.json"
+    ));
+    const BING_JSON: &str = include_str!(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/fixtures/bing_search.json"
+    ));
+
+    const QUERY: &str = "who built llama3";
+
+    /// The stub server is local, so private networks must be allowed.
+    fn local_policy() -> UrlPolicy {
+        UrlPolicy {
+            allow_private_networks: true,
+            ..Default::default()
+        }
+    }
+
+    /// All recorded responses are for the same query, so they share their top result.
+    fn assert_top_result(results: &[SearchResult]) {
+        assert_eq!(results[0].rank, 1);
+        assert_eq!(results[0].title, "Meta Llama 3");
+        assert_eq!(results[0].url, "https://llama.meta.com/llama3/");
+        assert_eq!(
+            results[0].snippet,
+            "Build the future of AI with Meta Llama 3."
+        );
+    }
+
+    #[test]
+    fn test_parse_ddg_results() {
+        let results = parse_ddg_results(DDG_HTML);
+        assert_eq!(results.len(), 3, "Ad result should be skipped");
+        assert_top_result(&results);
+
+        assert_eq!(
+            results[1].url,
+            "https://en.wikipedia.org/wiki/Llama_(language_model)"
+        );
+        assert_eq!(results[2].url, "https://github.com/meta-llama/llama3");
+        assert_eq!(results[2].rank, 3);
+
+        let no_results = "<html><body><div class=\"no-results\">No results.</div></body></html>";
+        assert!(parse_ddg_results(no_results).is_empty());
+        assert!(is_ddg_no_results(no_results));
+        assert!(!is_ddg_no_results(DDG_HTML));
+    }
+
+    #[tokio::test]
+    async fn test_duckduckgo_rate_limited() {
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock("GET", "/challenge/")
+            .match_query(mockito::Matcher::Any)
+            .with_header("content-type", "text/html")
+            .with_body("<html><body><form id=\"challenge-form\"></form></body></html>")
+            .create_async()
+            .await;
+        server
+            .mock("GET", "/accepted/")
+            .match_query(mockito::Matcher::Any)
+            .with_status(202)
+            .with_header("content-type", "text/html")
+            .with_body("<html><body></body></html>")
+            .create_async()
+            .await;
+        let brave = server
+            .mock("GET", "/brave")
+            .match_query(mockito::Matcher::Any)
+            .with_header("content-type", "application/json")
+            .with_body(BRAVE_JSON)
+            .expect(2)
+            .create_async()
+            .await;
+
+        for path in ["/challenge/", "/accepted/"] {
+            let ddg =
+                DuckDuckGoProvider::new(Some(format!("{}{}", server.url(), path)), local_policy())
+                    .expect("Should create provider");
+            let error = ddg.search(QUERY).await.expect_err("Should be rate limited");
+            assert!(error.to_string().contains("rate limited"), "{}", error);
+
+            let brave = JsonApiProvider::new(
+                JsonApiKind::Brave,
+                Some(format!("{}/brave", server.url())),
+                "key".to_string(),
+            )
+            .expect("Should create provider");
+            let provider = FailoverProvider::new(vec![Arc::new(ddg), Arc::new(brave)]);
+            let results = provider.search(QUERY).await.expect("Should fail over");
+            assert_top_result(&results);
+        }
+
+        brave.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_failover_on_empty_results() {
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock("GET", "/search")
+            .match_query(mockito::Matcher::Any)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"results": []}"#)
+            .expect(2)
+            .create_async()
+            .await;
+        server
+            .mock("GET", "/brave")
+            .match_query(mockito::Matcher::Any)
+            .with_header("content-type", "application/json")
+            .with_body(BRAVE_JSON)
+            .create_async()
+            .await;
+
+        let searxng = Arc::new(SearxngProvider::new(server.url()).expect("Should create provider"));
+        let brave = Arc::new(
+            JsonApiProvider::new(
+                JsonApiKind::Brave,
+                Some(format!("{}/brave", server.url())),
+                "key".to_string(),
+            )
+            .expect("Should create provider"),
+        );
+
+        let provider = FailoverProvider::new(vec![searxng.clone(), brave]);
+        assert_top_result(&provider.search(QUERY).await.expect("Should fail over"));
+
+        // no results at all is not an error
+        let provider = FailoverProvider::new(vec![searxng]);
+        assert!(provider
+            .search(QUERY)
+            .await
+            .expect("Should search")
+            .is_empty());
+    }
+
+    #[test]
+    fn test_provider_from_config() {
+        let brave = ProviderConfig::JsonApi {
+            kind: JsonApiKind::Brave,
+            api_key: "key".to_string(),
+        };
+        let provider = provider_from_config(
+            &[ProviderConfig::DuckDuckGo, brave.clone()],
+            &UrlPolicy::default(),
+        )
+        .expect("Should create");
+        assert_eq!(provider.name(), "failover");
+
+        let provider =
+            provider_from_config(&[brave], &UrlPolicy::default()).expect("Should create");
+        assert_eq!(provider.name(), "brave");
+
+        assert!(provider_from_config(&[], &UrlPolicy::default()).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_duckduckgo_provider() {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("GET", "/html/")
+            .match_query(mockito::Matcher::UrlEncoded("q".into(), QUERY.into()))
+            .with_header("content-type", "text/html")
+            .with_body(DDG_HTML)
+            .create_async()
+            .await;
+
+        let provider =
+            DuckDuckGoProvider::new(Some(format!("{}/html/", server.url())), local_policy())
+                .expect("Should create provider");
+        let results = provider.search(QUERY).await.expect("Should search");
+
+        assert_eq!(results.len(), 3);
+        assert_top_result(&results);
+        mock.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_searxng_provider() {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("GET", "/search")
+            .match_query(mockito::Matcher::AllOf(vec![
+                mockito::Matcher::UrlEncoded("q".into(), QUERY.into()),
+                mockito::Matcher::UrlEncoded("format".into(), "json".into()),
+            ]))
+            .with_header("content-type", "application/json")
+            .with_body(SEARXNG_JSON)
+            .create_async()
+            .await;
+
+        let provider =
+            SearxngProvider::new(format!("{}/", server.url())).expect("Should create provider");
+        let results = provider.search(QUERY).await.expect("Should search");
+
+        assert_eq!(results.len(), 2, "Result without URL should be skipped");
+        assert_top_result(&results);
+        assert_eq!(results[1].rank, 2);
+        mock.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_json_api_providers() {
+        let mut server = mockito::Server::new_async().await;
+        let brave = server
+            .mock("GET", "/brave")
+            .match_header("X-Subscription-Token", "brave-key")
+            .match_query(mockito::Matcher::UrlEncoded("q".into(), QUERY.into()))
+            .with_header("content-type", "application/json")
+            .with_body(BRAVE_JSON)
+            .create_async()
+            .await;
+        let bing = server
+            .mock("GET", "/bing")
+            .match_header("Ocp-Apim-Subscription-Key", "bing-key")
+            .match_query(mockito::Matcher::UrlEncoded("q".into(), QUERY.into()))
+            .with_header("content-type", "application/json")
+            .with_body(BING_JSON)
+            .create_async()
+            .await;
+
+        for (kind, path, key) in [
+            (JsonApiKind::Brave, "/brave", "brave-key"),
+            (JsonApiKind::Bing, "/bing", "bing-key"),
+        ] {
+            let provider = JsonApiProvider::new(
+                kind,
+                Some(format!("{}{}", server.url(), path)),
+                key.to_string(),
+            )
+            .expect("Should create provider");
+            let results = provider.search(QUERY).await.expect("Should search");
+
+            assert_eq!(
+                results.len(),
+                3,
+                "{} should return 3 results",
+                provider.name()
+            );
+            assert_top_result(&results);
+        }
+
+        brave.assert_async().await;
+        bing.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_failover_provider() {
+        let mut server = mockito::Server::new_async().await;
+        let failing = server
+            .mock("GET", "/html/")
+            .match_query(mockito::Matcher::Any)
+            .with_status(503)
+            .create_async()
+            .await;
+        let malformed = server
+            .mock("GET", "/search")
+            .match_query(mockito::Matcher::Any)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"error": "rate limited"}"#)
+            .expect(2)
+            .create_async()
+            .await;
+        let working = server
+            .mock("GET", "/brave")
+            .match_query(mockito::Matcher::Any)
+            .with_header("content-type", "application/json")
+            .with_body(BRAVE_JSON)
+            .create_async()
+            .await;
+
+        let ddg = Arc::new(
+            DuckDuckGoProvider::new(Some(format!("{}/html/", server.url())), local_policy())
+                .expect("Should create provider"),
+        );
+        let searxng = Arc::new(SearxngProvider::new(server.url()).expect("Should create provider"));
+        let brave = Arc::new(
+            JsonApiProvider::new(
+                JsonApiKind::Brave,
+                Some(format!("{}/brave", server.url())),
+                "key".to_string(),
+            )
+            .expect("Should create provider"),
+        );
+
+        let provider = FailoverProvider::new(vec![ddg, searxng.clone(), brave]);
+        let results = provider.search(QUERY).await.expect("Should fail over");
+        assert_top_result(&results);
+
+        let provider = FailoverProvider::new(vec![searxng]);
+        let error = provider.search(QUERY).await.expect_err("Should fail");
+        assert!(error.to_string().contains("searxng"));
+
+        failing.assert_async().await;
+        malformed.assert_async().await;
+        working.assert_async().await;
+    }
+}
@@ -0,0 +1,386 @@
+use async_trait::async_trait;
+use langchain_rust::embedding::{
+    embedder_trait::Embedder, ollama::ollama_embedder::OllamaEmbedder, EmbedderError,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashSet,
+    sync::{Arc, RwLock},
+};
+
+use crate::{
+    compute::{
+        constants::{DEFAULT_DKN_OLLAMA_HOST, DEFAULT_DKN_OLLAMA_PORT},
+        ollama::OllamaConfig,
+        search::tools::scraper::ScrapedPage,
+    },
+    errors::{NodeError, NodeResult},
+};
+
+/// Default embedding model, pulled from Ollama.
+pub const DEFAULT_DKN_SEARCH_EMBEDDING_MODEL: &str = "nomic-embed-text";
+
+/// Embeds text with an Ollama embedding model.
+#[derive(Debug)]
+pub struct Embeddings {
+    pub(crate) embedder: OllamaEmbedder,
+    model: String,
+}
+
+impl Default for Embeddings {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Embeddings {
+    /// Creates a new embedder with the default Ollama server and embedding model.
+    pub fn new() -> Self {
+        Self::with_server(
+            DEFAULT_DKN_OLLAMA_HOST,
+            DEFAULT_DKN_OLLAMA_PORT,
+            DEFAULT_DKN_SEARCH_EMBEDDING_MODEL,
+        )
+    }
+
+    /// Creates an embedder with the configured Ollama server and the given embedding model.
+    pub fn new_from_config(ollama: &OllamaConfig, model: &str) -> Self {
+        Self::with_server(&ollama.host, ollama.port, model)
+    }
+
+    fn with_server(host: &str, port: u16, model: &str) -> Self {
+        Self {
+            embedder: OllamaEmbedder::new(model.to_string(), format!("{}:{}", host, port)),
+            model: model.to_string(),
+        }
+    }
+
+    /// Uses the given embedding model instead.
+    pub fn with_model(mut self, model: &str) -> Self {
+        self.embedder = self.embedder.with_model(model);
+        self.model = model.to_string();
+        self
+    }
+
+    /// Returns the name of the embedding model.
+    #[inline]
+    pub fn model(&self) -> &str {
+        &self.model
+    }
+
+    pub async fn embed_documents(
+        &self,
+        documents: &[String],
+    ) -> Result<Vec<Vec<f64>>, EmbedderError> {
+        self.embedder.embed_documents(documents).await
+    }
+
+    pub async fn embed_query(&self, query: &str) -> Result<Vec<f64>, EmbedderError> {
+        self.embedder.embed_query(query).await
+    }
+}
+
+#[async_trait]
+impl Embedder for Embeddings {
+    async fn embed_documents(&self, documents: &[String]) -> Result<Vec<Vec<f64>>, EmbedderError> {
+        Embeddings::embed_documents(self, documents).await
+    }
+
+    async fn embed_query(&self, text: &str) -> Result<Vec<f64>, EmbedderError> {
+        Embeddings::embed_query(self, text).await
+    }
+}
+
+/// Similarity measure between two vectors, higher is more similar.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Similarity {
+    #[default]
+    Cosine,
+    DotProduct,
+}
+
+impl Similarity {
+    /// Computes the similarity of two vectors of the same dimension.
+    pub fn score(&self, a: &[f64], b: &[f64]) -> f64 {
+        let dot: f64 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+        match self {
+            Self::DotProduct => dot,
+            Self::Cosine => {
+                let norms = norm(a) * norm(b);
+                if norms == 0.0 {
+                    0.0
+                } else {
+                    dot / norms
+                }
+            }
+        }
+    }
+}
+
+#[inline]
+fn norm(vector: &[f64]) -> f64 {
+    vector.iter().map(|x| x * x).sum::<f64>().sqrt()
+}
+
+/// A chunk of a scraped page, along with where it came from.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Chunk {
+    pub text: String,
+    /// URL of the page that the chunk belongs to.
+    pub url: String,
+    /// Title of the page that the chunk belongs to, if it has one.
+    pub title: Option<String>,
+    /// Position of the chunk within its page.
+    pub index: usize,
+}
+
+/// A chunk that matched a query, with its similarity score.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ScoredChunk {
+    #[serde(flatten)]
+    pub chunk: Chunk,
+    pub score: f64,
+}
+
+/// # Vector Store
+///
+/// An in-memory index of embedded chunks, meant to live for a single task so that the agent can
+/// retrieve the most relevant parts of every page it has scraped, instead of reading whole pages.
+pub struct VectorStore {
+    embedder: Arc<dyn Embedder>,
+    similarity: Similarity,
+    entries: RwLock<Vec<(Chunk, Vec<f64>)>>,
+}
+
+impl VectorStore {
+    pub fn new(embedder: Arc<dyn Embedder>, similarity: Similarity) -> Self {
+        Self {
+            embedder,
+            similarity,
+            entries: RwLock::new(Vec::new()),
+        }
+    }
+
+    /// Returns the number of chunks within the store.
+    pub fn len(&self) -> usize {
+        self.entries
+            .read()
+            .map(|entries| entries.len())
+            .unwrap_or_default()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Embeds and adds the chunks, skipping those that are already in the store.
+    ///
+    /// Returns the number of chunks added.
+    pub async fn add_chunks(&self, chunks: Vec<Chunk>) -> NodeResult<usize> {
+        let chunks: Vec<Chunk> = {
+            let entries = self
+                .entries
+                .read()
+                .map_err(|_| NodeError::Other("Vector store is poisoned.".to_string()))?;
+            let mut seen: HashSet<(String, String)> = entries
+                .iter()
+                .map(|(entry, _)| (entry.url.clone(), entry.text.clone()))
+                .collect();
+            chunks
+                .into_iter()
+                .filter(|chunk| !chunk.text.trim().is_empty())
+                .filter(|chunk| seen.insert((chunk.url.clone(), chunk.text.clone())))
+                .collect()
+        };
+        if chunks.is_empty() {
+            return Ok(0);
+        }
+
+        let texts: Vec<String> = chunks.iter().map(|chunk| chunk.text.clone()).collect();
+        let vectors = self.embedder.embed_documents(&texts).await?;
+        if vectors.len() != chunks.len() {
+            return Err(NodeError::Llm(
+                format!(
+                    "Expected {} embeddings, got {}.",
+                    chunks.len(),
+                    vectors.len()
+                )
+                .into(),
+            ));
+        }
+
+        let mut entries = self
+            .entries
+            .write()
+            .map_err(|_| NodeError::Other("Vector store is poisoned.".to_string()))?;
+        let dimension = entries
+            .first()
+            .map(|(_, vector)| vector.len())
+            .or_else(|| vectors.first().map(Vec::len));
+        if let Some(dimension) = dimension {
+            if let Some(vector) = vectors.iter().find(|vector| vector.len() != dimension) {
+                return Err(NodeError::Llm(
+                    format!(
+                        "Embedding dimension {} does not match the store dimension {}.",
+                        vector.len(),
+                        dimension
+                    )
+                    .into(),
+                ));
+            }
+        }
+
+        let added = chunks.len();
+        entries.extend(chunks.into_iter().zip(vectors));
+        Ok(added)
+    }
+
+    /// Adds every chunk of a scraped page, along with its URL and title.
+    pub async fn add_page(&self, page: &ScrapedPage) -> NodeResult<usize> {
+        let chunks = page
+            .chunks
+            .iter()
+            .enumerate()
+            .map(|(index, text)| Chunk {
+                text: text.clone(),
+                url: page.url.clone(),
+                title: page.metadata.title.clone(),
+                index,
+            })
+            .collect();
+
+        self.add_chunks(chunks).await
+    }
+
+    /// Returns the `k` chunks most similar to the query, most similar first.
+    pub async fn search(&self, query: &str, k: usize) -> NodeResult<Vec<ScoredChunk>> {
+        if self.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let vector = self.embedder.embed_query(query).await?;
+        self.search_by_vector(&vector, k)
+    }
+
+    /// Returns the `k` chunks most similar to the vector, most similar first.
+    pub fn search_by_vector(&self, vector: &[f64], k: usize) -> NodeResult<Vec<ScoredChunk>> {
+        let entries = self
+            .entries
+            .read()
+            .map_err(|_| NodeError::Other("Vector store is poisoned.".to_string()))?;
+
+        let mut scored: Vec<ScoredChunk> = entries
+            .iter()
+            .filter(|(_, entry)| entry.len() == vector.len())
+            .map(|(chunk, entry)| ScoredChunk {
+                chunk: chunk.clone(),
+                score: self.similarity.score(vector, entry),
+            })
+            .collect();
+
+        scored.sort_by(|a, b| b.score.total_cmp(&a.score));
+        scored.truncate(k);
+        Ok(scored)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// Embeds text as the counts of a few keywords, so that tests do not need Ollama.
+    pub(crate) struct KeywordEmbedder;
+
+    const KEYWORDS: [&str; 4] = ["llama", "meta", "tokio", "rust"];
+
+    #[async_trait]
+    impl Embedder for KeywordEmbedder {
+        async fn embed_documents(
+            &self,
+            documents: &[String],
+        ) -> Result<Vec<Vec<f64>>, EmbedderError> {
+            let mut vectors = Vec::new();
+            for document in documents {
+                vectors.push(self.embed_query(document).await?);
+            }
+            Ok(vectors)
+        }
+
+        async fn embed_query(&self, text: &str) -> Result<Vec<f64>, EmbedderError> {
+            let text = text.to_lowercase();
+            Ok(KEYWORDS
+                .iter()
+                .map(|keyword| text.matches(keyword).count() as f64)
+                .collect())
+        }
+    }
+
+    fn chunk(text: &str, url: &str) -> Chunk {
+        Chunk {
+            text: text.to_string(),
+            url: url.to_string(),
+            title: None,
+            index: 0,
+        }
+    }
+
+    #[test]
+    fn test_similarity() {
+        let a = [1.0, 0.0];
+        let b = [3.0, 4.0];
+
+        assert_eq!(Similarity::DotProduct.score(&a, &b), 3.0);
+        assert!((Similarity::Cosine.score(&a, &b) - 0.6).abs() < 1e-9);
+        assert_eq!(Similarity::Cosine.score(&a, &[0.0, 0.0]), 0.0);
+    }
+
+    #[tokio::test]
+    async fn test_top_k_search() {
+        let store = VectorStore::new(Arc::new(KeywordEmbedder), Similarity::Cosine);
+        let added = store
+            .add_chunks(vec![
+                chunk(
+                    "Meta released Llama 3, the next Llama model.",
+                    "https://ai.meta.com",
+                ),
+                chunk("Tokio is an async runtime for Rust.", "https://tokio.rs"),
+                chunk("Rust has no garbage collector.", "https://rust-lang.org"),
+                // duplicates and empty chunks are skipped
+                chunk("Tokio is an async runtime for Rust.", "https://tokio.rs"),
+                chunk("  ", "https://tokio.rs"),
+            ])
+            .await
+            .expect("Should add chunks");
+        assert_eq!(added, 3);
+        assert_eq!(store.len(), 3);
+
+        let results = store
+            .search("who built llama?", 2)
+            .await
+            .expect("Should search");
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].chunk.url, "https://ai.meta.com");
+
+        let results = store.search("rust tokio", 1).await.expect("Should search");
+        assert_eq!(results[0].chunk.url, "https://tokio.rs");
+    }
+
+    #[tokio::test]
+    async fn test_dot_product_search() {
+        let store = VectorStore::new(Arc::new(KeywordEmbedder), Similarity::DotProduct);
+        store
+            .add_chunks(vec![
+                chunk("rust", "https://a.com"),
+                chunk("rust rust rust tokio", "https://b.com"),
+            ])
+            .await
+            .expect("Should add chunks");
+
+        // unlike cosine, dot product favors the longer vector
+        let results = store.search("rust", 2).await.expect("Should search");
+        assert_eq!(results[0].chunk.url, "https://b.com");
+        assert_eq!(results[0].score, 3.0);
+    }
+}
@@ -1,14 +1,31 @@
-use crate::utils::crypto::to_address;
+pub mod settings;
+
+use crate::{
+    compute::{ollama::OllamaConfig, search::config::SearchConfig},
+    errors::{NodeError, NodeResult},
+    utils::{
+        crypto::to_address,
+        keystore::{self, Kdf, Keystore, WalletSecret},
+    },
+    waku::WakuMode,
+};
 use ecies::PublicKey;
-use libsecp256k1::{PublicKeyFormat, SecretKey};
-use std::env;
+use std::path::Path;
+
+use self::settings::{parse_public_key, Settings};
 
 /// 33 byte compressed public key of secret key from hex(b"dria) * 8
 pub const DEFAULT_DKN_ADMIN_PUBLIC_KEY: &[u8; 33] =
     &hex_literal::hex!("0208ef5e65a9c656a6f92fb2c770d5d5e2ecffe02a6aade19207f75110be6ae658");
 
+/// Default maximum number of tasks that run at once.
+pub const DEFAULT_DKN_MAX_CONCURRENT_TASKS: usize = 4;
+
+/// Default time in seconds to look back for missed tasks at startup.
+pub const DEFAULT_DKN_WAKU_STORE_LOOKBACK: u64 = 600;
+
 /// 32 byte secret key hex(b"node") * 8
-/// address:
+/// address: 0x1f56f6131705fbf19371122c80d7a2d40fcf9a68
 #[cfg(test)]
 pub const DEFAULT_DKN_WALLET_SECRET_KEY: &[u8; 32] =
     &hex_literal::hex!("6e6f64656e6f64656e6f64656e6f64656e6f64656e6f64656e6f64656e6f6465");
@@ -16,74 +33,126 @@ pub const DEFAULT_DKN_WALLET_SECRET_KEY: &[u8; 32] =
 #[allow(non_snake_case)]
 #[derive(Debug, Clone)]
 pub struct DriaComputeNodeConfig {
-    /// Wallet secret/private key.
-    pub(crate) DKN_WALLET_SECRET_KEY: SecretKey,
+    /// Wallet secret/private key, from the environment or from a keystore.
+    pub(crate) DKN_WALLET_SECRET_KEY: WalletSecret,
     /// Wallet public key, derived from the secret key.
     pub DKN_WALLET_PUBLIC_KEY: PublicKey,
     /// Wallet address, derived from the public key.
     pub DKN_WALLET_ADDRESS: [u8; 20],
     /// Admin public key, used for message authenticity.
     pub DKN_ADMIN_PUBLIC_KEY: PublicKey,
-}
-
-#[cfg(test)]
-fn prepare_secret() -> SecretKey {
-    SecretKey::parse(DEFAULT_DKN_WALLET_SECRET_KEY).expect("Should decrypt default secret key.")
-}
-
-#[cfg(not(test))]
-fn prepare_secret() -> SecretKey {
-    let secret_env =
-        env::var("DKN_WALLET_SECRET_KEY").expect("Secret key should be provided in .env.");
-    let secret_dec = hex::decode(secret_env).expect("Secret key should be 32-bytes hex encoded.");
-    SecretKey::parse_slice(&secret_dec).expect("Secret key should be parseable.")
+    /// Maximum number of tasks that run at once, across all workers.
+    pub DKN_MAX_CONCURRENT_TASKS: usize,
+    /// URL of the Waku REST API.
+    pub DKN_WAKU_URL: String,
+    /// Whether the node runs as a full node with relay, or as a light node.
+    pub DKN_WAKU_MODE: WakuMode,
+    /// Time in seconds to look back for tasks that were missed while offline, 0 to disable.
+    pub DKN_WAKU_STORE_LOOKBACK: u64,
 }
 
 impl DriaComputeNodeConfig {
-    pub fn new() -> Self {
-        let secret_key = prepare_secret();
-
-        let public_key = PublicKey::from_secret_key(&secret_key);
-
-        let admin_public_key = PublicKey::parse_slice(
-            hex::decode(env::var("DKN_ADMIN_PUBLIC_KEY").unwrap_or_default())
-                .unwrap_or_default()
-                .as_slice(),
-            Some(PublicKeyFormat::Compressed),
-        )
-        .unwrap_or(
-            PublicKey::parse_compressed(DEFAULT_DKN_ADMIN_PUBLIC_KEY)
-                .expect("Should decrypt default Admin public key."),
-        );
+    /// Creates the config from the defaults and the environment, see [`Settings::new_from_env`].
+    pub fn new_from_env() -> NodeResult<Self> {
+        Self::new_from_settings(&Settings::new_from_env())
+    }
 
+    /// Creates the config from the given settings, which should be validated already.
+    ///
+    /// If a keystore is given, its password is read from the password file, or prompted for.
+    pub fn new_from_settings(settings: &Settings) -> NodeResult<Self> {
+        let secret_key = match settings.get("DKN_KEYSTORE") {
+            Some(path) => {
+                let keystore = Keystore::load(Path::new(path))?;
+                let password = keystore::read_password(
+                    settings.get("DKN_KEYSTORE_PASSWORD_FILE").map(Path::new),
+                )?;
+                keystore.decrypt(password.as_bytes())?
+            }
+            None => WalletSecret::from_hex(&settings.parse::<String>("DKN_WALLET_SECRET_KEY")?)
+                .map_err(|e| {
+                    NodeError::Config(format!("DKN_WALLET_SECRET_KEY is invalid: {}", e))
+                })?,
+        };
+        let public_key = secret_key.public_key();
         let address = to_address(&public_key);
 
+        let admin_public_key = parse_public_key(&settings.parse::<String>("DKN_ADMIN_PUBLIC_KEY")?)
+            .map_err(|e| NodeError::Config(format!("DKN_ADMIN_PUBLIC_KEY is invalid: {}", e)))?;
+
+        let max_concurrent_tasks = settings.parse::<usize>("DKN_MAX_CONCURRENT_TASKS")?;
+        let waku_url = settings.parse::<String>("DKN_WAKU_URL")?;
+        let waku_mode = settings.parse::<WakuMode>("DKN_WAKU_MODE")?;
+        let store_lookback = settings.parse::<u64>("DKN_WAKU_STORE_LOOKBACK")?;
+
         log::info!("Address:    0x{}", hex::encode(address));
         log::info!(
             "Node Public Key: 0x{}",
             hex::encode(public_key.serialize_compressed())
         );
-        log::info!(
-            "Node Secret Key: 0x{}...",
-            hex::encode(&secret_key.serialize()[0..1]),
-        );
         log::info!(
             "Admin Public Key: 0x{}",
             hex::encode(admin_public_key.serialize_compressed())
         );
 
-        Self {
+        Ok(Self {
             DKN_ADMIN_PUBLIC_KEY: admin_public_key,
             DKN_WALLET_SECRET_KEY: secret_key,
             DKN_WALLET_PUBLIC_KEY: public_key,
             DKN_WALLET_ADDRESS: address,
-        }
+            DKN_MAX_CONCURRENT_TASKS: max_concurrent_tasks,
+            DKN_WAKU_URL: waku_url,
+            DKN_WAKU_MODE: waku_mode,
+            DKN_WAKU_STORE_LOOKBACK: store_lookback,
+        })
+    }
+}
+
+/// Configuration of the workers, built once from the settings by [`Settings::validate`].
+#[derive(Debug, Clone, Default)]
+pub struct WorkerConfig {
+    pub ollama: OllamaConfig,
+    pub search: SearchConfig,
+}
+
+/// Creates a keystore at the given path for a new secret key, or for `DKN_WALLET_SECRET_KEY` if
+/// `import` is set, with the password from the password file or prompted for twice.
+pub fn create_keystore(settings: &Settings, path: &Path, import: bool) -> NodeResult<Keystore> {
+    if path.exists() {
+        return Err(NodeError::Config(format!(
+            "Keystore {} already exists.",
+            path.display()
+        )));
     }
+
+    let secret_key = if import {
+        WalletSecret::from_hex(&settings.parse::<String>("DKN_WALLET_SECRET_KEY")?)
+            .map_err(|e| NodeError::Config(format!("DKN_WALLET_SECRET_KEY is invalid: {}", e)))?
+    } else {
+        WalletSecret::random()
+    };
+
+    let password =
+        keystore::read_new_password(settings.get("DKN_KEYSTORE_PASSWORD_FILE").map(Path::new))?;
+    let keystore = Keystore::encrypt(
+        &secret_key,
+        password.as_bytes(),
+        Kdf::scrypt(
+            keystore::DEFAULT_SCRYPT_LOG_N,
+            keystore::DEFAULT_SCRYPT_R,
+            keystore::DEFAULT_SCRYPT_P,
+        ),
+    )?;
+    keystore.save(path)?;
+
+    Ok(keystore)
 }
 
+/// The default settings with the test secret key, which never reads the environment.
+#[cfg(test)]
 impl Default for DriaComputeNodeConfig {
     fn default() -> Self {
-        Self::new()
+        Self::new_from_settings(&Settings::default()).expect("Should read default settings.")
     }
 }
 
@@ -93,14 +162,71 @@ mod tests {
 
     #[test]
     fn test_config() {
-        env::set_var(
-            "DKN_WALLET_SECRET_KEY",
-            "6e6f64656e6f64656e6f64656e6f64656e6f64656e6f64656e6f64656e6f6465",
+        let mut settings = Settings::default();
+        settings.merge_env([(
+            "DKN_WALLET_SECRET_KEY".to_string(),
+            "0x6e6f64656e6f64656e6f64656e6f64656e6f64656e6f64656e6f64656e6f6465".to_string(),
+        )]);
+        let cfg =
+            DriaComputeNodeConfig::new_from_settings(&settings).expect("Should create config");
+        assert_eq!(
+            hex::encode(cfg.DKN_WALLET_ADDRESS),
+            "1f56f6131705fbf19371122c80d7a2d40fcf9a68"
+        );
+        assert_eq!(
+            cfg.DKN_ADMIN_PUBLIC_KEY.serialize_compressed(),
+            *DEFAULT_DKN_ADMIN_PUBLIC_KEY
+        );
+        assert_eq!(
+            cfg.DKN_MAX_CONCURRENT_TASKS,
+            DEFAULT_DKN_MAX_CONCURRENT_TASKS
         );
-        let cfg = DriaComputeNodeConfig::new();
+        assert_eq!(cfg.DKN_WAKU_URL, crate::waku::DEFAULT_DKN_WAKU_URL);
+        assert_eq!(cfg.DKN_WAKU_MODE, WakuMode::Relay);
+    }
+
+    #[test]
+    fn test_keystore_config() {
+        let secret_key = WalletSecret::new(DEFAULT_DKN_WALLET_SECRET_KEY).expect("Should parse");
+        let keystore = Keystore::encrypt(&secret_key, b"password", Kdf::scrypt(10, 8, 1))
+            .expect("Should encrypt");
+
+        let dir = std::env::temp_dir();
+        let keystore_path = dir.join(format!("dkn-config-keystore-{}.json", keystore.id));
+        let password_path = dir.join(format!("dkn-config-password-{}", keystore.id));
+        keystore.save(&keystore_path).expect("Should save");
+        std::fs::write(&password_path, "password\n").expect("Should write");
+
+        let mut settings = Settings::default();
+        settings.merge_env([
+            (
+                "DKN_KEYSTORE".to_string(),
+                keystore_path.display().to_string(),
+            ),
+            (
+                "DKN_KEYSTORE_PASSWORD_FILE".to_string(),
+                password_path.display().to_string(),
+            ),
+        ]);
+        let result = DriaComputeNodeConfig::new_from_settings(&settings);
+        std::fs::remove_file(&keystore_path).expect("Should remove");
+        std::fs::remove_file(&password_path).expect("Should remove");
+
+        let cfg = result.expect("Should create config");
         assert_eq!(
             hex::encode(cfg.DKN_WALLET_ADDRESS),
             "1f56f6131705fbf19371122c80d7a2d40fcf9a68"
         );
     }
+
+    #[test]
+    fn test_invalid_config() {
+        let mut settings = Settings::default();
+        settings.merge_env([("DKN_ADMIN_PUBLIC_KEY".to_string(), "abcd".to_string())]);
+        let error = DriaComputeNodeConfig::new_from_settings(&settings).expect_err("Should fail");
+        assert!(matches!(error, NodeError::Config(_)));
+        assert!(error
+            .to_string()
+            .contains("DKN_ADMIN_PUBLIC_KEY is invalid"));
+    }
 }
@@ -0,0 +1,1151 @@
+use clap::{Arg, ArgAction, ArgMatches, Command};
+use ecies::PublicKey;
+use libsecp256k1::PublicKeyFormat;
+use std::{
+    collections::BTreeMap,
+    env, fmt, fs,
+    path::{Path, PathBuf},
+    str::FromStr,
+    time::Duration,
+};
+use url::Url;
+
+use crate::{
+    compute::{
+        constants::{DEFAULT_DKN_OLLAMA_HOST, DEFAULT_DKN_OLLAMA_MODEL, DEFAULT_DKN_OLLAMA_PORT},
+        ollama::OllamaConfig,
+        search::{
+            config::{PersonaPool, SearchConfig},
+            utils::{
+                cache::{
+                    CacheConfig, DEFAULT_DKN_SEARCH_CACHE_MAX_BYTES, DEFAULT_DKN_SEARCH_CACHE_TTL,
+                },
+                fetcher::{
+                    BrowserlessConfig, FetchLimits, FetcherConfig,
+                    DEFAULT_DKN_SEARCH_FETCH_MAX_BYTES, DEFAULT_DKN_SEARCH_FETCH_TIMEOUT,
+                },
+                policy::UrlPolicy,
+                provider::{JsonApiKind, ProviderConfig, DEFAULT_DKN_SEARCH_PROVIDERS},
+            },
+            vectorstore::DEFAULT_DKN_SEARCH_EMBEDDING_MODEL,
+        },
+    },
+    errors::{NodeError, NodeResult},
+    utils::keystore::WalletSecret,
+    waku::DEFAULT_DKN_WAKU_URL,
+};
+
+use super::{
+    WorkerConfig, DEFAULT_DKN_ADMIN_PUBLIC_KEY, DEFAULT_DKN_MAX_CONCURRENT_TASKS,
+    DEFAULT_DKN_WAKU_STORE_LOOKBACK,
+};
+
+/// Environment variable with the path of the config file, if it is not given on the command line.
+const DKN_CONFIG: &str = "DKN_CONFIG";
+
+/// Search providers, and the setting that each of them requires.
+const SEARCH_PROVIDERS: [(&str, Option<&str>); 4] = [
+    ("duckduckgo", None),
+    ("searxng", Some("DKN_SEARXNG_URL")),
+    ("brave", Some("BRAVE_API_KEY")),
+    ("bing", Some("BING_API_KEY")),
+];
+
+/// What a value must look like.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    /// Any non-empty text.
+    Text,
+    /// An `http` or `https` URL.
+    Url,
+    /// A port number.
+    Port,
+    /// A non-negative integer.
+    Integer,
+    /// A positive integer.
+    Positive,
+    /// `true` or `false`, where `1` and `0` are accepted as well.
+    Bool,
+    /// One of the given choices.
+    OneOf(&'static [&'static str]),
+    /// A comma-separated list of the given choices.
+    ListOf(&'static [&'static str]),
+    /// A comma-separated list of anything.
+    List,
+    /// Path of an existing file.
+    File,
+    /// Path of an existing directory.
+    Dir,
+    /// Path of a file or directory, which may not exist yet.
+    Path,
+    /// A 32-byte hex encoded secret key.
+    SecretKey,
+    /// A 33-byte hex encoded compressed public key.
+    PublicKey,
+}
+
+impl Kind {
+    /// Checks the value, and describes what was expected if it is invalid.
+    pub fn check(&self, value: &str) -> Result<(), String> {
+        let valid = match self {
+            Kind::Text | Kind::List | Kind::Path => true,
+            Kind::Url => Url::parse(value)
+                .map(|url| matches!(url.scheme(), "http" | "https"))
+                .unwrap_or(false),
+            Kind::Port => value.parse::<u16>().map(|port| port > 0).unwrap_or(false),
+            Kind::Integer => value.parse::<u64>().is_ok(),
+            Kind::Positive => value.parse::<u64>().map(|n| n > 0).unwrap_or(false),
+            Kind::Bool => matches!(value, "true" | "false" | "1" | "0"),
+            Kind::OneOf(choices) => choices.contains(&value.to_lowercase().as_str()),
+            Kind::ListOf(choices) => {
+                let items = split_list(value);
+                !items.is_empty() && items.iter().all(|item| choices.contains(&item.as_str()))
+            }
+            Kind::File => Path::new(value).is_file(),
+            Kind::Dir => Path::new(value).is_dir(),
+            Kind::SecretKey => WalletSecret::from_hex(value).is_ok(),
+            Kind::PublicKey => parse_public_key(value).is_ok(),
+        };
+
+        if valid {
+            Ok(())
+        } else {
+            Err(self.expected())
+        }
+    }
+
+    fn expected(&self) -> String {
+        match self {
+            Kind::Text | Kind::List | Kind::Path => "expected a value".to_string(),
+            Kind::Url => "expected an http or https URL".to_string(),
+            Kind::Port => "expected a port number between 1 and 65535".to_string(),
+            Kind::Integer => "expected a non-negative integer".to_string(),
+            Kind::Positive => "expected a positive integer".to_string(),
+            Kind::Bool => "expected true or false".to_string(),
+            Kind::OneOf(choices) => format!("expected one of {}", choices.join(", ")),
+            Kind::ListOf(choices) => {
+                format!("expected a comma-separated list of {}", choices.join(", "))
+            }
+            Kind::File => "expected the path of an existing file".to_string(),
+            Kind::Dir => "expected the path of an existing directory".to_string(),
+            Kind::SecretKey => "expected a 32-byte hex encoded secret key".to_string(),
+            Kind::PublicKey => "expected a 33-byte hex encoded compressed public key".to_string(),
+        }
+    }
+}
+
+/// A setting of the node, which is known by its environment variable.
+#[derive(Debug, Clone)]
+pub struct Setting {
+    /// Name of the environment variable.
+    pub key: &'static str,
+    /// Table of the setting within the config file, and prefix of its command line flag.
+    pub section: &'static str,
+    /// Name of the setting within its table.
+    pub name: &'static str,
+    pub kind: Kind,
+    pub default: Option<String>,
+    /// Whether the value is kept out of logs and errors. Secrets can not be given on the command
+    /// line, which is visible to other processes.
+    pub secret: bool,
+    pub help: &'static str,
+}
+
+impl Setting {
+    fn new(
+        key: &'static str,
+        section: &'static str,
+        name: &'static str,
+        kind: Kind,
+        help: &'static str,
+    ) -> Self {
+        Self {
+            key,
+            section,
+            name,
+            kind,
+            default: None,
+            secret: false,
+            help,
+        }
+    }
+
+    fn default(mut self, default: impl ToString) -> Self {
+        self.default = Some(default.to_string());
+        self
+    }
+
+    fn secret(mut self) -> Self {
+        self.secret = true;
+        self
+    }
+
+    /// Name of the command line flag, such as `waku-url`.
+    pub fn flag(&self) -> String {
+        format!("{}-{}", self.section, self.name.replace('_', "-"))
+    }
+}
+
+/// Returns every setting of the node, in the order that they are listed.
+pub fn all_settings() -> Vec<Setting> {
+    vec![
+        // dria
+        Setting::new(
+            "DKN_WALLET_SECRET_KEY",
+            "dria",
+            "wallet_secret_key",
+            Kind::SecretKey,
+            "Secret key of the node, instead of a keystore",
+        )
+        .secret(),
+        Setting::new(
+            "DKN_KEYSTORE",
+            "dria",
+            "keystore",
+            Kind::File,
+            "Encrypted JSON keystore of the secret key, instead of the secret key itself",
+        ),
+        Setting::new(
+            "DKN_KEYSTORE_PASSWORD_FILE",
+            "dria",
+            "keystore_password_file",
+            Kind::File,
+            "File that contains the keystore password, which is prompted for otherwise",
+        ),
+        Setting::new(
+            "DKN_ADMIN_PUBLIC_KEY",
+            "dria",
+            "admin_public_key",
+            Kind::PublicKey,
+            "Public key of the admin node, which signs the tasks",
+        )
+        .default(hex::encode(DEFAULT_DKN_ADMIN_PUBLIC_KEY)),
+        Setting::new(
+            "DKN_MAX_CONCURRENT_TASKS",
+            "dria",
+            "max_concurrent_tasks",
+            Kind::Positive,
+            "Maximum number of tasks that run at once",
+        )
+        .default(DEFAULT_DKN_MAX_CONCURRENT_TASKS),
+        // waku
+        Setting::new(
+            "DKN_WAKU_URL",
+            "waku",
+            "url",
+            Kind::Url,
+            "URL of the Waku REST API",
+        )
+        .default(DEFAULT_DKN_WAKU_URL),
+        Setting::new(
+            "DKN_WAKU_MODE",
+            "waku",
+            "mode",
+            Kind::OneOf(&["relay", "light"]),
+            "Whether to run as a full node with relay, or as a light node",
+        )
+        .default("relay"),
+        Setting::new(
+            "DKN_WAKU_STORE_LOOKBACK",
+            "waku",
+            "store_lookback",
+            Kind::Integer,
+            "Seconds of history to recover missed tasks from at startup, 0 to disable",
+        )
+        .default(DEFAULT_DKN_WAKU_STORE_LOOKBACK),
+        // ollama
+        Setting::new(
+            "DKN_OLLAMA_HOST",
+            "ollama",
+            "host",
+            Kind::Url,
+            "Host of the Ollama server",
+        )
+        .default(DEFAULT_DKN_OLLAMA_HOST),
+        Setting::new(
+            "DKN_OLLAMA_PORT",
+            "ollama",
+            "port",
+            Kind::Port,
+            "Port of the Ollama server",
+        )
+        .default(DEFAULT_DKN_OLLAMA_PORT),
+        Setting::new(
+            "DKN_OLLAMA_MODEL",
+            "ollama",
+            "model",
+            Kind::Text,
+            "Ollama model that runs the tasks",
+        )
+        .default(DEFAULT_DKN_OLLAMA_MODEL),
+        // search
+        Setting::new(
+            "DKN_SEARCH_PERSONAS",
+            "search",
+            "personas",
+            Kind::File,
+            "JSON file of the personas of search and synthesis, instead of the built-in personas",
+        ),
+        Setting::new(
+            "DKN_SEARCH_PROMPTS_DIR",
+            "search",
+            "prompts_dir",
+            Kind::Dir,
+            "Directory of prompts that override the built-in prompts",
+        ),
+        Setting::new(
+            "DKN_SEARCH_EMBEDDING_MODEL",
+            "search",
+            "embedding_model",
+            Kind::Text,
+            "Ollama model used to index scraped pages",
+        )
+        .default(DEFAULT_DKN_SEARCH_EMBEDDING_MODEL),
+        Setting::new(
+            "DKN_BROWSERLESS_URL",
+            "search",
+            "browserless_url",
+            Kind::Url,
+            "Browserless URL to fetch pages with",
+        ),
+        Setting::new(
+            "BROWSERLESS_TOKEN",
+            "search",
+            "browserless_token",
+            Kind::Text,
+            "Browserless API token",
+        )
+        .secret(),
+        Setting::new(
+            "DKN_SEARCH_FETCH_TIMEOUT",
+            "search",
+            "fetch_timeout",
+            Kind::Positive,
+            "Timeout of fetching a page, in seconds",
+        )
+        .default(DEFAULT_DKN_SEARCH_FETCH_TIMEOUT),
+        Setting::new(
+            "DKN_SEARCH_FETCH_MAX_BYTES",
+            "search",
+            "fetch_max_bytes",
+            Kind::Positive,
+            "Maximum size of a fetched page, in bytes",
+        )
+        .default(DEFAULT_DKN_SEARCH_FETCH_MAX_BYTES),
+        Setting::new(
+            "DKN_SEARCH_PROVIDERS",
+            "search",
+            "providers",
+            Kind::ListOf(&["duckduckgo", "searxng", "brave", "bing"]),
+            "Web search providers in order of failover",
+        )
+        .default(DEFAULT_DKN_SEARCH_PROVIDERS),
+        Setting::new(
+            "DKN_SEARXNG_URL",
+            "search",
+            "searxng_url",
+            Kind::Url,
+            "SearxNG instance, required by the searxng provider",
+        ),
+        Setting::new(
+            "BRAVE_API_KEY",
+            "search",
+            "brave_api_key",
+            Kind::Text,
+            "Brave Search API key, required by the brave provider",
+        )
+        .secret(),
+        Setting::new(
+            "BING_API_KEY",
+            "search",
+            "bing_api_key",
+            Kind::Text,
+            "Bing Web Search API key, required by the bing provider",
+        )
+        .secret(),
+        Setting::new(
+            "DKN_SEARCH_ALLOWED_DOMAINS",
+            "search",
+            "allowed_domains",
+            Kind::List,
+            "Domains that tools may fetch from, any public domain if not given",
+        ),
+        Setting::new(
+            "DKN_SEARCH_BLOCKED_DOMAINS",
+            "search",
+            "blocked_domains",
+            Kind::List,
+            "Domains that tools may not fetch from",
+        ),
+        Setting::new(
+            "DKN_SEARCH_ALLOW_PRIVATE_NETWORKS",
+            "search",
+            "allow_private_networks",
+            Kind::Bool,
+            "Allow tools to fetch from loopback, private and link-local addresses",
+        )
+        .default(false),
+        Setting::new(
+            "DKN_SEARCH_CACHE_TTL",
+            "search",
+            "cache_ttl",
+            Kind::Integer,
+            "Maximum time that fetched pages and search results are cached for, in seconds",
+        )
+        .default(DEFAULT_DKN_SEARCH_CACHE_TTL),
+        Setting::new(
+            "DKN_SEARCH_CACHE_MAX_BYTES",
+            "search",
+            "cache_max_bytes",
+            Kind::Integer,
+            "Maximum size of the in-memory cache, in bytes",
+        )
+        .default(DEFAULT_DKN_SEARCH_CACHE_MAX_BYTES),
+        Setting::new(
+            "DKN_SEARCH_CACHE_DIR",
+            "search",
+            "cache_dir",
+            Kind::Path,
+            "Directory to persist the cache to, in memory only if not given",
+        ),
+    ]
+}
+
+/// Where the value of a setting comes from, in order of precedence.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Source {
+    Default,
+    File,
+    Env,
+    Cli,
+}
+
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let source = match self {
+            Source::Default => "default",
+            Source::File => "file",
+            Source::Env => "env",
+            Source::Cli => "cli",
+        };
+        write!(f, "{}", source)
+    }
+}
+
+/// # Settings
+///
+/// The effective configuration of the node, layered from the defaults, a TOML config file, the
+/// environment and the command line, where each layer overrides the ones before it.
+///
+/// Every setting is known by its environment variable, and empty values are ignored in every
+/// layer, as if they were not given.
+#[derive(Clone)]
+pub struct Settings {
+    values: BTreeMap<&'static str, (String, Source)>,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        let mut values = BTreeMap::new();
+        for setting in all_settings() {
+            if let Some(default) = setting.default {
+                values.insert(setting.key, (default, Source::Default));
+            }
+        }
+
+        #[cfg(test)]
+        values.insert(
+            "DKN_WALLET_SECRET_KEY",
+            (
+                hex::encode(super::DEFAULT_DKN_WALLET_SECRET_KEY),
+                Source::Default,
+            ),
+        );
+
+        Self { values }
+    }
+}
+
+impl fmt::Debug for Settings {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.summary())
+    }
+}
+
+impl Settings {
+    /// Reads the defaults and the environment, without a config file or the command line.
+    pub fn new_from_env() -> Self {
+        let mut settings = Self::default();
+        settings.merge_env(env::vars());
+        settings
+    }
+
+    /// Reads every layer, where the config file is given by `--config` or `DKN_CONFIG`, and
+    /// validates the result into the config of the workers.
+    ///
+    /// Exits the process if the command line is invalid, or if help is requested.
+    pub fn load() -> NodeResult<(Self, WorkerConfig)> {
+        Self::load_from(&command().get_matches(), env::vars())
+    }
+
+    /// Reads every layer with the given command line and environment, and validates the result
+    /// into the config of the workers.
+    pub fn load_from(
+        matches: &ArgMatches,
+        vars: impl IntoIterator<Item = (String, String)>,
+    ) -> NodeResult<(Self, WorkerConfig)> {
+        let settings = Self::read_from(matches, vars)?;
+        let config = settings.validate()?;
+        Ok((settings, config))
+    }
+
+    /// Reads every layer with the given command line and environment, without validation.
+    pub fn read_from(
+        matches: &ArgMatches,
+        vars: impl IntoIterator<Item = (String, String)>,
+    ) -> NodeResult<Self> {
+        let vars: BTreeMap<String, String> = vars.into_iter().collect();
+        let mut settings = Self::default();
+
+        let path = matches
+            .get_one::<String>("config")
+            .or(vars.get(DKN_CONFIG))
+            .filter(|path| !path.is_empty());
+        if let Some(path) = path {
+            settings.merge_file(Path::new(path))?;
+        }
+        settings.merge_env(vars);
+        settings.merge_args(matches);
+
+        Ok(settings)
+    }
+
+    /// Returns the value of a setting, by its environment variable.
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.values.get(key).map(|(value, _)| value.as_str())
+    }
+
+    /// Returns where the value of a setting comes from.
+    pub fn source(&self, key: &str) -> Option<Source> {
+        self.values.get(key).map(|(_, source)| *source)
+    }
+
+    /// Returns the items of a comma-separated list setting in lowercase, which is empty if the
+    /// setting is not given.
+    fn list(&self, key: &str) -> Vec<String> {
+        self.get(key).map(split_list).unwrap_or_default()
+    }
+
+    /// Returns whether a boolean setting is `true` or `1`.
+    fn is_true(&self, key: &str) -> bool {
+        matches!(self.get(key), Some("true" | "1"))
+    }
+
+    /// Parses the value of a setting, which must be given.
+    pub fn parse<T: FromStr>(&self, key: &str) -> NodeResult<T> {
+        let value = self
+            .get(key)
+            .ok_or_else(|| NodeError::Config(format!("{} is required.", key)))?;
+        value
+            .parse()
+            .map_err(|_| NodeError::Config(format!("{} has an invalid value.", key)))
+    }
+
+    fn set(&mut self, key: &'static str, value: &str, source: Source) {
+        let value = value.trim();
+        if !value.is_empty() {
+            self.values.insert(key, (value.to_string(), source));
+        }
+    }
+
+    /// Reads a TOML config file, see [`Settings::merge_toml`].
+    pub fn merge_file(&mut self, path: &Path) -> NodeResult<()> {
+        let contents = fs::read_to_string(path).map_err(|e| {
+            NodeError::Config(format!(
+                "Could not read config file {}: {}",
+                path.display(),
+                e
+            ))
+        })?;
+        self.merge_toml(&contents)
+            .map_err(|e| NodeError::Config(format!("{}: {}", path.display(), e)))
+    }
+
+    /// Reads a TOML config, where each setting is within the table of its section, such as `url`
+    /// within `[waku]`. Lists can be given as arrays, and unknown settings are errors.
+    pub fn merge_toml(&mut self, contents: &str) -> NodeResult<()> {
+        let table: toml::Table = toml::from_str(contents)
+            .map_err(|e| NodeError::Config(format!("Invalid config file: {}", e)))?;
+
+        let settings = all_settings();
+        for (section, values) in table {
+            let values = values
+                .as_table()
+                .ok_or_else(|| NodeError::Config(format!("Expected a table for [{}].", section)))?;
+
+            for (name, value) in values {
+                let setting = settings
+                    .iter()
+                    .find(|setting| setting.section == section && setting.name == *name)
+                    .ok_or_else(|| {
+                        NodeError::Config(format!("Unknown setting {}.{}.", section, name))
+                    })?;
+                let value = toml_to_string(value).ok_or_else(|| {
+                    NodeError::Config(format!(
+                        "Unsupported value for {}.{}, expected a string, number, boolean or array.",
+                        section, name
+                    ))
+                })?;
+                self.set(setting.key, &value, Source::File);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Reads the settings within the given environment variables, ignoring other variables.
+    pub fn merge_env(&mut self, vars: impl IntoIterator<Item = (String, String)>) {
+        let vars: BTreeMap<String, String> = vars.into_iter().collect();
+        for setting in all_settings() {
+            if let Some(value) = vars.get(setting.key) {
+                self.set(setting.key, value, Source::Env);
+            }
+        }
+    }
+
+    /// Reads the settings given on the command line, see [`command`].
+    pub fn merge_args(&mut self, matches: &ArgMatches) {
+        for setting in all_settings().into_iter().filter(|s| !s.secret) {
+            if let Some(value) = matches.get_one::<String>(setting.key) {
+                self.set(setting.key, value, Source::Cli);
+            }
+        }
+    }
+
+    /// Checks every setting along with the persona file, and returns the config of the workers.
+    ///
+    /// If anything is invalid, returns a single error that lists all invalid settings.
+    pub fn validate(&self) -> NodeResult<WorkerConfig> {
+        let mut errors = Vec::new();
+        for setting in all_settings() {
+            let location = format!("{} ({}.{})", setting.key, setting.section, setting.name);
+            match self.values.get(setting.key) {
+                None => {}
+                Some((value, source)) => {
+                    if let Err(expected) = setting.kind.check(value) {
+                        if setting.secret {
+                            errors.push(format!("{} from {}: {}", location, source, expected));
+                        } else {
+                            errors.push(format!(
+                                "{} from {}: {}, got {:?}",
+                                location, source, expected, value
+                            ));
+                        }
+                    }
+                }
+            }
+        }
+
+        match (
+            self.get("DKN_WALLET_SECRET_KEY"),
+            self.get("DKN_KEYSTORE"),
+        ) {
+            (None, None) => errors.push(
+                "DKN_WALLET_SECRET_KEY (dria.wallet_secret_key) or DKN_KEYSTORE (dria.keystore) is required"
+                    .to_string(),
+            ),
+            (Some(_), Some(_)) => errors.push(
+                "DKN_WALLET_SECRET_KEY (dria.wallet_secret_key) and DKN_KEYSTORE (dria.keystore) can not be given together"
+                    .to_string(),
+            ),
+            _ => {}
+        }
+
+        // redirects within Browserless can not be checked, see `BrowserlessFetcher`
+        let browserless =
+            self.get("DKN_BROWSERLESS_URL").is_some() || self.get("BROWSERLESS_TOKEN").is_some();
+        if browserless
+            && self.get("DKN_SEARCH_ALLOWED_DOMAINS").is_none()
+            && !self.is_true("DKN_SEARCH_ALLOW_PRIVATE_NETWORKS")
+        {
+            errors.push(
+                "DKN_SEARCH_ALLOWED_DOMAINS (search.allowed_domains) is required by Browserless"
+                    .to_string(),
+            );
+        }
+
+        let providers = self.list("DKN_SEARCH_PROVIDERS");
+        for (provider, required) in SEARCH_PROVIDERS {
+            if let Some(required) = required {
+                if providers.iter().any(|p| p == provider) && self.get(required).is_none() {
+                    errors.push(format!(
+                        "{} is required by the {} search provider",
+                        required, provider
+                    ));
+                }
+            }
+        }
+
+        // a missing file is reported above
+        let personas = match self.get("DKN_SEARCH_PERSONAS") {
+            Some(path) if Path::new(path).is_file() => match PersonaPool::new_from_file(path) {
+                Ok(personas) => Some(personas),
+                Err(e) => {
+                    let reason = match e {
+                        NodeError::Config(message) => message,
+                        e => e.to_string(),
+                    };
+                    errors.push(format!("DKN_SEARCH_PERSONAS (search.personas): {}", reason));
+                    None
+                }
+            },
+            Some(_) => None,
+            None => Some(PersonaPool::default()),
+        };
+
+        match personas {
+            Some(personas) if errors.is_empty() => self.worker_config(personas),
+            _ => Err(NodeError::Config(format!(
+                "{} invalid settings:\n  {}",
+                errors.len(),
+                errors.join("\n  ")
+            ))),
+        }
+    }
+
+    /// Builds the config of the workers from settings that are checked already.
+    fn worker_config(&self, personas: PersonaPool) -> NodeResult<WorkerConfig> {
+        let providers = self
+            .list("DKN_SEARCH_PROVIDERS")
+            .into_iter()
+            .map(|name| match name.as_str() {
+                "duckduckgo" => Ok(ProviderConfig::DuckDuckGo),
+                "searxng" => Ok(ProviderConfig::Searxng {
+                    url: self.parse("DKN_SEARXNG_URL")?,
+                }),
+                "brave" => Ok(ProviderConfig::JsonApi {
+                    kind: JsonApiKind::Brave,
+                    api_key: self.parse("BRAVE_API_KEY")?,
+                }),
+                "bing" => Ok(ProviderConfig::JsonApi {
+                    kind: JsonApiKind::Bing,
+                    api_key: self.parse("BING_API_KEY")?,
+                }),
+                _ => Err(NodeError::Config(format!(
+                    "Unknown search provider {}.",
+                    name
+                ))),
+            })
+            .collect::<NodeResult<Vec<_>>>()?;
+
+        let browserless = match (
+            self.get("DKN_BROWSERLESS_URL"),
+            self.get("BROWSERLESS_TOKEN"),
+        ) {
+            (None, None) => None,
+            (url, token) => Some(BrowserlessConfig {
+                url: url.map(str::to_string),
+                token: token.map(str::to_string),
+            }),
+        };
+
+        Ok(WorkerConfig {
+            ollama: OllamaConfig {
+                host: self.parse("DKN_OLLAMA_HOST")?,
+                port: self.parse("DKN_OLLAMA_PORT")?,
+                model: self.parse("DKN_OLLAMA_MODEL")?,
+            },
+            search: SearchConfig {
+                personas,
+                prompts_dir: self.get("DKN_SEARCH_PROMPTS_DIR").map(PathBuf::from),
+                embedding_model: self.parse("DKN_SEARCH_EMBEDDING_MODEL")?,
+                providers,
+                fetcher: FetcherConfig {
+                    limits: FetchLimits {
+                        timeout: Duration::from_secs(self.parse("DKN_SEARCH_FETCH_TIMEOUT")?),
+                        max_bytes: self.parse("DKN_SEARCH_FETCH_MAX_BYTES")?,
+                    },
+                    browserless,
+                },
+                policy: UrlPolicy::new(
+                    &self.list("DKN_SEARCH_ALLOWED_DOMAINS"),
+                    &self.list("DKN_SEARCH_BLOCKED_DOMAINS"),
+                    self.is_true("DKN_SEARCH_ALLOW_PRIVATE_NETWORKS"),
+                ),
+                cache: CacheConfig {
+                    ttl: Duration::from_secs(self.parse("DKN_SEARCH_CACHE_TTL")?),
+                    max_bytes: self.parse("DKN_SEARCH_CACHE_MAX_BYTES")?,
+                    dir: self.get("DKN_SEARCH_CACHE_DIR").map(PathBuf::from),
+                },
+            },
+        })
+    }
+
+    /// Returns every setting along with its value and source, where secrets are redacted.
+    pub fn summary(&self) -> String {
+        let mut summary = String::from("Configuration:");
+        for setting in all_settings() {
+            let value = match self.values.get(setting.key) {
+                None => "<not set>".to_string(),
+                Some((_, source)) if setting.secret => format!("<redacted> ({})", source),
+                Some((value, source)) => format!("{} ({})", value, source),
+            };
+            summary.push_str(&format!("\n  {} = {}", setting.key, value));
+        }
+        summary
+    }
+}
+
+/// The command line of the node, with a flag for each setting that is not a secret, such as
+/// `--waku-url` for `url` within `[waku]`, and the `keystore` subcommand.
+pub fn command() -> Command {
+    let mut command = Command::new("dkn-compute")
+        .version(env!("CARGO_PKG_VERSION"))
+        .about("Dria Compute Node")
+        .arg(
+            Arg::new("config")
+                .long("config")
+                .value_name("PATH")
+                .help(format!("TOML config file, also read from {}", DKN_CONFIG)),
+        )
+        .subcommand(
+            Command::new("keystore")
+                .about("Creates an encrypted keystore of a new secret key, and prints its address")
+                .arg(
+                    Arg::new("path")
+                        .value_name("PATH")
+                        .required(true)
+                        .help("Keystore file to create, which must not exist"),
+                )
+                .arg(
+                    Arg::new("import")
+                        .long("import")
+                        .action(ArgAction::SetTrue)
+                        .help("Encrypts DKN_WALLET_SECRET_KEY instead of a new secret key"),
+                ),
+        );
+
+    for setting in all_settings().into_iter().filter(|s| !s.secret) {
+        command = command.arg(
+            Arg::new(setting.key)
+                .long(setting.flag())
+                .value_name("VALUE")
+                .help(format!("{} [env: {}]", setting.help, setting.key)),
+        );
+    }
+
+    command
+}
+
+/// Parses a hex encoded compressed public key, with an optional `0x` prefix.
+pub(crate) fn parse_public_key(value: &str) -> Result<PublicKey, String> {
+    let bytes = hex::decode(value.trim_start_matches("0x")).map_err(|e| e.to_string())?;
+    PublicKey::parse_slice(&bytes, Some(PublicKeyFormat::Compressed)).map_err(|e| e.to_string())
+}
+
+/// Splits a comma-separated list into its lowercase items, dropping empty ones.
+fn split_list(value: &str) -> Vec<String> {
+    value
+        .split(',')
+        .map(|item| item.trim().to_lowercase())
+        .filter(|item| !item.is_empty())
+        .collect()
+}
+
+/// Converts a TOML value to the string that the environment variable would have.
+fn toml_to_string(value: &toml::Value) -> Option<String> {
+    match value {
+        toml::Value::String(value) => Some(value.clone()),
+        toml::Value::Integer(value) => Some(value.to_string()),
+        toml::Value::Boolean(value) => Some(value.to_string()),
+        toml::Value::Array(values) => values
+            .iter()
+            .map(|value| value.as_str().map(str::to_string))
+            .collect::<Option<Vec<_>>>()
+            .map(|values| values.join(",")),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECRET_KEY: &str = "6e6f64656e6f64656e6f64656e6f64656e6f64656e6f64656e6f64656e6f6465";
+
+    fn vars(vars: &[(&str, &str)]) -> Vec<(String, String)> {
+        vars.iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect()
+    }
+
+    /// The default settings, without the test secret key.
+    fn without_secret_key() -> Settings {
+        let mut settings = Settings::default();
+        settings.values.remove("DKN_WALLET_SECRET_KEY");
+        settings
+    }
+
+    #[test]
+    fn test_layers() {
+        let mut settings = Settings::default();
+        settings
+            .merge_toml(
+                r#"
+                [waku]
+                url = "http://waku:8645"
+                mode = "light"
+
+                [ollama]
+                model = "llama3"
+
+                [search]
+                providers = ["duckduckgo", "searxng"]
+                searxng_url = "http://searxng:8080"
+                "#,
+            )
+            .expect("Should read config");
+        settings.merge_env(vars(&[
+            ("DKN_OLLAMA_MODEL", "phi3"),
+            ("DKN_WAKU_MODE", ""),
+            ("UNRELATED", "value"),
+        ]));
+        let matches = command()
+            .try_get_matches_from(["dkn-compute", "--ollama-model", "gemma"])
+            .expect("Should parse");
+        settings.merge_args(&matches);
+
+        let cases = [
+            ("DKN_OLLAMA_MODEL", "gemma", Source::Cli),
+            ("DKN_WAKU_URL", "http://waku:8645", Source::File),
+            // empty values are ignored
+            ("DKN_WAKU_MODE", "light", Source::File),
+            ("DKN_SEARCH_PROVIDERS", "duckduckgo,searxng", Source::File),
+            ("DKN_OLLAMA_PORT", "11434", Source::Default),
+        ];
+        for (key, value, source) in cases {
+            assert_eq!(settings.get(key), Some(value), "{}", key);
+            assert_eq!(settings.source(key), Some(source), "{}", key);
+        }
+        assert!(settings.get("UNRELATED").is_none());
+        assert_eq!(
+            settings.list("DKN_SEARCH_PROVIDERS"),
+            vec!["duckduckgo", "searxng"]
+        );
+        assert!(settings.list("DKN_SEARCH_BLOCKED_DOMAINS").is_empty());
+        assert!(!settings.is_true("DKN_SEARCH_ALLOW_PRIVATE_NETWORKS"));
+        settings.validate().expect("Should be valid");
+    }
+
+    #[test]
+    fn test_load_from() {
+        let dir = std::env::temp_dir().join("dkn-settings-test");
+        fs::create_dir_all(&dir).expect("Should create dir");
+        let path = dir.join("config.toml");
+        fs::write(&path, "[dria]\nmax_concurrent_tasks = 8\n").expect("Should write config");
+
+        let matches = command()
+            .try_get_matches_from(["dkn-compute", "--waku-store-lookback", "0"])
+            .expect("Should parse");
+        let (settings, _) = Settings::load_from(
+            &matches,
+            vars(&[
+                ("DKN_CONFIG", path.to_str().expect("Should be UTF-8")),
+                ("DKN_WALLET_SECRET_KEY", SECRET_KEY),
+            ]),
+        )
+        .expect("Should load");
+
+        assert_eq!(
+            settings.parse::<usize>("DKN_MAX_CONCURRENT_TASKS").ok(),
+            Some(8)
+        );
+        assert_eq!(
+            settings.parse::<u64>("DKN_WAKU_STORE_LOOKBACK").ok(),
+            Some(0)
+        );
+        assert_eq!(settings.source("DKN_WALLET_SECRET_KEY"), Some(Source::Env));
+    }
+
+    #[test]
+    fn test_invalid_file() {
+        let cases = [
+            ("[waku]\nurll = \"http://waku:8645\"", "waku.urll"),
+            ("url = \"http://waku:8645\"", "[url]"),
+            ("[ollama]\nport = 1.5", "ollama.port"),
+            ("[ollama", "Invalid config file"),
+        ];
+        for (contents, expected) in cases {
+            let error = Settings::default()
+                .merge_toml(contents)
+                .expect_err(expected);
+            assert!(matches!(error, NodeError::Config(_)));
+            assert!(error.to_string().contains(expected), "{}", error);
+        }
+    }
+
+    #[test]
+    fn test_validation() {
+        let secret = "not-a-secret-key";
+        let mut settings = Settings::default();
+        settings.merge_env(vars(&[
+            ("DKN_WALLET_SECRET_KEY", secret),
+            ("DKN_OLLAMA_PORT", "70000"),
+            ("DKN_WAKU_MODE", "full"),
+            ("DKN_WAKU_URL", "127.0.0.1:8645"),
+            ("DKN_MAX_CONCURRENT_TASKS", "0"),
+            ("DKN_SEARCH_PROVIDERS", "duckduckgo,brave,google"),
+            ("DKN_SEARCH_ALLOW_PRIVATE_NETWORKS", "yes"),
+        ]));
+
+        let error = settings
+            .validate()
+            .expect_err("Should be invalid")
+            .to_string();
+        for expected in [
+            "8 invalid settings",
+            "DKN_WALLET_SECRET_KEY (dria.wallet_secret_key) from env",
+            "DKN_OLLAMA_PORT (ollama.port) from env: expected a port number",
+            "DKN_WAKU_MODE (waku.mode) from env: expected one of relay, light",
+            "DKN_WAKU_URL (waku.url)",
+            "DKN_MAX_CONCURRENT_TASKS (dria.max_concurrent_tasks)",
+            "DKN_SEARCH_PROVIDERS (search.providers)",
+            "DKN_SEARCH_ALLOW_PRIVATE_NETWORKS (search.allow_private_networks)",
+            "BRAVE_API_KEY is required by the brave search provider",
+        ] {
+            assert!(error.contains(expected), "{} in {}", expected, error);
+        }
+        // secrets are not echoed back
+        assert!(!error.contains(secret), "{}", error);
+
+        let mut settings = Settings::default();
+        settings.merge_env(vars(&[
+            ("DKN_SEARCH_PROVIDERS", "searxng,bing"),
+            ("DKN_BROWSERLESS_URL", "http://127.0.0.1:3000"),
+        ]));
+        let error = settings
+            .validate()
+            .expect_err("Should be invalid")
+            .to_string();
+        assert!(error.contains("DKN_SEARXNG_URL is required by the searxng"));
+        assert!(error.contains("BING_API_KEY is required by the bing"));
+        assert!(error.contains(
+            "DKN_SEARCH_ALLOWED_DOMAINS (search.allowed_domains) is required by Browserless"
+        ));
+    }
+
+    #[test]
+    fn test_worker_config() {
+        let mut settings = Settings::default();
+        settings.merge_env(vars(&[
+            ("DKN_OLLAMA_MODEL", "phi3"),
+            ("DKN_SEARCH_PROVIDERS", "searxng, brave"),
+            ("DKN_SEARXNG_URL", "http://searxng:8080"),
+            ("BRAVE_API_KEY", "brave-secret"),
+            ("DKN_BROWSERLESS_URL", "http://127.0.0.1:3000"),
+            ("DKN_SEARCH_ALLOWED_DOMAINS", "Wikipedia.org., arxiv.org"),
+            ("DKN_SEARCH_CACHE_TTL", "60"),
+        ]));
+
+        let config = settings.validate().expect("Should be valid");
+        assert_eq!(config.ollama.model, "phi3");
+        assert_eq!(config.ollama.port, DEFAULT_DKN_OLLAMA_PORT);
+
+        let search = config.search;
+        assert_eq!(
+            search.providers,
+            vec![
+                ProviderConfig::Searxng {
+                    url: "http://searxng:8080".to_string()
+                },
+                ProviderConfig::JsonApi {
+                    kind: JsonApiKind::Brave,
+                    api_key: "brave-secret".to_string()
+                },
+            ]
+        );
+        assert_eq!(
+            search.fetcher.browserless,
+            Some(BrowserlessConfig {
+                url: Some("http://127.0.0.1:3000".to_string()),
+                token: None,
+            })
+        );
+        assert_eq!(search.fetcher.limits, FetchLimits::default());
+        assert_eq!(
+            search.policy.allowed_domains,
+            ["wikipedia.org", "arxiv.org"]
+        );
+        assert_eq!(search.cache.ttl, Duration::from_secs(60));
+        assert_eq!(search.embedding_model, DEFAULT_DKN_SEARCH_EMBEDDING_MODEL);
+        // secrets are not echoed back
+        assert!(!format!("{:?}", search.providers).contains("brave-secret"));
+
+        // an invalid persona file fails validation, instead of falling back to the built-in one
+        let path =
+            std::env::temp_dir().join(format!("dkn-settings-personas-{}.json", std::process::id()));
+        fs::write(&path, r#"{"personas": []}"#).expect("Should write personas");
+        settings.merge_env(vars(&[(
+            "DKN_SEARCH_PERSONAS",
+            path.to_str().expect("Should be UTF-8"),
+        )]));
+        let result = settings.validate();
+        fs::remove_file(&path).expect("Should remove personas");
+        let error = result.expect_err("Should be invalid").to_string();
+        assert!(
+            error.contains("DKN_SEARCH_PERSONAS (search.personas): Persona pool can not be empty"),
+            "{}",
+            error
+        );
+    }
+
+    #[test]
+    fn test_required() {
+        let settings = without_secret_key();
+        let error = settings
+            .validate()
+            .expect_err("Should be invalid")
+            .to_string();
+        assert!(error.contains(
+            "DKN_WALLET_SECRET_KEY (dria.wallet_secret_key) or DKN_KEYSTORE (dria.keystore) is required"
+        ));
+
+        // a keystore instead of the secret key
+        let mut settings = without_secret_key();
+        settings.merge_env(vars(&[("DKN_KEYSTORE", "Cargo.toml")]));
+        settings.validate().expect("Should be valid");
+
+        // but not both
+        settings.merge_env(vars(&[("DKN_WALLET_SECRET_KEY", SECRET_KEY)]));
+        let error = settings
+            .validate()
+            .expect_err("Should be invalid")
+            .to_string();
+        assert!(error.contains("can not be given together"), "{}", error);
+    }
+
+    #[test]
+    fn test_summary() {
+        let mut settings = Settings::default();
+        settings.merge_env(vars(&[
+            ("DKN_WALLET_SECRET_KEY", SECRET_KEY),
+            ("BRAVE_API_KEY", "brave-secret"),
+        ]));
+
+        let summary = settings.summary();
+        assert!(!summary.contains(SECRET_KEY));
+        assert!(!summary.contains("brave-secret"));
+        assert!(summary.contains("DKN_WALLET_SECRET_KEY = <redacted> (env)"));
+        assert!(summary.contains("DKN_OLLAMA_PORT = 11434 (default)"));
+        assert!(summary.contains("DKN_SEARCH_PERSONAS = <not set>"));
+        assert_eq!(format!("{:?}", settings), summary);
+    }
+
+    #[test]
+    fn test_secrets_not_on_command_line() {
+        let result =
+            command().try_get_matches_from(["dkn-compute", "--dria-wallet-secret-key", SECRET_KEY]);
+        assert!(result.is_err());
+        command().debug_assert();
+    }
+}
@@ -1,91 +1,183 @@
-use serde::Deserialize;
+use reqwest::StatusCode;
+use std::error::Error;
 
 pub type NodeResult<T> = std::result::Result<T, NodeError>;
 
+/// A boxed error that is the source of a [`NodeError`].
+pub type BoxedError = Box<dyn Error + Send + Sync>;
+
 /// # Node Error
 ///
-/// A generic error within the Compute Node. This may originate from serde, reqwest and such. The source is
-/// included along the error message, and `From` traits are implemented for expected errors.
-#[derive(Deserialize)]
-pub struct NodeError {
-    #[serde(rename = "error")]
-    pub message: String,
-    pub source: String,
+/// An error within the Compute Node, categorized by what failed so that callers can match on it.
+/// The underlying error, if any, is kept as the [`Error::source`] of the node error, and `From`
+/// traits are implemented for expected errors.
+#[derive(Debug)]
+pub enum NodeError {
+    /// A request could not be sent, or its response could not be read.
+    Transport(reqwest::Error),
+    /// The Waku node responded with an error status, or an error within its response.
+    WakuStatus { status: StatusCode, message: String },
+    /// A key, signature or ciphertext is invalid.
+    Crypto(BoxedError),
+    /// A payload could not be encoded or decoded.
+    Payload(BoxedError),
+    /// The LLM backend, or the embedder, failed.
+    Llm(BoxedError),
+    /// A tool used by an agent failed.
+    Tool {
+        message: String,
+        source: Option<BoxedError>,
+    },
+    /// The configuration is invalid.
+    Config(String),
+    /// The operation was cancelled, e.g. as the node is shutting down.
+    Cancelled,
+    /// Any other error.
+    Other(String),
 }
 
-impl std::fmt::Display for NodeError {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{:?}", self) // use same as Debug
+impl NodeError {
+    /// Creates a tool error with the given message.
+    pub fn tool(message: impl Into<String>) -> Self {
+        Self::Tool {
+            message: message.into(),
+            source: None,
+        }
     }
-}
 
-impl std::fmt::Debug for NodeError {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{} error: {}", self.source, self.message)
+    /// Returns whether the operation may succeed if it is tried again, such as after a timeout, a
+    /// refused connection, or a server that is not ready yet.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            NodeError::Transport(e) => match e.status() {
+                Some(status) => is_retryable_status(status),
+                None => e.is_timeout() || e.is_connect(),
+            },
+            NodeError::WakuStatus { status, .. } => is_retryable_status(*status),
+            // the backend may still be starting, or pulling a model
+            NodeError::Llm(_) => true,
+            _ => false,
+        }
     }
 }
 
-impl std::error::Error for NodeError {}
+/// Server errors and rate limits are temporary, client errors are not.
+fn is_retryable_status(status: StatusCode) -> bool {
+    status.is_server_error() || status == StatusCode::TOO_MANY_REQUESTS
+}
 
-impl From<String> for NodeError {
-    fn from(message: String) -> Self {
-        Self {
-            message,
-            source: "self".to_string(),
+impl std::fmt::Display for NodeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            NodeError::Transport(e) => write!(f, "transport error: {}", e),
+            NodeError::WakuStatus { status, message } => {
+                write!(f, "waku responded with {}: {}", status, message)
+            }
+            NodeError::Crypto(e) => write!(f, "crypto error: {}", e),
+            NodeError::Payload(e) => write!(f, "payload error: {}", e),
+            NodeError::Llm(e) => write!(f, "llm error: {}", e),
+            NodeError::Tool { message, .. } => write!(f, "tool error: {}", message),
+            NodeError::Config(message) => write!(f, "config error: {}", message),
+            NodeError::Cancelled => write!(f, "cancelled"),
+            NodeError::Other(message) => write!(f, "{}", message),
         }
     }
 }
 
-impl From<&str> for NodeError {
-    fn from(message: &str) -> Self {
-        Self {
-            message: message.to_string(),
-            source: "self".to_string(),
+impl Error for NodeError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            NodeError::Transport(e) => Some(e),
+            NodeError::Crypto(e) | NodeError::Payload(e) | NodeError::Llm(e) => Some(e.as_ref()),
+            NodeError::Tool {
+                source: Some(e), ..
+            } => Some(e.as_ref()),
+            _ => None,
         }
     }
 }
 
 impl From<reqwest::Error> for NodeError {
     fn from(value: reqwest::Error) -> Self {
-        Self {
-            message: value.to_string(),
-            source: "reqwest".to_string(),
-        }
+        Self::Transport(value)
     }
 }
 
 impl From<serde_json::Error> for NodeError {
     fn from(value: serde_json::Error) -> Self {
-        Self {
-            message: value.to_string(),
-            source: "serde_json".to_string(),
-        }
+        Self::Payload(Box::new(value))
     }
 }
 
 impl From<base64::DecodeError> for NodeError {
     fn from(value: base64::DecodeError) -> Self {
-        Self {
-            message: value.to_string(),
-            source: "base64".to_string(),
-        }
+        Self::Payload(Box::new(value))
     }
 }
 
 impl From<hex::FromHexError> for NodeError {
     fn from(value: hex::FromHexError) -> Self {
-        Self {
-            message: value.to_string(),
-            source: "hex".to_string(),
-        }
+        Self::Payload(Box::new(value))
     }
 }
 
 impl From<ecies::SecpError> for NodeError {
     fn from(value: ecies::SecpError) -> Self {
-        Self {
-            message: value.to_string(),
-            source: "secp256k1".to_string(),
-        }
+        Self::Crypto(Box::new(value))
+    }
+}
+
+impl From<ollama_rs::error::OllamaError> for NodeError {
+    fn from(value: ollama_rs::error::OllamaError) -> Self {
+        Self::Llm(Box::new(value))
+    }
+}
+
+impl From<langchain_rust::embedding::EmbedderError> for NodeError {
+    fn from(value: langchain_rust::embedding::EmbedderError) -> Self {
+        Self::Llm(Box::new(value))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_retryable() {
+        let status = |code: u16| NodeError::WakuStatus {
+            status: StatusCode::from_u16(code).expect("Should be a status"),
+            message: String::new(),
+        };
+        assert!(status(503).is_retryable());
+        assert!(status(429).is_retryable());
+        assert!(!status(400).is_retryable());
+        assert!(!status(404).is_retryable());
+
+        assert!(!NodeError::Cancelled.is_retryable());
+        assert!(!NodeError::Config("invalid".to_string()).is_retryable());
+        assert!(!NodeError::from(hex::FromHexError::OddLength).is_retryable());
+    }
+
+    #[tokio::test]
+    async fn test_transport_retryable() {
+        // nothing listens on the discard port
+        let error = reqwest::get("http://127.0.0.1:9")
+            .await
+            .expect_err("Should not connect");
+        let error = NodeError::from(error);
+        assert!(matches!(error, NodeError::Transport(_)));
+        assert!(error.is_retryable());
+    }
+
+    #[test]
+    fn test_source_chain() {
+        let error = NodeError::from(hex::FromHexError::OddLength);
+        assert!(matches!(error, NodeError::Payload(_)));
+        let source = error.source().expect("Should have a source");
+        assert!(source.downcast_ref::<hex::FromHexError>().is_some());
+
+        assert!(NodeError::Config("invalid".to_string()).source().is_none());
+        assert_eq!(NodeError::Other("failed".to_string()).to_string(), "failed");
     }
 }
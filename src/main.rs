@@ -1,23 +1,22 @@
 use dkn_compute::utils::wait_for_termination;
-use dkn_compute::{config::DriaComputeNodeConfig, node::DriaComputeNode};
+use dkn_compute::{
+    compute::ollama::OllamaClient,
+    config::{
+        create_keystore,
+        settings::{command, Settings},
+        DriaComputeNodeConfig,
+    },
+    node::DriaComputeNode,
+};
 use tokio_util::sync::CancellationToken;
 use tokio_util::task::TaskTracker;
 
 // diagnostic & heartbeat always enabled
 use dkn_compute::workers::diagnostic::*;
 use dkn_compute::workers::heartbeat::*;
-use std::sync::Arc;
-
-#[cfg(feature = "synthesis")]
-use dkn_compute::workers::synthesis::*;
-
-#[cfg(feature = "search")]
-use dkn_compute::workers::search::*;
-
-#[cfg(feature = "search")]
-use dkn_compute::compute::search::tools::{StockScraper, Scraper, DDGSearcher};
-
+use std::{env, path::Path, sync::Arc};
 
+use dkn_compute::workers::registry::WorkerRegistry;
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
@@ -28,42 +27,58 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     const VERSION: &str = env!("CARGO_PKG_VERSION");
     log::info!("Using Dria Compute Node v{}", VERSION);
 
-    let config = DriaComputeNodeConfig::new();
+    let matches = command().get_matches();
+    if let Some(("keystore", args)) = matches.subcommand() {
+        let path = args.get_one::<String>("path").expect("Should be required.");
+        let keystore = Settings::read_from(&matches, env::vars()).and_then(|settings| {
+            create_keystore(&settings, Path::new(path), args.get_flag("import"))
+        });
+        match keystore {
+            Ok(keystore) => {
+                let address = keystore.address.unwrap_or_default();
+                log::info!("Created keystore {} for address 0x{}", path, address);
+                return Ok(());
+            }
+            Err(e) => {
+                log::error!("{}", e);
+                std::process::exit(1);
+            }
+        }
+    }
+
+    // defaults, then the config file, then the environment, then the command line
+    let (settings, worker_config) = match Settings::load_from(&matches, env::vars()) {
+        Ok(loaded) => loaded,
+        Err(e) => {
+            log::error!("{}", e);
+            std::process::exit(1);
+        }
+    };
+    log::info!("{}", settings.summary());
+
+    let config = DriaComputeNodeConfig::new_from_settings(&settings)?;
     let cancellation = CancellationToken::new();
     let node = Arc::new(DriaComputeNode::new(config, cancellation.clone()));
 
     log::info!("Starting workers");
     let tracker = TaskTracker::new();
-    tracker.spawn(heartbeat_worker(
-        node.clone(),
-        "heartbeat",
-        tokio::time::Duration::from_millis(1000),
-    ));
+    let dispatcher_node = node.clone();
+    tracker.spawn(async move {
+        dispatcher_node
+            .messages
+            .run(&dispatcher_node.cancellation)
+            .await
+    });
+    let ollama = OllamaClient::new_from_config(&worker_config.ollama);
+    tracker.spawn(heartbeat_worker(node.clone(), "heartbeat", ollama));
     tracker.spawn(diagnostic_worker(
         node.clone(),
         tokio::time::Duration::from_secs(60),
     ));
 
-    #[cfg(feature = "synthesis")]
-    tracker.spawn(synthesis_worker(
-        node.clone(),
-        "synthesis",
-        tokio::time::Duration::from_millis(1000),
-    ));
-
-    #[cfg(feature = "search")]
-    {
-        let scraper_tool = Scraper {};
-        let stock_data = StockScraper::new();
-        let ddg_searcher = DDGSearcher::new();
-        tracker.spawn(search_worker(
-            node.clone(),
-            "search",
-            (Arc::new(scraper_tool), Arc::new(stock_data), Arc::new(ddg_searcher)),
-            tokio::time::Duration::from_millis(1000),
-        ));
-    }
-
+    // task workers, one for each task type enabled by the features
+    let registry = WorkerRegistry::new_from_features(&worker_config)?;
+    registry.spawn(&node, &tracker);
 
     tracker.close(); // close tracker after spawning everything
 
@@ -1,42 +1,76 @@
 use ecies::encrypt;
 use fastbloom_rs::{BloomFilter, Membership};
-use libsecp256k1::{sign, Message, RecoveryId, Signature};
+use libsecp256k1::{Message, RecoveryId, Signature};
+use std::{sync::Arc, time::Duration};
+use tokio::sync::mpsc;
 use tokio_util::sync::CancellationToken;
-use parking_lot::RwLock;
-use std::sync::Arc;
 
 use crate::{
     compute::payload::TaskResponsePayload,
     config::DriaComputeNodeConfig,
     errors::NodeResult,
-    utils::{crypto::sha256hash, filter::FilterPayload},
-    waku::{message::WakuMessage, WakuClient},
+    utils::{
+        capabilities::{enabled_features, Capabilities, Hardware, TaskTypes},
+        crypto::sha256hash,
+        executor::TaskExecutor,
+        filter::FilterPayload,
+        status::{HeartbeatStats, NodeStatus},
+    },
+    waku::{
+        message::WakuMessage,
+        store::StoreQuery,
+        stream::{MessageDispatcher, DEFAULT_TOPIC_CAPACITY},
+        WakuClient,
+    },
 };
 
+/// Maximum number of pages of history that are queried for missed messages.
+const MAX_STORE_PAGES: usize = 10;
+
 #[allow(unused)]
 #[derive(Debug)]
 pub struct DriaComputeNode {
     pub config: DriaComputeNodeConfig,
     pub waku: WakuClient,
+    /// Receives the messages of all subscribed topics, and sends them to their workers.
+    pub messages: MessageDispatcher,
     pub cancellation: CancellationToken,
-    pub busy_lock: RwLock<bool>,
+    /// Running tasks of each task type, within `DKN_MAX_CONCURRENT_TASKS` slots.
+    pub status: Arc<NodeStatus>,
+    /// Runs the tasks of all workers within the slots of `status`.
+    pub executor: TaskExecutor,
+    /// Task types run by the workers, advertised in heartbeat responses.
+    pub task_types: TaskTypes,
+    /// Heartbeat requests that were not answered.
+    pub heartbeats: HeartbeatStats,
 }
 
+#[cfg(test)]
 impl Default for DriaComputeNode {
     fn default() -> Self {
-        DriaComputeNode::new(DriaComputeNodeConfig::new(), CancellationToken::default())
+        DriaComputeNode::new(
+            DriaComputeNodeConfig::default(),
+            CancellationToken::default(),
+        )
     }
 }
 
 impl DriaComputeNode {
     pub fn new(config: DriaComputeNodeConfig, cancellation: CancellationToken) -> Self {
-        let waku = WakuClient::new(None);
-        let busy_lock = RwLock::new(false);
+        let waku =
+            WakuClient::new(Some(config.DKN_WAKU_URL.clone())).with_mode(config.DKN_WAKU_MODE);
+        let messages = MessageDispatcher::new(waku.transport());
+        let status = Arc::new(NodeStatus::new(config.DKN_MAX_CONCURRENT_TASKS));
+        let executor = TaskExecutor::new(status.clone());
         DriaComputeNode {
             config,
             waku,
+            messages,
             cancellation,
-            busy_lock
+            status,
+            executor,
+            task_types: TaskTypes::default(),
+            heartbeats: HeartbeatStats::default(),
         }
     }
 
@@ -49,19 +83,7 @@ impl DriaComputeNode {
     /// Shorthand to sign a digest with node's secret key and return signature & recovery id.
     #[inline]
     pub fn sign(&self, message: &Message) -> (Signature, RecoveryId) {
-        sign(message, &self.config.DKN_WALLET_SECRET_KEY)
-    }
-
-    /// Returns the state of the node, whether it is busy or not.
-    #[inline]
-    pub fn is_busy(&self) -> bool {
-        *self.busy_lock.read()
-    }
-
-    /// Set the state of the node, whether it is busy or not.
-    #[inline]
-    pub fn set_busy(&self, busy: bool) {
-        *self.busy_lock.write() = busy;
+        self.config.DKN_WALLET_SECRET_KEY.sign(message)
     }
 
     /// Shorthand to sign a digest (bytes) with node's secret key and return signature & recovery id
@@ -69,7 +91,7 @@ impl DriaComputeNode {
     #[inline]
     pub fn sign_bytes(&self, message: &[u8; 32]) -> String {
         let message = Message::parse(message);
-        let (signature, recid) = sign(&message, &self.config.DKN_WALLET_SECRET_KEY);
+        let (signature, recid) = self.sign(&message);
 
         format!(
             "{}{}",
@@ -78,6 +100,17 @@ impl DriaComputeNode {
         )
     }
 
+    /// Returns the capabilities of the node, with the given Ollama models.
+    pub fn capabilities(&self, models: Vec<String>) -> Capabilities {
+        Capabilities {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            features: enabled_features(),
+            models,
+            tasks: self.task_types.to_map(),
+            hardware: Hardware::current(),
+        }
+    }
+
     /// Given a hex-string serialized Bloom Filter of a task, checks if this node is selected to do the task.
     ///
     /// This is done by checking if the address of this node is in the filter.
@@ -101,7 +134,7 @@ impl DriaComputeNode {
         // sign result
         let result_digest: [u8; 32] = sha256hash(result.as_ref());
         let result_msg = Message::parse(&result_digest);
-        let (signature, recid) = sign(&result_msg, &self.config.DKN_WALLET_SECRET_KEY);
+        let (signature, recid) = self.sign(&result_msg);
         let signature: [u8; 64] = signature.serialize();
         let recid: [u8; 1] = [recid.serialize()];
 
@@ -122,20 +155,37 @@ impl DriaComputeNode {
         })
     }
 
-    /// Subscribe to a certain task with its topic.
-    pub async fn subscribe_topic(&self, topic: &str) -> () {
+    /// Subscribe to a certain task with its topic, returning the channel that its messages are
+    /// received from.
+    ///
+    /// Retryable errors are retried every 5 seconds, up to 30 times. Otherwise, the node is cancelled.
+    ///
+    /// Returns `None` if the node is cancelled before the subscription succeeds.
+    pub async fn subscribe_topic(&self, topic: &str) -> Option<mpsc::Receiver<WakuMessage>> {
         let content_topic = WakuMessage::create_content_topic(topic);
 
         let mut retry_count = 0; // retry count for edge case
-        while let Err(e) = self.waku.relay.subscribe(&content_topic).await {
-            if retry_count < 30 {
+        loop {
+            let e = match self
+                .messages
+                .subscribe(&content_topic, DEFAULT_TOPIC_CAPACITY)
+                .await
+            {
+                Ok(receiver) => {
+                    log::info!("Subscribed to {}", topic);
+                    return Some(receiver);
+                }
+                Err(e) => e,
+            };
+
+            if e.is_retryable() && retry_count < 30 {
                 log::error!(
                     "Error subscribing to {}: {}\nRetrying in 5 seconds.",
                     topic,
                     e
                 );
                 tokio::select! {
-                    _ = self.cancellation.cancelled() => return,
+                    _ = self.cancellation.cancelled() => return None,
                     _ = tokio::time::sleep(tokio::time::Duration::from_secs(5)) => {
                         retry_count += 1; // Increment the retry counter
                     }
@@ -143,47 +193,61 @@ impl DriaComputeNode {
             } else {
                 log::error!("Error subscribing to {}: {}\nAborting.", topic, e);
                 self.cancellation.cancel();
+                return None;
             }
         }
-
-        log::info!("Subscribed to {}", topic);
     }
 
     /// Unsubscribe from a certain task with its topic.
     pub async fn unsubscribe_topic(&self, topic: &str) -> NodeResult<()> {
         let content_topic = WakuMessage::create_content_topic(topic);
-        self.waku.relay.unsubscribe(&content_topic).await?;
+        self.messages.unsubscribe(&content_topic).await?;
         log::info!("Unsubscribed from {}", topic);
         Ok(())
     }
 
-    /// Send a message via Waku Relay, assuming the content is subscribed to already.
+    /// Send a message via Waku, assuming the content is subscribed to already.
     pub async fn send_message(&self, message: WakuMessage) -> NodeResult<()> {
-        self.waku.relay.send_message(message).await
+        self.waku.send_message(message).await
     }
 
-    /// Send a message via Waku Relay on a topic, where
-    /// the topic is subscribed, the message is sent, and
-    /// the topic is unsubscribed right afterwards.
+    /// Send a message via Waku on a topic that is not subscribed to, such as the topic of a
+    /// response.
     pub async fn send_message_once(&self, message: WakuMessage) -> NodeResult<()> {
-        let content_topic = message.content_topic.clone();
-        self.waku.relay.subscribe(&content_topic).await?;
-        self.waku.relay.send_message(message).await?;
-        self.waku.relay.unsubscribe(&content_topic).await?;
-        Ok(())
+        self.waku.send_message_once(message).await
     }
 
-    /// Process messages on a certain topic, and if they are expected to be signed by the admin
-    /// key of Dria, only keeps the ones that are authentic.
-    pub async fn process_topic(&self, topic: &str, signed: bool) -> NodeResult<Vec<WakuMessage>> {
-        let content_topic = WakuMessage::create_content_topic(topic);
-        let mut messages: Vec<WakuMessage> = self.waku.relay.get_messages(&content_topic).await?;
-
-        // dont bother if there are no messages
-        if messages.is_empty() {
-            return Ok(messages);
+    /// Returns the messages on a certain topic that were published within
+    /// `DKN_WAKU_STORE_LOOKBACK` seconds before `until` (in nanoseconds), such as the tasks that
+    /// were missed while the node was offline.
+    pub async fn recover_messages(&self, topic: &str, until: u128) -> NodeResult<Vec<WakuMessage>> {
+        if self.config.DKN_WAKU_STORE_LOOKBACK == 0 {
+            return Ok(Vec::new());
         }
 
+        let lookback = Duration::from_secs(self.config.DKN_WAKU_STORE_LOOKBACK).as_nanos();
+        let query = StoreQuery::new(
+            vec![WakuMessage::create_content_topic(topic)],
+            until.saturating_sub(lookback),
+            until,
+        );
+        let messages = self
+            .waku
+            .store
+            .get_all_messages(query, MAX_STORE_PAGES)
+            .await?;
+
+        Ok(self.process_messages(topic, messages, false))
+    }
+
+    /// Process messages received on a certain topic, and if they are expected to be signed by the
+    /// admin key of Dria, only keeps the ones that are authentic.
+    pub fn process_messages(
+        &self,
+        topic: &str,
+        mut messages: Vec<WakuMessage>,
+        signed: bool,
+    ) -> Vec<WakuMessage> {
         log::debug!("Received {} messages on topic {}:", messages.len(), topic);
         for message in &messages {
             log::debug!("{}", message);
@@ -201,7 +265,7 @@ impl DriaComputeNode {
             });
         }
 
-        Ok(messages)
+        messages
     }
 }
 
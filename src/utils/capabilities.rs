@@ -0,0 +1,73 @@
+use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// # Capabilities
+///
+/// What the node can do, advertised to the admin node within heartbeat replies so that tasks are only assigned to nodes that can run them.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Capabilities {
+    /// Version of the compute node.
+    pub version: String,
+    /// Crate features that the node is built with.
+    pub features: Vec<String>,
+    /// Models that are pulled in Ollama.
+    pub models: Vec<String>,
+    /// Task types that the node runs, along with the names of the tools that each can use.
+    pub tasks: BTreeMap<String, Vec<String>>,
+    pub hardware: Hardware,
+}
+
+/// The machine that the node runs on.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Hardware {
+    pub os: String,
+    pub arch: String,
+    /// Number of threads that can run in parallel, 0 if unknown.
+    pub cpus: usize,
+}
+
+impl Hardware {
+    /// Returns the hardware of the current machine.
+    pub fn current() -> Self {
+        Self {
+            os: std::env::consts::OS.to_string(),
+            arch: std::env::consts::ARCH.to_string(),
+            cpus: std::thread::available_parallelism()
+                .map(|cpus| cpus.get())
+                .unwrap_or(0),
+        }
+    }
+}
+
+/// Returns the task features that the crate is built with.
+pub fn enabled_features() -> Vec<String> {
+    [
+        ("search", cfg!(feature = "search")),
+        ("synthesis", cfg!(feature = "synthesis")),
+    ]
+    .into_iter()
+    .filter(|(_, enabled)| *enabled)
+    .map(|(feature, _)| feature.to_string())
+    .collect()
+}
+
+/// The task types registered at the node, along with their tool names.
+#[derive(Debug, Default)]
+pub struct TaskTypes(RwLock<BTreeMap<&'static str, Vec<String>>>);
+
+impl TaskTypes {
+    /// Records a task type and its tools, replacing the tools if it was already recorded.
+    pub fn insert(&self, task_type: &'static str, tools: Vec<String>) {
+        self.0.write().insert(task_type, tools);
+    }
+
+    /// Returns the task types, with their tool names.
+    pub fn to_map(&self) -> BTreeMap<String, Vec<String>> {
+        self.0
+            .read()
+            .iter()
+            .map(|(task_type, tools)| (task_type.to_string(), tools.clone()))
+            .collect()
+    }
+}
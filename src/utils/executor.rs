@@ -0,0 +1,229 @@
+use std::{future::Future, sync::Arc, time::Duration};
+use tokio::task::JoinSet;
+use tokio_util::sync::CancellationToken;
+
+use super::{
+    get_current_time_nanos,
+    status::{NodeStatus, TaskGuard},
+};
+
+/// # Task Executor
+///
+/// Runs tasks concurrently, each within a slot of the node status, so that at most its capacity
+/// of tasks run at once across every worker. Each task runs until its deadline, and is cancelled if
+/// the deadline passes while it is running.
+#[derive(Debug, Clone)]
+pub struct TaskExecutor {
+    status: Arc<NodeStatus>,
+}
+
+impl TaskExecutor {
+    pub fn new(status: Arc<NodeStatus>) -> Self {
+        Self { status }
+    }
+
+    /// Returns the status whose slots the tasks run in.
+    #[inline]
+    pub fn status(&self) -> &Arc<NodeStatus> {
+        &self.status
+    }
+
+    /// Spawns a task within the set, in a slot of the status that was already taken.
+    ///
+    /// The task is given a child of `cancellation`, which is cancelled when the deadline (in
+    /// nanoseconds since the Unix epoch) passes, and the task itself is dropped at that point.
+    /// The set yields `Some` with the output of the task if it finished in time, `None` otherwise.
+    pub fn spawn_in_slot<F, Fut>(
+        &self,
+        set: &mut JoinSet<Option<Fut::Output>>,
+        slot: TaskGuard,
+        task_id: String,
+        deadline: u128,
+        cancellation: &CancellationToken,
+        task: F,
+    ) where
+        F: FnOnce(CancellationToken) -> Fut + Send + 'static,
+        Fut: Future + Send + 'static,
+        Fut::Output: Send + 'static,
+    {
+        let cancellation = cancellation.child_token();
+        set.spawn(async move {
+            // the slot is released when the task finishes, is cancelled or panics
+            let _guard = slot;
+            let output =
+                run_until_deadline(deadline, cancellation.clone(), task(cancellation)).await;
+
+            if output.is_none() {
+                log::warn!("Task {} did not finish before its deadline.", task_id);
+            }
+            output
+        });
+    }
+}
+
+/// Runs the future until the deadline (in nanoseconds since the Unix epoch) passes or the token is
+/// cancelled, whichever comes first, cancelling the token in either case.
+///
+/// Returns `None` if the future did not finish in time, and never polls it if the deadline has
+/// already passed.
+pub async fn run_until_deadline<F: Future>(
+    deadline: u128,
+    cancellation: CancellationToken,
+    future: F,
+) -> Option<F::Output> {
+    // a zero sleep is not ready on its first poll, so an expired deadline is checked up front
+    if time_until(deadline).is_zero() {
+        cancellation.cancel();
+        return None;
+    }
+
+    let output = tokio::select! {
+        biased;
+        _ = cancellation.cancelled() => None,
+        _ = tokio::time::sleep(time_until(deadline)) => None,
+        output = future => Some(output),
+    };
+
+    cancellation.cancel();
+    output
+}
+
+/// Returns the time left until the deadline, which is zero if it has passed.
+#[inline]
+pub fn time_until(deadline: u128) -> Duration {
+    let remaining = deadline.saturating_sub(get_current_time_nanos());
+    Duration::from_nanos(u64::try_from(remaining).unwrap_or(u64::MAX))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// Returns a deadline the given number of milliseconds from now.
+    fn deadline_in(millis: u64) -> u128 {
+        get_current_time_nanos() + Duration::from_millis(millis).as_nanos()
+    }
+
+    #[tokio::test]
+    async fn test_concurrency_limit() {
+        let executor = TaskExecutor::new(Arc::new(NodeStatus::new(2)));
+        let running = Arc::new(AtomicUsize::new(0));
+        let peak = Arc::new(AtomicUsize::new(0));
+        let cancellation = CancellationToken::new();
+
+        let mut set = JoinSet::new();
+        for i in 0..6 {
+            let (running, peak) = (running.clone(), peak.clone());
+            let slot = executor.status().acquire("test").await;
+            executor.spawn_in_slot(
+                &mut set,
+                slot,
+                i.to_string(),
+                deadline_in(5_000),
+                &cancellation,
+                move |_| async move {
+                    let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+                    peak.fetch_max(now, Ordering::SeqCst);
+                    tokio::time::sleep(Duration::from_millis(20)).await;
+                    running.fetch_sub(1, Ordering::SeqCst);
+                    i
+                },
+            );
+        }
+
+        let mut finished = 0;
+        while let Some(output) = set.join_next().await {
+            assert!(output.expect("Should not panic").is_some());
+            finished += 1;
+        }
+        assert_eq!(finished, 6);
+        assert_eq!(peak.load(Ordering::SeqCst), 2);
+        assert_eq!(executor.status().remaining(), 2);
+        assert_eq!(executor.status().in_flight_of("test"), 0);
+    }
+
+    #[tokio::test]
+    async fn test_deadline_cancels_task() {
+        let executor = TaskExecutor::new(Arc::new(NodeStatus::new(1)));
+        let cancellation = CancellationToken::new();
+        let (sender, receiver) = tokio::sync::oneshot::channel();
+
+        let mut set = JoinSet::new();
+        let slot = executor.status().acquire("test").await;
+        executor.spawn_in_slot(
+            &mut set,
+            slot,
+            "slow".to_string(),
+            deadline_in(50),
+            &cancellation,
+            |token| async move {
+                tokio::time::sleep(Duration::from_secs(10)).await;
+                let _ = sender.send(token);
+            },
+        );
+        // the slot is released once the deadline passes, and an expired task never starts
+        let slot = executor.status().acquire("test").await;
+        executor.spawn_in_slot(
+            &mut set,
+            slot,
+            "expired".to_string(),
+            deadline_in(0),
+            &cancellation,
+            |_| async { unreachable!("Should not start") },
+        );
+
+        let started = std::time::Instant::now();
+        while let Some(output) = set.join_next().await {
+            assert!(output.expect("Should not panic").is_none());
+        }
+        assert!(started.elapsed() < Duration::from_secs(1));
+        assert!(receiver.await.is_err(), "Slow task should be dropped");
+        assert_eq!(executor.status().remaining(), 1);
+        assert!(
+            !cancellation.is_cancelled(),
+            "Parent should not be cancelled"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_spawn_in_slot() {
+        let executor = TaskExecutor::new(Arc::new(NodeStatus::new(1)));
+        let slot = executor
+            .status()
+            .try_acquire("test")
+            .expect("Should acquire");
+        let cancellation = CancellationToken::new();
+
+        // the task runs in the given slot, instead of waiting for another
+        let mut set = JoinSet::new();
+        executor.spawn_in_slot(
+            &mut set,
+            slot,
+            "slotted".to_string(),
+            deadline_in(5_000),
+            &cancellation,
+            |_| async { 1 },
+        );
+        let output = set.join_next().await.expect("Should have task");
+        assert_eq!(output.expect("Should not panic"), Some(1));
+        assert_eq!(executor.status().remaining(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_run_until_cancelled() {
+        let cancellation = CancellationToken::new();
+        cancellation.cancel();
+
+        let output = run_until_deadline(deadline_in(5_000), cancellation, async { 1 }).await;
+        assert_eq!(output, None);
+
+        let token = CancellationToken::new();
+        let output = run_until_deadline(deadline_in(5_000), token.clone(), async { 1 }).await;
+        assert_eq!(output, Some(1));
+        assert!(
+            token.is_cancelled(),
+            "Token is cancelled once the task is done"
+        );
+    }
+}
@@ -0,0 +1,499 @@
+use aes::Aes128;
+use ctr::{
+    cipher::{KeyIvInit, StreamCipher},
+    Ctr128BE,
+};
+use libsecp256k1::{sign, Message, PublicKey, RecoveryId, SecretKey, Signature};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+#[cfg(unix)]
+use std::os::unix::fs::OpenOptionsExt;
+use std::{fmt, fs, io::Write, path::Path};
+use zeroize::Zeroizing;
+
+use crate::{
+    errors::{NodeError, NodeResult},
+    utils::crypto::{keccak256hash, to_address},
+};
+
+type Aes128Ctr = Ctr128BE<Aes128>;
+
+/// Scrypt cost of new keystores as a power of 2, same as the standard scrypt parameters of geth.
+pub const DEFAULT_SCRYPT_LOG_N: u8 = 18;
+pub const DEFAULT_SCRYPT_R: u32 = 8;
+pub const DEFAULT_SCRYPT_P: u32 = 1;
+
+/// Length of the derived key, where the first half is the AES key and the second half is for the MAC.
+const DKLEN: usize = 32;
+
+/// # Wallet Secret
+///
+/// The secret key of the node, which is zeroed in memory once dropped. The key is parsed only for
+/// the duration of signing, and is never printed.
+#[derive(Clone)]
+pub struct WalletSecret(Zeroizing<[u8; 32]>);
+
+impl fmt::Debug for WalletSecret {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "WalletSecret(<redacted>)")
+    }
+}
+
+impl WalletSecret {
+    /// Creates a secret from 32 bytes, which must be a valid secp256k1 secret key.
+    pub fn new(bytes: &[u8]) -> NodeResult<Self> {
+        let mut secret = Zeroizing::new([0u8; 32]);
+        if bytes.len() != secret.len() {
+            return Err(NodeError::Crypto(
+                format!("Secret key must be 32 bytes, got {}.", bytes.len()).into(),
+            ));
+        }
+        secret.copy_from_slice(bytes);
+        SecretKey::parse(&secret)?;
+
+        Ok(Self(secret))
+    }
+
+    /// Parses a hex encoded secret, with an optional `0x` prefix.
+    pub fn from_hex(value: &str) -> NodeResult<Self> {
+        let bytes = Zeroizing::new(hex::decode(value.trim_start_matches("0x"))?);
+        Self::new(&bytes)
+    }
+
+    /// Creates a random secret.
+    pub fn random() -> Self {
+        loop {
+            let bytes = Zeroizing::new(rand::random::<[u8; 32]>());
+            if let Ok(secret) = Self::new(bytes.as_ref()) {
+                return secret;
+            }
+        }
+    }
+
+    fn secret_key(&self) -> SecretKey {
+        SecretKey::parse(&self.0).expect("Should be checked on creation.")
+    }
+
+    pub fn public_key(&self) -> PublicKey {
+        PublicKey::from_secret_key(&self.secret_key())
+    }
+
+    /// Returns the address of the public key, see [`to_address`].
+    pub fn address(&self) -> [u8; 20] {
+        to_address(&self.public_key())
+    }
+
+    /// Signs a digest, returning the signature and recovery id.
+    pub fn sign(&self, message: &Message) -> (Signature, RecoveryId) {
+        sign(message, &self.secret_key())
+    }
+}
+
+/// # Keystore
+///
+/// An [Ethereum V3 keystore](https://ethereum.org/en/developers/docs/data-structures-and-encoding/web3-secret-storage/),
+/// where the secret key is encrypted with AES-128-CTR under a key derived from a password with
+/// scrypt or PBKDF2.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Keystore {
+    pub version: u8,
+    pub id: String,
+    /// Address of the key as hex, without the `0x` prefix.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub address: Option<String>,
+    #[serde(alias = "Crypto")]
+    pub crypto: KeystoreCrypto,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct KeystoreCrypto {
+    pub cipher: String,
+    pub cipherparams: CipherParams,
+    pub ciphertext: String,
+    #[serde(flatten)]
+    pub kdf: Kdf,
+    pub mac: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct CipherParams {
+    pub iv: String,
+}
+
+/// The key derivation function, along with its parameters.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "kdf", content = "kdfparams", rename_all = "lowercase")]
+pub enum Kdf {
+    Scrypt {
+        dklen: usize,
+        n: u64,
+        r: u32,
+        p: u32,
+        salt: String,
+    },
+    Pbkdf2 {
+        dklen: usize,
+        c: u32,
+        prf: String,
+        salt: String,
+    },
+}
+
+impl Kdf {
+    /// Scrypt with a cost of `2^log_n` and a random salt.
+    pub fn scrypt(log_n: u8, r: u32, p: u32) -> Self {
+        Kdf::Scrypt {
+            dklen: DKLEN,
+            n: 1 << log_n,
+            r,
+            p,
+            salt: hex::encode(rand::random::<[u8; 32]>()),
+        }
+    }
+
+    /// PBKDF2 with HMAC-SHA256 for `c` rounds and a random salt.
+    pub fn pbkdf2(c: u32) -> Self {
+        Kdf::Pbkdf2 {
+            dklen: DKLEN,
+            c,
+            prf: "hmac-sha256".to_string(),
+            salt: hex::encode(rand::random::<[u8; 32]>()),
+        }
+    }
+
+    /// Derives the key from the password.
+    fn derive(&self, password: &[u8]) -> NodeResult<Zeroizing<Vec<u8>>> {
+        let invalid = |message: String| NodeError::Crypto(message.into());
+        match self {
+            Kdf::Scrypt {
+                dklen,
+                n,
+                r,
+                p,
+                salt,
+            } => {
+                check_dklen(*dklen)?;
+                if !n.is_power_of_two() || *n < 2 {
+                    return Err(invalid(format!("Scrypt cost {} is not a power of 2.", n)));
+                }
+                let params = scrypt::Params::new(n.trailing_zeros() as u8, *r, *p, *dklen)
+                    .map_err(|e| invalid(format!("Invalid scrypt parameters: {}", e)))?;
+
+                let mut key = Zeroizing::new(vec![0u8; *dklen]);
+                scrypt::scrypt(password, &hex::decode(salt)?, &params, &mut key)
+                    .map_err(|e| invalid(format!("Scrypt failed: {}", e)))?;
+                Ok(key)
+            }
+            Kdf::Pbkdf2 {
+                dklen,
+                c,
+                prf,
+                salt,
+            } => {
+                check_dklen(*dklen)?;
+                if prf != "hmac-sha256" {
+                    return Err(invalid(format!("Unsupported PBKDF2 function {}.", prf)));
+                }
+
+                let mut key = Zeroizing::new(vec![0u8; *dklen]);
+                pbkdf2::pbkdf2_hmac::<Sha256>(password, &hex::decode(salt)?, *c, &mut key);
+                Ok(key)
+            }
+        }
+    }
+}
+
+fn check_dklen(dklen: usize) -> NodeResult<()> {
+    if !(DKLEN..=64).contains(&dklen) {
+        return Err(NodeError::Crypto(
+            format!("Derived key length {} is not supported.", dklen).into(),
+        ));
+    }
+    Ok(())
+}
+
+impl Keystore {
+    /// Encrypts the secret with a key derived from the password.
+    pub fn encrypt(secret: &WalletSecret, password: &[u8], kdf: Kdf) -> NodeResult<Self> {
+        let key = kdf.derive(password)?;
+        let iv = rand::random::<[u8; 16]>();
+
+        let mut ciphertext = secret.0.to_vec();
+        apply_cipher(&key, &iv, &mut ciphertext)?;
+
+        Ok(Self {
+            version: 3,
+            id: random_uuid(),
+            address: Some(hex::encode(secret.address())),
+            crypto: KeystoreCrypto {
+                cipher: "aes-128-ctr".to_string(),
+                cipherparams: CipherParams {
+                    iv: hex::encode(iv),
+                },
+                mac: hex::encode(mac(&key, &ciphertext)),
+                ciphertext: hex::encode(ciphertext),
+                kdf,
+            },
+        })
+    }
+
+    /// Decrypts the secret, where a wrong password is detected by the MAC.
+    pub fn decrypt(&self, password: &[u8]) -> NodeResult<WalletSecret> {
+        if self.version != 3 {
+            return Err(NodeError::Crypto(
+                format!("Keystore version {} is not supported.", self.version).into(),
+            ));
+        }
+        if self.crypto.cipher != "aes-128-ctr" {
+            return Err(NodeError::Crypto(
+                format!("Keystore cipher {} is not supported.", self.crypto.cipher).into(),
+            ));
+        }
+
+        let key = self.crypto.kdf.derive(password)?;
+        let ciphertext = hex::decode(&self.crypto.ciphertext)?;
+        if hex::decode(&self.crypto.mac)? != mac(&key, &ciphertext) {
+            return Err(NodeError::Crypto(
+                "Wrong keystore password, or the keystore is corrupted.".into(),
+            ));
+        }
+
+        let mut secret = Zeroizing::new(ciphertext);
+        apply_cipher(
+            &key,
+            &hex::decode(&self.crypto.cipherparams.iv)?,
+            &mut secret,
+        )?;
+        WalletSecret::new(&secret)
+    }
+
+    /// Reads a keystore from a JSON file.
+    pub fn load(path: &Path) -> NodeResult<Self> {
+        let contents = fs::read_to_string(path).map_err(|e| {
+            NodeError::Config(format!("Could not read keystore {}: {}", path.display(), e))
+        })?;
+        Ok(serde_json::from_str(&contents)?)
+    }
+
+    /// Writes the keystore to a new JSON file without overwriting an existing file, where only the
+    /// owner can read the file on Unix.
+    pub fn save(&self, path: &Path) -> NodeResult<()> {
+        let mut options = fs::OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        options.mode(0o600);
+
+        let mut file = options.open(path).map_err(|e| {
+            NodeError::Config(format!(
+                "Could not create keystore {}: {}",
+                path.display(),
+                e
+            ))
+        })?;
+        file.write_all(serde_json::to_string_pretty(self)?.as_bytes())
+            .map_err(|e| {
+                NodeError::Config(format!(
+                    "Could not write keystore {}: {}",
+                    path.display(),
+                    e
+                ))
+            })?;
+        Ok(())
+    }
+}
+
+/// Encrypts or decrypts in place with AES-128-CTR, keyed by the first half of the derived key.
+fn apply_cipher(key: &[u8], iv: &[u8], data: &mut [u8]) -> NodeResult<()> {
+    let mut cipher = Aes128Ctr::new_from_slices(&key[..16], iv)
+        .map_err(|e| NodeError::Crypto(format!("Invalid cipher parameters: {}", e).into()))?;
+    cipher.apply_keystream(data);
+    Ok(())
+}
+
+/// Keccak256 of the second half of the derived key and the ciphertext.
+fn mac(key: &[u8], ciphertext: &[u8]) -> [u8; 32] {
+    keccak256hash([&key[16..32], ciphertext].concat())
+}
+
+/// Returns a random version 4 UUID.
+fn random_uuid() -> String {
+    let mut bytes = rand::random::<[u8; 16]>();
+    bytes[6] = (bytes[6] & 0x0f) | 0x40;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+    let hex = hex::encode(bytes);
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[0..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..32]
+    )
+}
+
+/// Reads the keystore password from the given file, or prompts for it if there is no file.
+///
+/// A single trailing line break within the file is not a part of the password.
+pub fn read_password(file: Option<&Path>) -> NodeResult<Zeroizing<String>> {
+    match file {
+        Some(path) => {
+            let mut password = Zeroizing::new(fs::read_to_string(path).map_err(|e| {
+                NodeError::Config(format!(
+                    "Could not read password file {}: {}",
+                    path.display(),
+                    e
+                ))
+            })?);
+            if password.ends_with('\n') {
+                password.pop();
+                if password.ends_with('\r') {
+                    password.pop();
+                }
+            }
+            Ok(password)
+        }
+        None => prompt_password("Keystore password: "),
+    }
+}
+
+/// Reads the password of a new keystore from the given file, or prompts for it twice if there is
+/// no file.
+pub fn read_new_password(file: Option<&Path>) -> NodeResult<Zeroizing<String>> {
+    if file.is_some() {
+        return read_password(file);
+    }
+
+    let password = prompt_password("New keystore password: ")?;
+    let confirmation = prompt_password("Repeat the password: ")?;
+    if password != confirmation {
+        return Err(NodeError::Config("Passwords do not match.".to_string()));
+    }
+    Ok(password)
+}
+
+fn prompt_password(prompt: &str) -> NodeResult<Zeroizing<String>> {
+    rpassword::prompt_password(prompt)
+        .map(Zeroizing::new)
+        .map_err(|e| NodeError::Config(format!("Could not read password: {}", e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Test vector of the Web3 Secret Storage Definition, with the password `testpassword`.
+    const PBKDF2_KEYSTORE: &str = r#"{
+        "crypto" : {
+            "cipher" : "aes-128-ctr",
+            "cipherparams" : {
+                "iv" : "6087dab2f9fdbbfaddc31a909735c1e6"
+            },
+            "ciphertext" : "5318b4d5bcd28de64ee5559e671353e16f075ecae9f99c7a79a38af5f869aa46",
+            "kdf" : "pbkdf2",
+            "kdfparams" : {
+                "c" : 262144,
+                "dklen" : 32,
+                "prf" : "hmac-sha256",
+                "salt" : "ae3cd4e7013836a3df6bd7241b12db061dbe2c6785853cce422d148a624ce0bd"
+            },
+            "mac" : "517ead924a9d0dc3124507e3393d175ce3ff7c1e96529c6c555ce9e51205e9b2"
+        },
+        "id" : "3198bc9c-6672-5ab3-d995-4942343ae5b6",
+        "version" : 3
+    }"#;
+
+    const SECRET_KEY: &str = "6e6f64656e6f64656e6f64656e6f64656e6f64656e6f64656e6f64656e6f6465";
+
+    #[test]
+    fn test_pbkdf2_vector() {
+        let keystore: Keystore = serde_json::from_str(PBKDF2_KEYSTORE).expect("Should parse");
+        let secret = keystore.decrypt(b"testpassword").expect("Should decrypt");
+        assert_eq!(
+            hex::encode(*secret.0),
+            "7a28b5ba57c53603b0b07b56bba752f7784bf506fa95edc395f5cf6c7514fe9d"
+        );
+        assert_eq!(
+            hex::encode(secret.address()),
+            "008aeeda4d805471df9b2a5b0f38a0c3bcba786b"
+        );
+
+        let error = keystore.decrypt(b"wrongpassword").expect_err("Should fail");
+        assert!(error.to_string().contains("Wrong keystore password"));
+    }
+
+    #[test]
+    fn test_roundtrip() {
+        let secret = WalletSecret::from_hex(SECRET_KEY).expect("Should parse");
+        let address = to_address(&PublicKey::from_secret_key(
+            &SecretKey::parse_slice(&hex::decode(SECRET_KEY).expect("Should decode"))
+                .expect("Should parse"),
+        ));
+        assert_eq!(secret.address(), address);
+
+        for kdf in [Kdf::scrypt(10, 8, 1), Kdf::pbkdf2(1024)] {
+            let keystore = Keystore::encrypt(&secret, b"password", kdf).expect("Should encrypt");
+            assert_eq!(keystore.address, Some(hex::encode(address)));
+
+            // the same address, regardless of how the key is loaded
+            let json = serde_json::to_string(&keystore).expect("Should serialize");
+            let decrypted = serde_json::from_str::<Keystore>(&json)
+                .expect("Should parse")
+                .decrypt(b"password")
+                .expect("Should decrypt");
+            assert_eq!(decrypted.address(), address);
+            assert!(keystore.decrypt(b"Password").is_err());
+
+            let mut tampered = keystore.clone();
+            tampered.crypto.ciphertext.replace_range(0..2, "00");
+            assert!(tampered.decrypt(b"password").is_err());
+        }
+    }
+
+    #[test]
+    fn test_save_load() {
+        let secret = WalletSecret::random();
+        let keystore =
+            Keystore::encrypt(&secret, b"password", Kdf::scrypt(10, 8, 1)).expect("Should encrypt");
+
+        let path = std::env::temp_dir().join(format!("dkn-keystore-{}.json", keystore.id));
+        keystore.save(&path).expect("Should save");
+        assert!(keystore.save(&path).is_err(), "Should not overwrite");
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let permissions = fs::metadata(&path).expect("Should exist").permissions();
+            assert_eq!(permissions.mode() & 0o777, 0o600);
+        }
+
+        let loaded = Keystore::load(&path).expect("Should load");
+        fs::remove_file(&path).expect("Should remove");
+        assert_eq!(loaded, keystore);
+        assert_eq!(
+            loaded
+                .decrypt(b"password")
+                .expect("Should decrypt")
+                .address(),
+            secret.address()
+        );
+    }
+
+    #[test]
+    fn test_read_password() {
+        let path = std::env::temp_dir().join(format!("dkn-password-{}", random_uuid()));
+        fs::write(&path, "correct horse\r\n").expect("Should write");
+        let password = read_password(Some(&path)).expect("Should read");
+        fs::remove_file(&path).expect("Should remove");
+        assert_eq!(password.as_str(), "correct horse");
+    }
+
+    #[test]
+    fn test_invalid_secrets() {
+        assert!(WalletSecret::from_hex("abcd").is_err());
+        assert!(WalletSecret::new(&[0u8; 32]).is_err());
+        assert!(WalletSecret::from_hex(&format!("0x{}", SECRET_KEY)).is_ok());
+
+        let secret = WalletSecret::from_hex(SECRET_KEY).expect("Should parse");
+        assert!(!format!("{:?}", secret).contains(SECRET_KEY));
+        assert!(!format!("{:?}", secret).contains("6e6f"));
+    }
+}
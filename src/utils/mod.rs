@@ -1,5 +1,10 @@
+pub mod capabilities;
 pub mod crypto;
+pub mod executor;
 pub mod filter;
+pub mod keystore;
+pub mod recent;
+pub mod status;
 
 use std::time::{Duration, SystemTime};
 use tokio::signal::unix::{signal, SignalKind};
@@ -0,0 +1,78 @@
+use std::collections::{HashSet, VecDeque};
+
+/// # Recent Ids
+///
+/// The latest identifiers that were recorded, up to a capacity, such as the uuids of answered
+/// heartbeats or the ids of admitted tasks. The oldest identifier is forgotten first.
+#[derive(Debug)]
+pub struct RecentIds {
+    capacity: usize,
+    order: VecDeque<String>,
+    ids: HashSet<String>,
+}
+
+impl RecentIds {
+    /// Creates an empty set that remembers at most `capacity` ids, which is at least 1.
+    pub fn new(capacity: usize) -> Self {
+        let capacity = capacity.max(1);
+        Self {
+            capacity,
+            order: VecDeque::with_capacity(capacity),
+            ids: HashSet::with_capacity(capacity),
+        }
+    }
+
+    /// Returns whether the id was recorded, and not forgotten since.
+    #[inline]
+    pub fn contains(&self, id: &str) -> bool {
+        self.ids.contains(id)
+    }
+
+    /// Returns the number of remembered ids.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.ids.len()
+    }
+
+    /// Returns whether no id is remembered.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.ids.is_empty()
+    }
+
+    /// Records the id, returning `false` if it was already recorded. The oldest id is forgotten
+    /// when the capacity is exceeded.
+    pub fn insert(&mut self, id: &str) -> bool {
+        if !self.ids.insert(id.to_string()) {
+            return false;
+        }
+
+        self.order.push_back(id.to_string());
+        if self.order.len() > self.capacity {
+            if let Some(oldest) = self.order.pop_front() {
+                self.ids.remove(&oldest);
+            }
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_recent_ids() {
+        let mut recent = RecentIds::new(2);
+        assert!(recent.insert("a"));
+        assert!(recent.insert("b"));
+        assert!(!recent.insert("a"));
+
+        // "a" is forgotten as the oldest
+        assert!(recent.insert("c"));
+        assert!(!recent.contains("a"));
+        assert!(recent.insert("a"));
+        assert!(!recent.insert("c"));
+        assert_eq!(recent.len(), 2);
+    }
+}
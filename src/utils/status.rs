@@ -0,0 +1,222 @@
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+/// # Node Status
+///
+/// Tracks the tasks that the node is running, with a fixed number of capacity slots shared by all
+/// task types. A slot is held by a [`TaskGuard`], which releases it when dropped, including when
+/// the task panics.
+#[derive(Debug)]
+pub struct NodeStatus {
+    capacity: usize,
+    slots: Arc<Semaphore>,
+    in_flight: Mutex<BTreeMap<&'static str, usize>>,
+}
+
+/// The status of the node at some point in time.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct StatusSnapshot {
+    /// Number of tasks that can run at once.
+    pub capacity: usize,
+    /// Number of tasks that can start right away.
+    pub remaining: usize,
+    /// Number of running tasks of each task type.
+    pub in_flight: BTreeMap<String, usize>,
+}
+
+impl NodeStatus {
+    /// Creates a status with the given number of slots, which is at least 1.
+    pub fn new(capacity: usize) -> Self {
+        let capacity = capacity.max(1);
+        Self {
+            capacity,
+            slots: Arc::new(Semaphore::new(capacity)),
+            in_flight: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    /// Returns the number of tasks that can run at once.
+    #[inline]
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Returns the number of tasks that can start right away.
+    #[inline]
+    pub fn remaining(&self) -> usize {
+        self.slots.available_permits()
+    }
+
+    /// Returns whether all slots are taken.
+    #[inline]
+    pub fn is_busy(&self) -> bool {
+        self.remaining() == 0
+    }
+
+    /// Returns the number of running tasks, of all task types.
+    pub fn in_flight(&self) -> usize {
+        self.in_flight.lock().values().sum()
+    }
+
+    /// Returns the number of running tasks of the given task type.
+    pub fn in_flight_of(&self, task_type: &str) -> usize {
+        self.in_flight.lock().get(task_type).copied().unwrap_or(0)
+    }
+
+    /// Waits for a free slot, and takes it for a task of the given type.
+    pub async fn acquire(self: &Arc<Self>, task_type: &'static str) -> TaskGuard {
+        let permit = self
+            .slots
+            .clone()
+            .acquire_owned()
+            .await
+            .expect("Slots are never closed.");
+        self.guard(task_type, permit)
+    }
+
+    /// Takes a slot for a task of the given type if one is free.
+    pub fn try_acquire(self: &Arc<Self>, task_type: &'static str) -> Option<TaskGuard> {
+        let permit = self.slots.clone().try_acquire_owned().ok()?;
+        Some(self.guard(task_type, permit))
+    }
+
+    fn guard(self: &Arc<Self>, task_type: &'static str, permit: OwnedSemaphorePermit) -> TaskGuard {
+        *self.in_flight.lock().entry(task_type).or_insert(0) += 1;
+        TaskGuard {
+            status: self.clone(),
+            task_type,
+            _permit: permit,
+        }
+    }
+
+    /// Returns the current capacity and running tasks.
+    pub fn snapshot(&self) -> StatusSnapshot {
+        StatusSnapshot {
+            capacity: self.capacity,
+            remaining: self.remaining(),
+            in_flight: self
+                .in_flight
+                .lock()
+                .iter()
+                .filter(|(_, count)| **count > 0)
+                .map(|(task_type, count)| (task_type.to_string(), *count))
+                .collect(),
+        }
+    }
+}
+
+/// A slot taken by a running task, which is released when the guard is dropped.
+#[derive(Debug)]
+pub struct TaskGuard {
+    status: Arc<NodeStatus>,
+    task_type: &'static str,
+    _permit: OwnedSemaphorePermit,
+}
+
+impl TaskGuard {
+    /// Returns the type of the task holding the slot.
+    #[inline]
+    pub fn task_type(&self) -> &'static str {
+        self.task_type
+    }
+}
+
+impl Drop for TaskGuard {
+    fn drop(&mut self) {
+        // the count is decremented before the permit is released, as fields drop after this
+        if let Some(count) = self.status.in_flight.lock().get_mut(self.task_type) {
+            *count = count.saturating_sub(1);
+        }
+    }
+}
+
+/// Counts of the heartbeat requests that were not answered.
+#[derive(Debug, Default)]
+pub struct HeartbeatStats {
+    dropped: AtomicU64,
+    replayed: AtomicU64,
+}
+
+impl HeartbeatStats {
+    /// Counts a request that was invalid or past its deadline.
+    #[inline]
+    pub fn record_dropped(&self) {
+        self.dropped.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Counts a request whose uuid was already answered.
+    #[inline]
+    pub fn record_replayed(&self) {
+        self.replayed.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Returns the number of dropped requests.
+    #[inline]
+    pub fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+
+    /// Returns the number of replayed requests.
+    #[inline]
+    pub fn replayed(&self) -> u64 {
+        self.replayed.load(Ordering::Relaxed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_guards() {
+        let status = Arc::new(NodeStatus::new(3));
+        let search = status.try_acquire("search").expect("Should acquire");
+        let synthesis = status.try_acquire("synthesis").expect("Should acquire");
+        let _search = status.try_acquire("search").expect("Should acquire");
+
+        assert!(status.is_busy());
+        assert!(status.try_acquire("search").is_none(), "Should be full");
+        assert_eq!(status.in_flight(), 3);
+        assert_eq!(status.in_flight_of("search"), 2);
+        assert_eq!(search.task_type(), "search");
+
+        drop(search);
+        drop(synthesis);
+        assert_eq!(
+            status.snapshot(),
+            StatusSnapshot {
+                capacity: 3,
+                remaining: 2,
+                in_flight: BTreeMap::from([("search".to_string(), 1)]),
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn test_guard_released_on_panic() {
+        let status = Arc::new(NodeStatus::new(1));
+
+        let task_status = status.clone();
+        let result = tokio::spawn(async move {
+            let _guard = task_status.acquire("search").await;
+            panic!("Task failed");
+        })
+        .await;
+
+        assert!(result.is_err(), "Task should panic");
+        assert_eq!(status.in_flight(), 0);
+        assert_eq!(status.remaining(), 1);
+
+        // the slot can be taken again, without waiting
+        let _guard = status.acquire("synthesis").await;
+        assert_eq!(status.in_flight_of("synthesis"), 1);
+    }
+}
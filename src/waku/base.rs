@@ -1,6 +1,8 @@
-use reqwest::Client;
+use reqwest::{Client, Response};
 use std::collections::HashMap;
 
+use crate::errors::{NodeError, NodeResult};
+
 /// A wrapper for GET, POST and DELETE requests.
 #[derive(Debug, Clone)]
 pub struct BaseClient {
@@ -22,7 +24,7 @@ impl BaseClient {
         &self,
         url: &str,
         query_params: Option<HashMap<String, String>>,
-    ) -> Result<reqwest::Response, reqwest::Error> {
+    ) -> NodeResult<Response> {
         let mut full_url = format!("{}/{}", self.base_url, url);
 
         // add query parameters
@@ -38,15 +40,11 @@ impl BaseClient {
             .send()
             .await?;
 
-        res.error_for_status()
+        check_status(res).await
     }
 
     /// A generic POST request.
-    pub async fn post(
-        &self,
-        url: &str,
-        body: serde_json::Value,
-    ) -> Result<reqwest::Response, reqwest::Error> {
+    pub async fn post(&self, url: &str, body: serde_json::Value) -> NodeResult<Response> {
         let full_url = format!("{}/{}", self.base_url, url);
 
         let res = self
@@ -58,15 +56,11 @@ impl BaseClient {
             .send()
             .await?;
 
-        res.error_for_status()
+        check_status(res).await
     }
 
     /// A generic DELETE request.
-    pub async fn delete(
-        &self,
-        url: &str,
-        body: serde_json::Value,
-    ) -> Result<reqwest::Response, reqwest::Error> {
+    pub async fn delete(&self, url: &str, body: serde_json::Value) -> NodeResult<Response> {
         let full_url = format!("{}/{}", self.base_url, url);
 
         let res = self
@@ -78,8 +72,20 @@ impl BaseClient {
             .send()
             .await?;
 
-        res.error_for_status()
+        check_status(res).await
+    }
+}
+
+/// Returns the response if its status is successful, otherwise an error with the response body,
+/// which describes the error for the Waku REST API.
+async fn check_status(res: Response) -> NodeResult<Response> {
+    let status = res.status();
+    if status.is_success() {
+        return Ok(res);
     }
+
+    let message = res.text().await.unwrap_or_default();
+    Err(NodeError::WakuStatus { status, message })
 }
 
 #[inline]
@@ -103,4 +109,26 @@ mod tests {
         let expected = "key1=v_a+lue%2F1".to_string();
         assert_eq!(convert_to_query_params(params), expected);
     }
+
+    #[tokio::test]
+    async fn test_error_status() {
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock("GET", "/health")
+            .with_status(503)
+            .with_body("Node is not ready")
+            .create_async()
+            .await;
+
+        let client = BaseClient::new(server.url());
+        let error = client.get("health", None).await.expect_err("Should fail");
+        match &error {
+            NodeError::WakuStatus { status, message } => {
+                assert_eq!(status.as_u16(), 503);
+                assert_eq!(message, "Node is not ready");
+            }
+            _ => panic!("Unexpected error: {}", error),
+        }
+        assert!(error.is_retryable());
+    }
 }
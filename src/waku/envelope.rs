@@ -0,0 +1,236 @@
+use libsecp256k1::{recover, Message, PublicKey, RecoveryId, Signature};
+use std::fmt;
+
+use crate::{errors::NodeError, utils::crypto::sha256hash};
+
+/// 65-byte signature as hex characters take up 130 characters.
+/// The 65-byte signature is composed of 64-byte RSV signature and 1-byte recovery id.
+pub const SIGNATURE_SIZE: usize = 130;
+
+/// # Signed Envelope
+///
+/// The payload of a message signed by the admin node, which is a 65-byte signature as 130 hex
+/// characters followed by the body. The signature is over the SHA256 digest of the body.
+///
+/// Parsing never panics: any payload that is not a well-formed envelope results in an error.
+#[derive(Debug, Clone)]
+pub struct SignedEnvelope<'a> {
+    signature: Signature,
+    recovery_id: RecoveryId,
+    body: &'a [u8],
+}
+
+/// Why a payload is not a well-formed signed envelope.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EnvelopeError {
+    /// The payload is shorter than a signature.
+    TooShort(usize),
+    /// The signature is not hex encoded.
+    InvalidHex,
+    /// The signature is not a valid ECDSA signature.
+    InvalidSignature,
+    /// The recovery id is not one of 0, 1, 2, 3 or their Ethereum forms 27, 28, 29, 30.
+    InvalidRecoveryId(u8),
+    /// No public key can be recovered from the signature.
+    Recovery,
+}
+
+impl fmt::Display for EnvelopeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EnvelopeError::TooShort(len) => write!(
+                f,
+                "payload of {} bytes is shorter than a {} byte signature",
+                len, SIGNATURE_SIZE
+            ),
+            EnvelopeError::InvalidHex => write!(f, "signature is not hex encoded"),
+            EnvelopeError::InvalidSignature => write!(f, "signature is malformed"),
+            EnvelopeError::InvalidRecoveryId(id) => write!(f, "recovery id {} is invalid", id),
+            EnvelopeError::Recovery => write!(f, "public key could not be recovered"),
+        }
+    }
+}
+
+impl std::error::Error for EnvelopeError {}
+
+impl From<EnvelopeError> for NodeError {
+    fn from(value: EnvelopeError) -> Self {
+        Self::Crypto(Box::new(value))
+    }
+}
+
+impl<'a> SignedEnvelope<'a> {
+    /// Parses the signature and body of a payload.
+    pub fn parse(payload: &'a [u8]) -> Result<Self, EnvelopeError> {
+        if payload.len() < SIGNATURE_SIZE {
+            return Err(EnvelopeError::TooShort(payload.len()));
+        }
+        let (signature, body) = payload.split_at(SIGNATURE_SIZE);
+
+        let mut bytes = [0u8; SIGNATURE_SIZE / 2];
+        hex::decode_to_slice(signature, &mut bytes).map_err(|_| EnvelopeError::InvalidHex)?;
+        let (rs, v) = bytes.split_at(64);
+
+        let signature =
+            Signature::parse_standard_slice(rs).map_err(|_| EnvelopeError::InvalidSignature)?;
+        let recovery_id = match v[0] {
+            id @ 27..=30 => id - 27,
+            id => id,
+        };
+        let recovery_id =
+            RecoveryId::parse(recovery_id).map_err(|_| EnvelopeError::InvalidRecoveryId(v[0]))?;
+
+        Ok(Self {
+            signature,
+            recovery_id,
+            body,
+        })
+    }
+
+    /// Returns the signed body.
+    #[inline]
+    pub fn body(&self) -> &'a [u8] {
+        self.body
+    }
+
+    /// Returns the public key that signed the body.
+    pub fn recover(&self) -> Result<PublicKey, EnvelopeError> {
+        let digest = Message::parse(&sha256hash(self.body));
+        recover(&digest, &self.signature, &self.recovery_id).map_err(|_| EnvelopeError::Recovery)
+    }
+
+    /// Returns whether the body is signed by the given public key.
+    pub fn is_signed_by(&self, public_key: &PublicKey) -> bool {
+        self.recover()
+            .map(|signer| signer == *public_key)
+            .unwrap_or(false)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::waku::message::WakuMessage;
+    use libsecp256k1::{sign, SecretKey};
+    use proptest::prelude::*;
+
+    /// Creates a payload with the body signed by the secret key.
+    fn signed_payload(secret_key: &SecretKey, body: &[u8], recovery_offset: u8) -> Vec<u8> {
+        let (signature, recovery_id) = sign(&Message::parse(&sha256hash(body)), secret_key);
+        let mut payload = hex::encode(signature.serialize()).into_bytes();
+        payload.extend(hex::encode([recovery_id.serialize() + recovery_offset]).into_bytes());
+        payload.extend_from_slice(body);
+        payload
+    }
+
+    fn secret_key() -> SecretKey {
+        SecretKey::parse(b"driadriadriadriadriadriadriadria").expect("Should parse secret key")
+    }
+
+    #[test]
+    fn test_malformed_envelopes() {
+        let valid = signed_payload(&secret_key(), b"{}", 0);
+        let signature = std::str::from_utf8(&valid[..SIGNATURE_SIZE]).expect("Should be hex");
+
+        let cases: Vec<(&str, Vec<u8>, EnvelopeError)> = vec![
+            ("empty", vec![], EnvelopeError::TooShort(0)),
+            ("short", b"abcdef".to_vec(), EnvelopeError::TooShort(6)),
+            (
+                "cut signature",
+                valid[..SIGNATURE_SIZE - 2].to_vec(),
+                EnvelopeError::TooShort(SIGNATURE_SIZE - 2),
+            ),
+            (
+                "non-hex",
+                format!("{}{{}}", "z".repeat(SIGNATURE_SIZE)).into_bytes(),
+                EnvelopeError::InvalidHex,
+            ),
+            (
+                "overflowing signature",
+                format!("{}{{}}", "f".repeat(SIGNATURE_SIZE)).into_bytes(),
+                EnvelopeError::InvalidSignature,
+            ),
+            (
+                "recovery id",
+                format!("{}07{{}}", &signature[..SIGNATURE_SIZE - 2]).into_bytes(),
+                EnvelopeError::InvalidRecoveryId(7),
+            ),
+        ];
+
+        for (name, payload, expected) in cases {
+            let error = SignedEnvelope::parse(&payload).expect_err(name);
+            assert_eq!(error, expected, "Case: {}", name);
+        }
+    }
+
+    #[test]
+    fn test_signed_envelope() {
+        let secret_key = secret_key();
+        let public_key = PublicKey::from_secret_key(&secret_key);
+        let other = PublicKey::from_secret_key(
+            &SecretKey::parse(b"nodenodenodenodenodenodenodenode").expect("Should parse"),
+        );
+
+        // both recovery id forms are accepted
+        for offset in [0, 27] {
+            let payload = signed_payload(&secret_key, b"{\"hello\":\"world\"}", offset);
+            let envelope = SignedEnvelope::parse(&payload).expect("Should parse");
+            assert_eq!(envelope.body(), b"{\"hello\":\"world\"}");
+            assert!(envelope.is_signed_by(&public_key));
+            assert!(!envelope.is_signed_by(&other));
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn test_parse_arbitrary_bytes(payload in prop::collection::vec(any::<u8>(), 0..512)) {
+            if let Ok(envelope) = SignedEnvelope::parse(&payload) {
+                let _ = envelope.recover();
+            }
+        }
+
+        #[test]
+        fn test_parse_arbitrary_hex(signature in "[0-9a-fA-F]{130}", body in ".*") {
+            let payload = format!("{}{}", signature, body);
+            if let Ok(envelope) = SignedEnvelope::parse(payload.as_bytes()) {
+                prop_assert_eq!(envelope.body(), body.as_bytes());
+                let _ = envelope.recover();
+            }
+        }
+
+        #[test]
+        fn test_arbitrary_messages(payload in prop::collection::vec(any::<u8>(), 0..512)) {
+            let public_key = PublicKey::from_secret_key(&secret_key());
+            let message = WakuMessage::new(&payload, "test-topic");
+            let _ = message.is_signed(&public_key);
+            let _ = message.parse_payload::<serde_json::Value>(true);
+
+            // payloads that are not base64 are errors as well
+            let message = WakuMessage {
+                payload: String::from_utf8_lossy(&payload).to_string(),
+                ..message
+            };
+            let _ = message.is_signed(&public_key);
+            let _ = message.parse_payload::<serde_json::Value>(true);
+        }
+
+        #[test]
+        fn test_signed_roundtrip(body in prop::collection::vec(any::<u8>(), 0..256), flip in any::<prop::sample::Index>()) {
+            let secret_key = secret_key();
+            let public_key = PublicKey::from_secret_key(&secret_key);
+            let payload = signed_payload(&secret_key, &body, 0);
+
+            let envelope = SignedEnvelope::parse(&payload).expect("Should parse");
+            prop_assert!(envelope.is_signed_by(&public_key));
+
+            // changing any byte of the body breaks the signature
+            if !body.is_empty() {
+                let mut tampered = payload.clone();
+                let index = SIGNATURE_SIZE + flip.index(body.len());
+                tampered[index] ^= 0x01;
+                let envelope = SignedEnvelope::parse(&tampered).expect("Should parse");
+                prop_assert!(!envelope.is_signed_by(&public_key));
+            }
+        }
+    }
+}
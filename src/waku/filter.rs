@@ -0,0 +1,152 @@
+use async_trait::async_trait;
+use rand::Rng;
+use urlencoding;
+
+use crate::{errors::NodeResult, waku::BaseClient};
+
+use super::{message::WakuMessage, stream::MessageTransport};
+
+/// Client for [12/WAKU2-FILTER](https://github.com/vacp2p/rfc-index/blob/main/waku/standards/core/12/filter.md) operations.
+///
+/// The filter client lets a light node receive the messages of its content topics from a full
+/// node, without relaying the messages of the whole network. It works as follows:
+///
+/// 1. A node subscribes to a content topic at a filter service node.
+/// 2. The service node pushes the messages of that content topic to the node, where they wait to be retrieved.
+/// 3. On termination, the node unsubscribes from the content topic.
+#[derive(Debug, Clone)]
+pub struct FilterClient {
+    base: BaseClient,
+}
+
+impl FilterClient {
+    pub fn new(base: BaseClient) -> Self {
+        FilterClient { base }
+    }
+
+    /// Get messages with a given content topic.
+    ///
+    /// The content topic must have been subscribed to before.
+    pub async fn get_messages(&self, content_topic: &str) -> NodeResult<Vec<WakuMessage>> {
+        log::debug!("Polling {}", content_topic);
+        let content_topic_encoded = urlencoding::encode(content_topic).to_string();
+        let res = self
+            .base
+            .get(
+                &format!("filter/v2/messages/{}", content_topic_encoded),
+                None,
+            )
+            .await?;
+
+        // parse body
+        let msgs = res.json().await?;
+
+        Ok(msgs)
+    }
+
+    /// Subscribe to a content topic.
+    pub async fn subscribe(&self, content_topic: &str) -> NodeResult<()> {
+        log::debug!("Subscribing to {}", content_topic);
+        self.base
+            .post(
+                "filter/v2/subscriptions",
+                subscription_request(content_topic),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    /// Unsubscribe from a content topic.
+    pub async fn unsubscribe(&self, content_topic: &str) -> NodeResult<()> {
+        log::debug!("Unsubscribing from {}", content_topic);
+        self.base
+            .delete(
+                "filter/v2/subscriptions",
+                subscription_request(content_topic),
+            )
+            .await?;
+
+        Ok(())
+    }
+}
+
+/// Creates the body of a subscription request, with a random request id.
+fn subscription_request(content_topic: &str) -> serde_json::Value {
+    let request_id = hex::encode(rand::thread_rng().gen::<[u8; 16]>());
+    serde_json::json!({
+        "requestId": request_id,
+        "contentFilters": [content_topic],
+    })
+}
+
+#[async_trait]
+impl MessageTransport for FilterClient {
+    async fn subscribe(&self, content_topic: &str) -> NodeResult<()> {
+        FilterClient::subscribe(self, content_topic).await
+    }
+
+    async fn unsubscribe(&self, content_topic: &str) -> NodeResult<()> {
+        FilterClient::unsubscribe(self, content_topic).await
+    }
+
+    async fn receive(&self, content_topics: &[String]) -> NodeResult<Vec<WakuMessage>> {
+        let mut messages = Vec::new();
+        for content_topic in content_topics {
+            match self.get_messages(content_topic).await {
+                Ok(topic_messages) => messages.extend(topic_messages),
+                Err(e) => log::error!("Error polling {}: {}", content_topic, e),
+            }
+        }
+        Ok(messages)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_filter_client() {
+        let content_topic = WakuMessage::create_content_topic("heartbeat");
+        let mut server = mockito::Server::new_async().await;
+        let subscribe = server
+            .mock("POST", "/filter/v2/subscriptions")
+            .match_body(mockito::Matcher::PartialJson(serde_json::json!({
+                "contentFilters": [content_topic],
+            })))
+            .with_body(r#"{"requestId":"1","statusDesc":"OK"}"#)
+            .create_async()
+            .await;
+        let messages = server
+            .mock(
+                "GET",
+                format!(
+                    "/filter/v2/messages/{}",
+                    urlencoding::encode(&content_topic)
+                )
+                .as_str(),
+            )
+            .with_header("content-type", "application/json")
+            .with_body(
+                serde_json::to_string(&vec![WakuMessage::new("ping", "heartbeat")])
+                    .expect("Should serialize"),
+            )
+            .create_async()
+            .await;
+
+        let client = FilterClient::new(BaseClient::new(server.url()));
+        MessageTransport::subscribe(&client, &content_topic)
+            .await
+            .expect("Should subscribe");
+        let received = client
+            .receive(std::slice::from_ref(&content_topic))
+            .await
+            .expect("Should receive");
+
+        assert_eq!(received.len(), 1);
+        assert_eq!(received[0].content_topic, content_topic);
+        subscribe.assert_async().await;
+        messages.assert_async().await;
+    }
+}
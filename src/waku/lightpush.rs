@@ -0,0 +1,52 @@
+use crate::{errors::NodeResult, waku::BaseClient};
+
+use super::message::WakuMessage;
+
+/// Client for [19/WAKU2-LIGHTPUSH](https://github.com/vacp2p/rfc-index/blob/main/waku/standards/core/19/lightpush.md) operations.
+///
+/// The lightpush client lets a light node publish messages through a full node, which relays them
+/// to the network on its behalf. Unlike relay, no subscription is needed to publish.
+#[derive(Debug, Clone)]
+pub struct LightpushClient {
+    base: BaseClient,
+}
+
+impl LightpushClient {
+    pub fn new(base: BaseClient) -> Self {
+        LightpushClient { base }
+    }
+
+    /// Send a message.
+    pub async fn send_message(&self, message: WakuMessage) -> NodeResult<()> {
+        log::info!("Pushing: {}", message);
+        let body = serde_json::json!({ "message": message });
+        self.base.post("lightpush/v1/message", body).await?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_lightpush_client() {
+        let message = WakuMessage::new("hello", "test-topic");
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("POST", "/lightpush/v1/message")
+            .match_body(mockito::Matcher::PartialJson(serde_json::json!({
+                "message": {
+                    "payload": message.payload,
+                    "contentTopic": message.content_topic,
+                },
+            })))
+            .create_async()
+            .await;
+
+        let client = LightpushClient::new(BaseClient::new(server.url()));
+        client.send_message(message).await.expect("Should send");
+        mock.assert_async().await;
+    }
+}
@@ -1,7 +1,6 @@
-use crate::{
-    errors::NodeResult,
-    utils::{crypto::sha256hash, get_current_time_nanos},
-};
+use crate::{errors::NodeResult, utils::get_current_time_nanos};
+
+use super::envelope::SignedEnvelope;
 
 use base64::{prelude::BASE64_STANDARD, Engine};
 use core::fmt;
@@ -46,13 +45,6 @@ pub struct WakuMessage {
     pub ephemeral: bool,
 }
 
-/// 65-byte signature as hex characters take up 130 characters.
-/// The 65-byte signature is composed of 64-byte RSV signature and 1-byte recovery id.
-///
-/// When recovery is not required and only verification is being done, we omit the recovery id
-/// and therefore use 128 characters: SIGNATURE_SIZE - 2.
-const SIGNATURE_SIZE: usize = 130;
-
 impl WakuMessage {
     /// Creates a new ephemeral Waku message with current timestamp, version 0.
     ///
@@ -60,7 +52,7 @@ impl WakuMessage {
     ///
     /// - `payload` is gives as bytes. It is base64 encoded internally.
     /// - `topic` is the name of the topic itself within the full content topic. The rest of the content topic
-    /// is filled in automatically, e.g. `/dria/0/<topic>/proto`.
+    ///   is filled in automatically, e.g. `/dria/0/<topic>/proto`.
     pub fn new(payload: impl AsRef<[u8]>, topic: &str) -> Self {
         WakuMessage {
             payload: BASE64_STANDARD.encode(payload),
@@ -76,13 +68,14 @@ impl WakuMessage {
         BASE64_STANDARD.decode(&self.payload)
     }
 
-    /// Decodes and parses the payload into JSON.
+    /// Decodes and parses the payload into JSON, skipping the signature if `signed`.
+    ///
+    /// The signature itself is not checked here, see [`WakuMessage::is_signed`].
     pub fn parse_payload<T: for<'a> Deserialize<'a>>(&self, signed: bool) -> NodeResult<T> {
         let payload = self.decode_payload()?;
 
         let body = if signed {
-            // skips the 65 byte hex signature
-            &payload[SIGNATURE_SIZE..]
+            SignedEnvelope::parse(&payload)?.body()
         } else {
             &payload[..]
         };
@@ -91,19 +84,12 @@ impl WakuMessage {
         Ok(parsed)
     }
 
+    /// Returns whether the payload is signed by the given public key, or an error if the payload
+    /// is not a [`SignedEnvelope`].
     pub fn is_signed(&self, public_key: &PublicKey) -> NodeResult<bool> {
-        // decode base64 payload
         let payload = self.decode_payload()?;
-
-        // parse signature (64 bytes = 128 hex chars, although the full 65-byte RSV signature is given)
-        let (signature, body) = (&payload[..SIGNATURE_SIZE - 2], &payload[SIGNATURE_SIZE..]);
-        let signature = hex::decode(signature).expect("could not decode");
-        let signature =
-            libsecp256k1::Signature::parse_standard_slice(&signature).expect("could not parse");
-
-        // verify signature
-        let digest = libsecp256k1::Message::parse(&sha256hash(body));
-        Ok(libsecp256k1::verify(&digest, &signature, public_key))
+        let envelope = SignedEnvelope::parse(&payload)?;
+        Ok(envelope.is_signed_by(public_key))
     }
 
     /// A [Content Topic](https://docs.waku.org/learn/concepts/content-topics) is represented as a string with the form:
@@ -141,6 +127,7 @@ impl fmt::Display for WakuMessage {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::utils::crypto::sha256hash;
     use libsecp256k1::{Message, SecretKey};
     use rand::thread_rng;
     use serde_json::json;
@@ -188,7 +175,7 @@ mod tests {
         );
         assert_eq!(message.content_topic, "/dria/0/test-topic/proto");
         assert_eq!(message.version, WAKU_ENC_VERSION);
-        assert_eq!(message.ephemeral, true);
+        assert!(message.ephemeral);
         assert!(message.timestamp > 0);
 
         let parsed_body = message.parse_payload(false).expect("Should decode");
@@ -222,7 +209,7 @@ mod tests {
         );
         assert_eq!(message.content_topic, "/dria/0/test-topic/proto");
         assert_eq!(message.version, WAKU_ENC_VERSION);
-        assert_eq!(message.ephemeral, true);
+        assert!(message.ephemeral);
         assert!(message.timestamp > 0);
 
         // check signature
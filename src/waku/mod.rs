@@ -1,21 +1,56 @@
 mod base;
+pub mod envelope;
+mod filter;
+mod lightpush;
 pub mod message;
 mod relay;
+pub mod store;
+pub mod stream;
 
-const DEFAULT_DKN_WAKU_URL: &str = "http://127.0.0.1:8645";
+pub const DEFAULT_DKN_WAKU_URL: &str = "http://127.0.0.1:8645";
 
-use std::env;
+use std::{str::FromStr, sync::Arc};
 
-use crate::errors::NodeResult;
+use crate::errors::{NodeError, NodeResult};
 
-use self::{base::BaseClient, relay::RelayClient};
+use self::{
+    base::BaseClient, filter::FilterClient, lightpush::LightpushClient, message::WakuMessage,
+    relay::RelayClient, store::StoreClient, stream::MessageTransport,
+};
 use serde::{Deserialize, Serialize};
 
+/// How the node takes part in the Waku network.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum WakuMode {
+    /// Receives and sends messages with Relay, as a full node.
+    #[default]
+    Relay,
+    /// Receives messages with Filter and sends them with Lightpush, through a full node.
+    Light,
+}
+
+impl FromStr for WakuMode {
+    type Err = NodeError;
+
+    /// Parses `relay` or `light`, in any case.
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_lowercase().as_str() {
+            "relay" => Ok(WakuMode::Relay),
+            "light" => Ok(WakuMode::Light),
+            other => Err(NodeError::Config(format!("Unknown Waku mode {}.", other))),
+        }
+    }
+}
+
 /// Waku [REST API](https://waku-org.github.io/waku-rest-api) wrapper.
 #[derive(Debug, Clone)]
 pub struct WakuClient {
     base: BaseClient,
+    pub mode: WakuMode,
     pub relay: RelayClient,
+    pub filter: FilterClient,
+    pub lightpush: LightpushClient,
+    pub store: StoreClient,
 }
 
 impl Default for WakuClient {
@@ -25,16 +60,61 @@ impl Default for WakuClient {
 }
 
 impl WakuClient {
-    /// Creates a new instance of WakuClient.
+    /// Creates a new instance of WakuClient in relay mode, with the default URL if none is given.
+    ///
+    /// See [`WakuClient::with_mode`] for light mode.
     pub fn new(url: Option<String>) -> Self {
-        let url: String = url.unwrap_or_else(|| {
-            env::var("DKN_WAKU_URL").unwrap_or(DEFAULT_DKN_WAKU_URL.to_string())
-        });
+        let url = url.unwrap_or_else(|| DEFAULT_DKN_WAKU_URL.to_string());
         log::info!("Waku URL: {}", url);
+        let mode = WakuMode::default();
 
         let base = BaseClient::new(url);
-        let relay = RelayClient::new(base.clone());
-        WakuClient { base, relay }
+        WakuClient {
+            relay: RelayClient::new(base.clone()),
+            filter: FilterClient::new(base.clone()),
+            lightpush: LightpushClient::new(base.clone()),
+            store: StoreClient::new(base.clone()),
+            base,
+            mode,
+        }
+    }
+
+    /// Sets the mode of the client.
+    pub fn with_mode(mut self, mode: WakuMode) -> Self {
+        log::info!("Waku Mode: {:?}", mode);
+        self.mode = mode;
+        self
+    }
+
+    /// Returns the transport that messages are received with in the current mode.
+    pub fn transport(&self) -> Arc<dyn MessageTransport> {
+        match self.mode {
+            WakuMode::Relay => Arc::new(self.relay.clone()),
+            WakuMode::Light => Arc::new(self.filter.clone()),
+        }
+    }
+
+    /// Send a message, where in relay mode its content topic must be subscribed to already.
+    pub async fn send_message(&self, message: WakuMessage) -> NodeResult<()> {
+        match self.mode {
+            WakuMode::Relay => self.relay.send_message(message).await,
+            WakuMode::Light => self.lightpush.send_message(message).await,
+        }
+    }
+
+    /// Send a message on a content topic that is not subscribed to.
+    ///
+    /// In relay mode, the content topic is subscribed to for the duration of the send.
+    pub async fn send_message_once(&self, message: WakuMessage) -> NodeResult<()> {
+        match self.mode {
+            WakuMode::Relay => {
+                let content_topic = message.content_topic.clone();
+                self.relay.subscribe(&content_topic).await?;
+                self.relay.send_message(message).await?;
+                self.relay.unsubscribe(&content_topic).await
+            }
+            WakuMode::Light => self.lightpush.send_message(message).await,
+        }
     }
 
     /// Health-check for the node.
@@ -94,9 +174,35 @@ mod tests {
 
     #[test]
     fn test_waku_config() {
-        env::set_var("DKN_WAKU_URL", "im-a-host:1337");
+        let waku = WakuClient::new(Some("im-a-host:1337".to_string()));
+        assert_eq!(waku.base.base_url, "im-a-host:1337");
+        assert_eq!(waku.mode, WakuMode::Relay);
 
         let waku = WakuClient::new(None);
-        assert_eq!(waku.base.base_url, "im-a-host:1337");
+        assert_eq!(waku.base.base_url, DEFAULT_DKN_WAKU_URL);
+
+        assert_eq!("Light".parse::<WakuMode>().ok(), Some(WakuMode::Light));
+        assert!("full".parse::<WakuMode>().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_light_mode() {
+        let message = WakuMessage::new("hello", "test-topic");
+        let mut server = mockito::Server::new_async().await;
+        let lightpush = server
+            .mock("POST", "/lightpush/v1/message")
+            .create_async()
+            .await;
+        let relay = server
+            .mock("POST", mockito::Matcher::Regex("^/relay/".to_string()))
+            .expect(0)
+            .create_async()
+            .await;
+
+        let waku = WakuClient::new(Some(server.url())).with_mode(WakuMode::Light);
+        waku.send_message_once(message).await.expect("Should send");
+
+        lightpush.assert_async().await;
+        relay.assert_async().await;
     }
 }
@@ -1,7 +1,9 @@
-use crate::{errors::NodeResult, waku::BaseClient};
+use async_trait::async_trait;
 use urlencoding;
 
-use super::message::WakuMessage;
+use crate::{errors::NodeResult, waku::BaseClient};
+
+use super::{message::WakuMessage, stream::MessageTransport};
 
 /// Client for [11/WAKU2-RELAY](https://github.com/vacp2p/rfc-index/blob/main/waku/standards/core/11/relay.md) operations.
 ///
@@ -75,3 +77,27 @@ impl RelayClient {
         Ok(())
     }
 }
+
+/// Polls each content topic in turn, as the REST API has no way of getting the messages of
+/// several content topics at once.
+#[async_trait]
+impl MessageTransport for RelayClient {
+    async fn subscribe(&self, content_topic: &str) -> NodeResult<()> {
+        RelayClient::subscribe(self, content_topic).await
+    }
+
+    async fn unsubscribe(&self, content_topic: &str) -> NodeResult<()> {
+        RelayClient::unsubscribe(self, content_topic).await
+    }
+
+    async fn receive(&self, content_topics: &[String]) -> NodeResult<Vec<WakuMessage>> {
+        let mut messages = Vec::new();
+        for content_topic in content_topics {
+            match self.get_messages(content_topic).await {
+                Ok(topic_messages) => messages.extend(topic_messages),
+                Err(e) => log::error!("Error polling {}: {}", content_topic, e),
+            }
+        }
+        Ok(messages)
+    }
+}
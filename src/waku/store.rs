@@ -0,0 +1,237 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::{
+    errors::{NodeError, NodeResult},
+    waku::BaseClient,
+};
+
+use super::message::WakuMessage;
+
+/// Number of messages in each page of a query.
+pub const DEFAULT_STORE_PAGE_SIZE: usize = 20;
+
+/// Client for [13/WAKU2-STORE](https://github.com/vacp2p/rfc-index/blob/main/waku/standards/core/13/store.md) operations.
+///
+/// The store client retrieves the messages that were published in the past, from the nodes that
+/// keep a history of the network. Ephemeral messages are never stored.
+#[derive(Debug, Clone)]
+pub struct StoreClient {
+    base: BaseClient,
+}
+
+/// A query for the history of some content topics.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StoreQuery {
+    pub content_topics: Vec<String>,
+    /// Earliest timestamp of the messages, in nanoseconds since the Unix epoch.
+    pub start_time: Option<u128>,
+    /// Latest timestamp of the messages, in nanoseconds since the Unix epoch.
+    pub end_time: Option<u128>,
+    pub page_size: usize,
+    /// Whether the oldest messages come first.
+    pub ascending: bool,
+    /// Where to continue from, as returned with the previous page.
+    pub cursor: Option<StoreCursor>,
+}
+
+/// The position of a message within the history, used to request the page after it.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct StoreCursor {
+    pub pubsub_topic: String,
+    pub sender_time: u128,
+    pub store_time: u128,
+    pub digest: StoreDigest,
+}
+
+/// The base64 encoded digest of a message.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct StoreDigest {
+    pub data: String,
+}
+
+/// A page of messages, with the cursor of the next page if there is one.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct StorePage {
+    #[serde(default)]
+    pub messages: Vec<WakuMessage>,
+    #[serde(default)]
+    pub cursor: Option<StoreCursor>,
+    #[serde(default)]
+    error_message: Option<String>,
+}
+
+impl StoreQuery {
+    /// Creates a query for the messages of the content topics within the time range, oldest first.
+    pub fn new(content_topics: Vec<String>, start_time: u128, end_time: u128) -> Self {
+        Self {
+            content_topics,
+            start_time: Some(start_time),
+            end_time: Some(end_time),
+            page_size: DEFAULT_STORE_PAGE_SIZE,
+            ascending: true,
+            cursor: None,
+        }
+    }
+
+    /// Returns the query parameters of the request.
+    fn to_params(&self) -> HashMap<String, String> {
+        let mut params = HashMap::from([
+            ("contentTopics".to_string(), self.content_topics.join(",")),
+            ("pageSize".to_string(), self.page_size.to_string()),
+            ("ascending".to_string(), self.ascending.to_string()),
+        ]);
+        if let Some(start_time) = self.start_time {
+            params.insert("startTime".to_string(), start_time.to_string());
+        }
+        if let Some(end_time) = self.end_time {
+            params.insert("endTime".to_string(), end_time.to_string());
+        }
+        if let Some(cursor) = &self.cursor {
+            params.insert("pubsubTopic".to_string(), cursor.pubsub_topic.clone());
+            params.insert("senderTime".to_string(), cursor.sender_time.to_string());
+            params.insert("storeTime".to_string(), cursor.store_time.to_string());
+            params.insert("digest".to_string(), cursor.digest.data.clone());
+        }
+        params
+    }
+}
+
+impl StoreClient {
+    pub fn new(base: BaseClient) -> Self {
+        StoreClient { base }
+    }
+
+    /// Get a single page of messages.
+    pub async fn get_messages(&self, query: &StoreQuery) -> NodeResult<StorePage> {
+        log::debug!("Querying history of {:?}", query.content_topics);
+        let res = self
+            .base
+            .get("store/v1/messages", Some(query.to_params()))
+            .await?;
+
+        // parse body
+        let status = res.status();
+        let page: StorePage = res.json().await?;
+        match page.error_message {
+            Some(message) if !message.is_empty() => Err(NodeError::WakuStatus { status, message }),
+            _ => Ok(page),
+        }
+    }
+
+    /// Get the messages of all pages, following the cursors up to `max_pages` pages.
+    pub async fn get_all_messages(
+        &self,
+        mut query: StoreQuery,
+        max_pages: usize,
+    ) -> NodeResult<Vec<WakuMessage>> {
+        let mut messages = Vec::new();
+        for _ in 0..max_pages {
+            let page = self.get_messages(&query).await?;
+            messages.extend(page.messages);
+
+            match page.cursor {
+                Some(cursor) => query.cursor = Some(cursor),
+                None => return Ok(messages),
+            }
+        }
+
+        log::warn!(
+            "Stopped querying history of {:?} after {} pages.",
+            query.content_topics,
+            max_pages
+        );
+        Ok(messages)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mockito::Matcher;
+
+    #[tokio::test]
+    async fn test_store_pagination() {
+        let content_topic = WakuMessage::create_content_topic("search");
+        let cursor = StoreCursor {
+            pubsub_topic: "/waku/2/rs/1/0".to_string(),
+            sender_time: 1714129073557846272,
+            store_time: 1714129073557846300,
+            digest: StoreDigest {
+                data: "ZGlnZXN0".to_string(),
+            },
+        };
+
+        let mut server = mockito::Server::new_async().await;
+        // the page after the cursor is matched first, as the first page matches any cursor
+        let second = server
+            .mock("GET", "/store/v1/messages")
+            .match_query(Matcher::AllOf(vec![
+                Matcher::UrlEncoded("digest".into(), "ZGlnZXN0".into()),
+                Matcher::UrlEncoded("storeTime".into(), "1714129073557846300".into()),
+            ]))
+            .with_header("content-type", "application/json")
+            .with_body(
+                serde_json::json!({
+                    "messages": [WakuMessage::new("second", "search")],
+                    "cursor": null,
+                })
+                .to_string(),
+            )
+            .create_async()
+            .await;
+
+        let first = server
+            .mock("GET", "/store/v1/messages")
+            .match_query(Matcher::AllOf(vec![
+                Matcher::UrlEncoded("contentTopics".into(), content_topic.clone()),
+                Matcher::UrlEncoded("startTime".into(), "100".into()),
+                Matcher::UrlEncoded("endTime".into(), "200".into()),
+                Matcher::UrlEncoded("ascending".into(), "true".into()),
+            ]))
+            .with_header("content-type", "application/json")
+            .with_body(
+                serde_json::json!({
+                    "messages": [WakuMessage::new("first", "search")],
+                    "cursor": cursor,
+                    "errorMessage": "",
+                })
+                .to_string(),
+            )
+            .create_async()
+            .await;
+
+        let client = StoreClient::new(BaseClient::new(server.url()));
+        let messages = client
+            .get_all_messages(StoreQuery::new(vec![content_topic], 100, 200), 10)
+            .await
+            .expect("Should get messages");
+
+        let payloads = messages
+            .iter()
+            .map(|message| message.decode_payload().expect("Should decode"))
+            .collect::<Vec<_>>();
+        assert_eq!(payloads, vec![b"first".to_vec(), b"second".to_vec()]);
+        first.assert_async().await;
+        second.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_store_error() {
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock("GET", "/store/v1/messages")
+            .match_query(Matcher::Any)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"messages":[],"errorMessage":"store is not mounted"}"#)
+            .create_async()
+            .await;
+
+        let client = StoreClient::new(BaseClient::new(server.url()));
+        let query = StoreQuery::new(vec!["/dria/0/search/proto".to_string()], 100, 200);
+        let error = client.get_messages(&query).await.expect_err("Should fail");
+        assert!(error.to_string().contains("store is not mounted"));
+    }
+}
@@ -0,0 +1,444 @@
+use async_trait::async_trait;
+use parking_lot::Mutex;
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::Arc,
+    time::Duration,
+};
+use tokio::sync::mpsc::{self, error::TrySendError};
+use tokio_util::sync::CancellationToken;
+
+use super::message::WakuMessage;
+use crate::errors::NodeResult;
+
+/// Polling interval right after messages are received.
+pub const DEFAULT_MIN_POLL_INTERVAL: Duration = Duration::from_millis(250);
+/// Polling interval after the node has been idle for a while.
+pub const DEFAULT_MAX_POLL_INTERVAL: Duration = Duration::from_secs(2);
+/// Number of messages buffered for each topic before the topic stops being polled.
+pub const DEFAULT_TOPIC_CAPACITY: usize = 64;
+
+/// # Message Transport
+///
+/// A way of receiving messages from the Waku network, such as polling the REST API or listening to
+/// a websocket. Messages are only received on subscribed content topics.
+#[async_trait]
+pub trait MessageTransport: Send + Sync + 'static {
+    async fn subscribe(&self, content_topic: &str) -> NodeResult<()>;
+
+    async fn unsubscribe(&self, content_topic: &str) -> NodeResult<()>;
+
+    /// Returns the messages received on the given content topics since the last call.
+    ///
+    /// A push-based transport may wait here until messages arrive, while a polling transport
+    /// returns right away, possibly with no messages.
+    async fn receive(&self, content_topics: &[String]) -> NodeResult<Vec<WakuMessage>>;
+}
+
+/// # Message Dispatcher
+///
+/// Receives the messages of all subscribed topics through a single transport, and sends each to
+/// the channel of its content topic, read by the worker of that topic.
+///
+/// A topic is not received from while its channel is full, so the messages of a slow worker are
+/// left within the transport instead of piling up in memory. Messages received for a topic whose
+/// channel fills up meanwhile are kept aside until it has room, so that a slow worker never holds
+/// back the others. The transport is polled less often while no messages arrive, down to the
+/// maximum interval.
+pub struct MessageDispatcher {
+    transport: Arc<dyn MessageTransport>,
+    routes: Mutex<HashMap<String, mpsc::Sender<WakuMessage>>>,
+    /// Messages waiting for room in the channel of their topic.
+    pending: Mutex<HashMap<String, VecDeque<WakuMessage>>>,
+    min_interval: Duration,
+    max_interval: Duration,
+}
+
+impl std::fmt::Debug for MessageDispatcher {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MessageDispatcher")
+            .field("topics", &self.routes.lock().keys().collect::<Vec<_>>())
+            .field("min_interval", &self.min_interval)
+            .field("max_interval", &self.max_interval)
+            .finish()
+    }
+}
+
+impl MessageDispatcher {
+    pub fn new(transport: Arc<dyn MessageTransport>) -> Self {
+        Self {
+            transport,
+            routes: Mutex::new(HashMap::new()),
+            pending: Mutex::new(HashMap::new()),
+            min_interval: DEFAULT_MIN_POLL_INTERVAL,
+            max_interval: DEFAULT_MAX_POLL_INTERVAL,
+        }
+    }
+
+    /// Sets the polling intervals when busy and when idle.
+    pub fn with_intervals(mut self, min_interval: Duration, max_interval: Duration) -> Self {
+        self.min_interval = min_interval;
+        self.max_interval = max_interval.max(min_interval);
+        self
+    }
+
+    /// Subscribes to the content topic, and returns the channel that its messages are sent to.
+    ///
+    /// Subscribing to a topic again replaces its previous channel.
+    pub async fn subscribe(
+        &self,
+        content_topic: &str,
+        capacity: usize,
+    ) -> NodeResult<mpsc::Receiver<WakuMessage>> {
+        self.transport.subscribe(content_topic).await?;
+
+        let (sender, receiver) = mpsc::channel(capacity.max(1));
+        self.routes.lock().insert(content_topic.to_string(), sender);
+        Ok(receiver)
+    }
+
+    /// Unsubscribes from the content topic, closing its channel.
+    pub async fn unsubscribe(&self, content_topic: &str) -> NodeResult<()> {
+        self.routes.lock().remove(content_topic);
+        self.pending.lock().remove(content_topic);
+        self.transport.unsubscribe(content_topic).await
+    }
+
+    /// Returns the subscribed content topics whose channels have room for new messages, and no
+    /// pending messages.
+    fn ready_topics(&self) -> Vec<String> {
+        let mut routes = self.routes.lock();
+        let mut pending = self.pending.lock();
+        routes.retain(|_, sender| !sender.is_closed());
+        pending.retain(|topic, messages| !messages.is_empty() && routes.contains_key(topic));
+        routes
+            .iter()
+            .filter(|(topic, sender)| sender.capacity() > 0 && !pending.contains_key(*topic))
+            .map(|(topic, _)| topic.clone())
+            .collect()
+    }
+
+    /// Sends the pending messages of each topic until its channel is full.
+    ///
+    /// Returns the number of messages that were sent.
+    fn flush_pending(&self) -> usize {
+        let routes = self.routes.lock();
+        let mut pending = self.pending.lock();
+
+        let mut dispatched = 0;
+        for (topic, messages) in pending.iter_mut() {
+            let Some(sender) = routes.get(topic) else {
+                messages.clear();
+                continue;
+            };
+
+            while let Some(message) = messages.pop_front() {
+                match sender.try_send(message) {
+                    Ok(()) => dispatched += 1,
+                    Err(TrySendError::Full(message)) => {
+                        messages.push_front(message);
+                        break;
+                    }
+                    Err(TrySendError::Closed(_)) => {
+                        messages.clear();
+                        break;
+                    }
+                }
+            }
+        }
+
+        dispatched
+    }
+
+    /// Sends the message to the channel of its topic, keeping it aside if the channel is full or
+    /// other messages of the topic are already waiting.
+    ///
+    /// Returns `true` if the message was sent.
+    fn route(&self, message: WakuMessage) -> bool {
+        let sender = self.routes.lock().get(&message.content_topic).cloned();
+        let Some(sender) = sender else {
+            log::debug!(
+                "Dropping message on {}: not subscribed",
+                message.content_topic
+            );
+            return false;
+        };
+
+        let mut pending = self.pending.lock();
+        if let Some(messages) = pending.get_mut(&message.content_topic) {
+            messages.push_back(message);
+            return false;
+        }
+
+        match sender.try_send(message) {
+            Ok(()) => true,
+            Err(TrySendError::Full(message)) => {
+                pending
+                    .entry(message.content_topic.clone())
+                    .or_default()
+                    .push_back(message);
+                false
+            }
+            // a closed channel is removed on the next dispatch
+            Err(TrySendError::Closed(_)) => false,
+        }
+    }
+
+    /// Sends the pending messages, then receives messages once and sends them to their channels.
+    ///
+    /// Returns the number of messages that were sent. This never waits for room in a channel, as
+    /// messages of full channels are kept pending.
+    pub async fn dispatch_once(&self) -> NodeResult<usize> {
+        let mut dispatched = self.flush_pending();
+
+        let topics = self.ready_topics();
+        if topics.is_empty() {
+            return Ok(dispatched);
+        }
+
+        for message in self.transport.receive(&topics).await? {
+            if self.route(message) {
+                dispatched += 1;
+            }
+        }
+
+        Ok(dispatched)
+    }
+
+    /// Dispatches messages until the token is cancelled, polling more often while messages arrive.
+    pub async fn run(&self, cancellation: &CancellationToken) {
+        let mut interval = self.min_interval;
+        loop {
+            let dispatched = tokio::select! {
+                _ = cancellation.cancelled() => break,
+                result = self.dispatch_once() => result.unwrap_or_else(|e| {
+                    log::error!("Error receiving messages: {}", e);
+                    0
+                }),
+            };
+
+            interval = next_interval(interval, dispatched, self.min_interval, self.max_interval);
+            tokio::select! {
+                _ = cancellation.cancelled() => break,
+                _ = tokio::time::sleep(interval) => {}
+            }
+        }
+    }
+}
+
+/// Returns the polling interval after a dispatch, which is reset when messages were received and
+/// doubled otherwise.
+#[inline]
+fn next_interval(
+    interval: Duration,
+    dispatched: usize,
+    min_interval: Duration,
+    max_interval: Duration,
+) -> Duration {
+    if dispatched > 0 {
+        min_interval
+    } else {
+        (interval * 2).clamp(min_interval, max_interval)
+    }
+}
+
+/// Waits for a message on the channel, and returns it along with the rest of the buffered ones.
+///
+/// Returns `None` once the channel is closed.
+pub async fn recv_batch(receiver: &mut mpsc::Receiver<WakuMessage>) -> Option<Vec<WakuMessage>> {
+    let mut messages = vec![receiver.recv().await?];
+    while let Ok(message) = receiver.try_recv() {
+        messages.push(message);
+    }
+    Some(messages)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::VecDeque;
+
+    /// A transport that returns the queued messages of each topic, one per receive unless a larger
+    /// batch size is given.
+    #[derive(Default)]
+    struct MockTransport {
+        queues: Mutex<HashMap<String, VecDeque<WakuMessage>>>,
+        received: Mutex<Vec<Vec<String>>>,
+        batch_size: usize,
+    }
+
+    impl MockTransport {
+        fn with_batch_size(batch_size: usize) -> Self {
+            Self {
+                batch_size,
+                ..Default::default()
+            }
+        }
+
+        fn push(&self, topic: &str, payload: &str) {
+            let message = WakuMessage::new(payload, topic);
+            self.queues
+                .lock()
+                .entry(message.content_topic.clone())
+                .or_default()
+                .push_back(message);
+        }
+    }
+
+    #[async_trait]
+    impl MessageTransport for MockTransport {
+        async fn subscribe(&self, _: &str) -> NodeResult<()> {
+            Ok(())
+        }
+
+        async fn unsubscribe(&self, _: &str) -> NodeResult<()> {
+            Ok(())
+        }
+
+        async fn receive(&self, content_topics: &[String]) -> NodeResult<Vec<WakuMessage>> {
+            let mut content_topics = content_topics.to_vec();
+            content_topics.sort();
+            self.received.lock().push(content_topics.clone());
+
+            let mut queues = self.queues.lock();
+            let mut messages = Vec::new();
+            for topic in &content_topics {
+                if let Some(queue) = queues.get_mut(topic) {
+                    let count = self.batch_size.max(1).min(queue.len());
+                    messages.extend(queue.drain(..count));
+                }
+            }
+            Ok(messages)
+        }
+    }
+
+    fn content_topic(topic: &str) -> String {
+        WakuMessage::create_content_topic(topic)
+    }
+
+    #[tokio::test]
+    async fn test_dispatch_by_topic() {
+        let transport = Arc::new(MockTransport::default());
+        let dispatcher = MessageDispatcher::new(transport.clone());
+        let mut heartbeats = dispatcher
+            .subscribe(&content_topic("heartbeat"), 8)
+            .await
+            .expect("Should subscribe");
+        let mut tasks = dispatcher
+            .subscribe(&content_topic("search"), 8)
+            .await
+            .expect("Should subscribe");
+
+        transport.push("heartbeat", "ping");
+        transport.push("search", "task");
+        transport.push("synthesis", "unknown");
+        assert_eq!(
+            dispatcher.dispatch_once().await.expect("Should dispatch"),
+            2
+        );
+
+        let heartbeat = heartbeats.try_recv().expect("Should have heartbeat");
+        assert_eq!(heartbeat.decode_payload().expect("Should decode"), b"ping");
+        let task = tasks.try_recv().expect("Should have task");
+        assert_eq!(task.decode_payload().expect("Should decode"), b"task");
+        assert!(heartbeats.try_recv().is_err() && tasks.try_recv().is_err());
+
+        // closed channels are not received from
+        drop(tasks);
+        dispatcher.dispatch_once().await.expect("Should dispatch");
+        assert_eq!(
+            transport.received.lock().last(),
+            Some(&vec![content_topic("heartbeat")])
+        );
+    }
+
+    #[tokio::test]
+    async fn test_backpressure() {
+        let transport = Arc::new(MockTransport::default());
+        let dispatcher = MessageDispatcher::new(transport.clone());
+        let mut tasks = dispatcher
+            .subscribe(&content_topic("search"), 1)
+            .await
+            .expect("Should subscribe");
+
+        transport.push("search", "first");
+        transport.push("search", "second");
+        assert_eq!(
+            dispatcher.dispatch_once().await.expect("Should dispatch"),
+            1
+        );
+
+        // the full topic is left within the transport
+        assert_eq!(
+            dispatcher.dispatch_once().await.expect("Should dispatch"),
+            0
+        );
+        assert_eq!(transport.received.lock().len(), 1);
+
+        let batch = recv_batch(&mut tasks).await.expect("Should receive");
+        assert_eq!(batch.len(), 1);
+        assert_eq!(
+            dispatcher.dispatch_once().await.expect("Should dispatch"),
+            1
+        );
+        assert_eq!(transport.received.lock().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_full_channel_does_not_block() {
+        let transport = Arc::new(MockTransport::with_batch_size(2));
+        let dispatcher = MessageDispatcher::new(transport.clone());
+        let mut tasks = dispatcher
+            .subscribe(&content_topic("search"), 1)
+            .await
+            .expect("Should subscribe");
+        let mut workflows = dispatcher
+            .subscribe(&content_topic("workflow"), 8)
+            .await
+            .expect("Should subscribe");
+
+        // the second search message does not fit, and comes before the workflow message
+        transport.push("search", "first");
+        transport.push("search", "second");
+        transport.push("workflow", "task");
+        let dispatched = tokio::time::timeout(Duration::from_secs(1), dispatcher.dispatch_once())
+            .await
+            .expect("Should not wait for the full channel")
+            .expect("Should dispatch");
+        assert_eq!(dispatched, 2);
+        let workflow = workflows.try_recv().expect("Should have workflow task");
+        assert_eq!(workflow.decode_payload().expect("Should decode"), b"task");
+
+        // the topic with pending messages is not received from
+        assert_eq!(
+            dispatcher.dispatch_once().await.expect("Should dispatch"),
+            0
+        );
+        assert_eq!(
+            transport.received.lock().last(),
+            Some(&vec![content_topic("workflow")])
+        );
+
+        // the pending message is sent once there is room
+        let first = tasks.try_recv().expect("Should have first task");
+        assert_eq!(first.decode_payload().expect("Should decode"), b"first");
+        assert_eq!(
+            dispatcher.dispatch_once().await.expect("Should dispatch"),
+            1
+        );
+        let second = tasks.try_recv().expect("Should have second task");
+        assert_eq!(second.decode_payload().expect("Should decode"), b"second");
+    }
+
+    #[test]
+    fn test_next_interval() {
+        let (min, max) = (Duration::from_millis(250), Duration::from_secs(2));
+
+        let mut interval = min;
+        for expected in [500, 1000, 2000, 2000] {
+            interval = next_interval(interval, 0, min, max);
+            assert_eq!(interval, Duration::from_millis(expected));
+        }
+        assert_eq!(next_interval(interval, 3, min, max), min);
+    }
+}
@@ -0,0 +1,331 @@
+use serde::de::DeserializeOwned;
+use std::{collections::BTreeMap, fmt};
+
+use crate::{
+    compute::payload::TaskRequestPayload,
+    node::DriaComputeNode,
+    utils::{get_current_time_nanos, recent::RecentIds, status::TaskGuard},
+    waku::message::WakuMessage,
+};
+
+/// Number of admitted task ids that are remembered to reject duplicates.
+const MAX_SEEN_TASKS: usize = 1024;
+
+/// Why a task was not admitted, in the order that the checks are made.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Rejection {
+    /// The payload could not be parsed as a task.
+    Parse,
+    /// The payload is not signed by the admin node.
+    Signature,
+    /// The deadline of the task has passed.
+    Deadline,
+    /// The filter of the task could not be read.
+    Filter,
+    /// The node is not selected by the filter of the task.
+    NotSelected,
+    /// The task was already admitted.
+    Duplicate,
+    /// The node has no free slot, so the task should be kept until one frees up.
+    Capacity,
+}
+
+impl fmt::Display for Rejection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let reason = match self {
+            Rejection::Parse => "invalid payload",
+            Rejection::Signature => "invalid signature",
+            Rejection::Deadline => "deadline",
+            Rejection::Filter => "invalid filter",
+            Rejection::NotSelected => "filter",
+            Rejection::Duplicate => "duplicate",
+            Rejection::Capacity => "capacity",
+        };
+        write!(f, "{}", reason)
+    }
+}
+
+/// # Task Admission
+///
+/// Decides which of the received tasks a worker runs, counting the rejected ones by reason.
+///
+/// An admitted task takes one of the slots of the node, shared by all workers. Tasks that find no
+/// free slot are not counted as rejected, as they can be admitted again once a slot frees up.
+#[derive(Debug)]
+pub struct TaskAdmission {
+    seen: RecentIds,
+    rejections: BTreeMap<Rejection, usize>,
+}
+
+impl Default for TaskAdmission {
+    fn default() -> Self {
+        Self::new(MAX_SEEN_TASKS)
+    }
+}
+
+impl TaskAdmission {
+    /// Creates an admission that remembers the last `max_seen` admitted tasks.
+    pub fn new(max_seen: usize) -> Self {
+        Self {
+            seen: RecentIds::new(max_seen),
+            rejections: BTreeMap::new(),
+        }
+    }
+
+    /// Returns the task within the message along with the slot that it runs in, if it should be
+    /// run as a task of the given type.
+    pub fn admit<I: DeserializeOwned>(
+        &mut self,
+        node: &DriaComputeNode,
+        message: &WakuMessage,
+        task_type: &'static str,
+    ) -> Result<(TaskRequestPayload<I>, TaskGuard), Rejection> {
+        let task = match self.check(node, message) {
+            Ok(task) => task,
+            Err(rejection) => {
+                *self.rejections.entry(rejection).or_insert(0) += 1;
+                return Err(rejection);
+            }
+        };
+
+        let Some(slot) = node.status.try_acquire(task_type) else {
+            log::debug!("Deferring {} until a slot is free.", task.task_id);
+            return Err(Rejection::Capacity);
+        };
+
+        self.seen.insert(&task.task_id);
+        Ok((task, slot))
+    }
+
+    /// Returns the number of rejected tasks for each reason.
+    pub fn rejections(&self) -> &BTreeMap<Rejection, usize> {
+        &self.rejections
+    }
+
+    fn check<I: DeserializeOwned>(
+        &self,
+        node: &DriaComputeNode,
+        message: &WakuMessage,
+    ) -> Result<TaskRequestPayload<I>, Rejection> {
+        let task = message
+            .parse_payload::<TaskRequestPayload<I>>(true)
+            .map_err(|e| {
+                log::error!("Error parsing payload: {}", e);
+                Rejection::Parse
+            })?;
+
+        let reject = |rejection: Rejection| {
+            log::debug!("Skipping {} due to {}.", task.task_id, rejection);
+            rejection
+        };
+
+        match message.is_signed(&node.config.DKN_ADMIN_PUBLIC_KEY) {
+            Ok(true) => {}
+            Ok(false) => return Err(reject(Rejection::Signature)),
+            Err(e) => {
+                log::warn!("Could not verify message signature: {}", e);
+                return Err(reject(Rejection::Signature));
+            }
+        }
+
+        if get_current_time_nanos() >= task.deadline {
+            return Err(reject(Rejection::Deadline));
+        }
+
+        match node.is_tasked(&task.filter) {
+            Ok(true) => {}
+            Ok(false) => return Err(reject(Rejection::NotSelected)),
+            Err(e) => {
+                log::error!("Error checking task inclusion: {}", e);
+                return Err(reject(Rejection::Filter));
+            }
+        }
+
+        if self.seen.contains(&task.task_id) {
+            return Err(reject(Rejection::Duplicate));
+        }
+
+        Ok(task)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::utils::{crypto::sha256hash, filter::FilterPayload};
+    use fastbloom_rs::{FilterBuilder, Membership};
+    use libsecp256k1::{sign, Message, SecretKey};
+    use serde_json::json;
+
+    /// Secret key of the default admin public key, hex(b"dria") * 8.
+    pub(crate) const ADMIN_SECRET_KEY: &[u8; 32] = b"driadriadriadriadriadriadriadria";
+
+    /// Creates a task message, signed with the given secret key.
+    pub(crate) fn task_message(
+        secret_key: &[u8; 32],
+        task_id: &str,
+        deadline: u128,
+        filter: FilterPayload,
+    ) -> WakuMessage {
+        let body = json!({
+            "taskId": task_id,
+            "deadline": deadline,
+            "input": "What is the capital of France?",
+            "filter": filter,
+            "publicKey": "",
+        })
+        .to_string();
+
+        let secret_key = SecretKey::parse(secret_key).expect("Should parse secret key");
+        let (signature, recid) = sign(&Message::parse(&sha256hash(body.as_bytes())), &secret_key);
+        let payload = format!(
+            "{}{}{}",
+            hex::encode(signature.serialize()),
+            hex::encode([recid.serialize()]),
+            body
+        );
+        WakuMessage::new(payload, "search")
+    }
+
+    /// Creates a filter with the given items.
+    pub(crate) fn filter_of(items: &[&[u8]]) -> FilterPayload {
+        let mut bloom = FilterBuilder::new(128, 0.01).build_bloom_filter();
+        for item in items {
+            bloom.add(item);
+        }
+        FilterPayload::from(bloom)
+    }
+
+    /// Creates a tiny filter that is full of other items, so that it contains any item.
+    fn false_positive_filter() -> FilterPayload {
+        let mut bloom = FilterBuilder::new(1, 0.5).build_bloom_filter();
+        for i in 0u32..256 {
+            bloom.add(&i.to_be_bytes());
+        }
+        FilterPayload::from(bloom)
+    }
+
+    #[test]
+    fn test_admission() {
+        let node = DriaComputeNode::default();
+        let address = node.address();
+        let future = get_current_time_nanos() + 60_000_000_000;
+        let past = get_current_time_nanos() - 1;
+
+        let false_positive = false_positive_filter();
+        let bloom = fastbloom_rs::BloomFilter::try_from(&false_positive).expect("Should read");
+        assert!(bloom.contains(&address), "Filter should be saturated");
+
+        let unparsable = WakuMessage::new(format!("{}{{}}", "0".repeat(130)), "search");
+        let mut invalid_filter = filter_of(&[&address]);
+        invalid_filter.hex = "not hex".to_string();
+
+        let cases: Vec<(&str, WakuMessage, bool, Result<(), Rejection>)> = vec![
+            (
+                "selected",
+                task_message(ADMIN_SECRET_KEY, "selected", future, filter_of(&[&address])),
+                false,
+                Ok(()),
+            ),
+            (
+                "selected among others",
+                task_message(
+                    ADMIN_SECRET_KEY,
+                    "among",
+                    future,
+                    filter_of(&[b"other", &address]),
+                ),
+                false,
+                Ok(()),
+            ),
+            (
+                "not selected",
+                task_message(
+                    ADMIN_SECRET_KEY,
+                    "not-selected",
+                    future,
+                    filter_of(&[b"other"]),
+                ),
+                false,
+                Err(Rejection::NotSelected),
+            ),
+            (
+                "empty filter",
+                task_message(ADMIN_SECRET_KEY, "empty", future, filter_of(&[])),
+                false,
+                Err(Rejection::NotSelected),
+            ),
+            (
+                "false positive",
+                task_message(ADMIN_SECRET_KEY, "false-positive", future, false_positive),
+                false,
+                Ok(()),
+            ),
+            ("unparsable", unparsable, false, Err(Rejection::Parse)),
+            (
+                "not signed by admin",
+                task_message(
+                    b"nodenodenodenodenodenodenodenode",
+                    "forged",
+                    future,
+                    filter_of(&[&address]),
+                ),
+                false,
+                Err(Rejection::Signature),
+            ),
+            (
+                "expired",
+                task_message(ADMIN_SECRET_KEY, "expired", past, filter_of(&[&address])),
+                false,
+                Err(Rejection::Deadline),
+            ),
+            (
+                "invalid filter",
+                task_message(ADMIN_SECRET_KEY, "invalid-filter", future, invalid_filter),
+                false,
+                Err(Rejection::Filter),
+            ),
+            (
+                "duplicate",
+                task_message(ADMIN_SECRET_KEY, "selected", future, filter_of(&[&address])),
+                false,
+                Err(Rejection::Duplicate),
+            ),
+            (
+                "at capacity",
+                task_message(ADMIN_SECRET_KEY, "busy", future, filter_of(&[&address])),
+                true,
+                Err(Rejection::Capacity),
+            ),
+            (
+                "slot freed",
+                task_message(ADMIN_SECRET_KEY, "busy", future, filter_of(&[&address])),
+                false,
+                Ok(()),
+            ),
+        ];
+
+        let mut admission = TaskAdmission::default();
+        for (name, message, busy, expected) in cases {
+            // slots may be taken by the tasks of any worker
+            let taken: Vec<_> = std::iter::from_fn(|| node.status.try_acquire("synthesis"))
+                .take(if busy { usize::MAX } else { 0 })
+                .collect();
+
+            let result = admission
+                .admit::<String>(&node, &message, "search")
+                .map(|(_, slot)| assert_eq!(slot.task_type(), "search"));
+            assert_eq!(result, expected, "Case: {}", name);
+            drop(taken);
+        }
+        assert_eq!(node.status.in_flight(), 0);
+
+        assert_eq!(
+            admission.rejections().get(&Rejection::NotSelected),
+            Some(&2)
+        );
+        // deferred tasks are not counted as rejected
+        assert_eq!(admission.rejections().values().sum::<usize>(), 7);
+        assert_eq!(admission.rejections().get(&Rejection::Capacity), None);
+    }
+}
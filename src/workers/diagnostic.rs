@@ -6,7 +6,7 @@ use crate::node::DriaComputeNode;
 /// # Diagnostic Worker
 ///
 /// This is a simple worker that keeps track of the node information, and prints it at regular intervals.
-/// In particular, it will print the number of peers.
+/// In particular, it will print the number of peers, and the number of unanswered heartbeats.
 pub fn diagnostic_worker(
     node: Arc<DriaComputeNode>,
     sleep_amount: Duration,
@@ -17,6 +17,11 @@ pub fn diagnostic_worker(
                 _ = node.cancellation.cancelled() => break,
                 _ = tokio::time::sleep(sleep_amount) => {
 
+                    let (dropped, replayed) = (node.heartbeats.dropped(), node.heartbeats.replayed());
+                    if dropped + replayed > 0 {
+                        log::info!("Unanswered heartbeats: {} dropped, {} replayed", dropped, replayed);
+                    }
+
                     match node.waku.peers().await {
                         Ok(peers) => {
                             log::info!("Active number of peers: {}", peers.len());
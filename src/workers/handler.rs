@@ -0,0 +1,245 @@
+use async_trait::async_trait;
+use serde::de::DeserializeOwned;
+use std::{collections::VecDeque, sync::Arc};
+use tokio::task::JoinSet;
+use tokio_util::sync::CancellationToken;
+
+use super::admission::{Rejection, TaskAdmission};
+use crate::{
+    compute::payload::TaskRequestPayload,
+    errors::NodeResult,
+    node::DriaComputeNode,
+    utils::get_current_time_nanos,
+    waku::{message::WakuMessage, stream::recv_batch},
+};
+
+/// # Task Handler
+///
+/// The logic of a task type, which is run by [`task_worker`]. The worker takes care of receiving from
+/// the topic, admitting each task with [`TaskAdmission`], running tasks concurrently, and signing,
+/// encrypting and publishing their results.
+///
+/// Tasks that arrive while every slot of the node is taken are kept until a slot frees up, and the
+/// worker stops receiving meanwhile so that the rest wait within the Waku network.
+#[async_trait]
+pub trait TaskHandler: Send + Sync + 'static {
+    /// Name of the topic that the tasks are received from.
+    const TOPIC: &'static str;
+
+    /// Input of a task, within its request payload.
+    type Input: DeserializeOwned + Send + 'static;
+
+    /// Prepares the handler before the topic is subscribed to, e.g. by pulling models.
+    ///
+    /// Failures should be logged here, as the worker runs regardless.
+    async fn setup(&mut self, _cancellation: &CancellationToken) {}
+
+    /// Names of the tools that the tasks can use, advertised in heartbeat responses.
+    fn tools(&self) -> Vec<String> {
+        Vec::new()
+    }
+
+    /// Computes the result of a task, which stops with an error if the token is cancelled.
+    async fn handle(
+        &self,
+        input: Self::Input,
+        cancellation: &CancellationToken,
+    ) -> NodeResult<String>;
+}
+
+/// Spawns a worker that runs the tasks of the given handler as they are received, on the executor
+/// of the node.
+pub fn task_worker<H: TaskHandler>(
+    node: Arc<DriaComputeNode>,
+    mut handler: H,
+) -> tokio::task::JoinHandle<()> {
+    let topic = H::TOPIC;
+
+    tokio::spawn(async move {
+        handler.setup(&node.cancellation).await;
+        let handler = Arc::new(handler);
+
+        // messages before the subscription are recovered from the store, so none is received twice
+        let subscribed_at = get_current_time_nanos();
+        let Some(mut receiver) = node.subscribe_topic(topic).await else {
+            return;
+        };
+
+        let mut running = JoinSet::new();
+        let mut admission = TaskAdmission::default();
+        let mut waiting = VecDeque::new();
+        match node.recover_messages(topic, subscribed_at).await {
+            Ok(messages) if !messages.is_empty() => {
+                log::info!("Recovered {} {} tasks.", messages.len(), topic);
+                spawn_tasks(
+                    &node,
+                    &handler,
+                    &mut admission,
+                    &mut running,
+                    &mut waiting,
+                    messages,
+                );
+            }
+            Ok(_) => {}
+            Err(e) => log::warn!("Could not recover missed {} tasks: {}", topic, e),
+        }
+
+        loop {
+            tokio::select! {
+                _ = node.cancellation.cancelled() => {
+                    if let Err(e) = node.unsubscribe_topic(topic).await {
+                        log::error!("Error unsubscribing from {}: {}\nContinuing anyway.", topic, e);
+                    }
+                    break;
+                }
+                Some(result) = running.join_next(), if !running.is_empty() => {
+                    if let Err(e) = result {
+                        log::error!("A {} task panicked: {}", topic, e);
+                    }
+                }
+                // the slot may be taken by another worker by the time the tasks are admitted again
+                slot = node.status.acquire(topic), if !waiting.is_empty() => {
+                    drop(slot);
+                    let messages = waiting.drain(..).collect();
+                    spawn_tasks(&node, &handler, &mut admission, &mut running, &mut waiting, messages);
+                }
+                messages = recv_batch(&mut receiver), if waiting.is_empty() => {
+                    let Some(messages) = messages else {
+                        log::warn!("Stopped receiving messages on {}", topic);
+                        break;
+                    };
+                    // signatures are checked along with the rest of the admission
+                    let messages = node.process_messages(topic, messages, false);
+                    log::info!("Received {} {} tasks.", messages.len(), topic);
+
+                    spawn_tasks(&node, &handler, &mut admission, &mut running, &mut waiting, messages);
+                }
+            }
+        }
+
+        // running tasks are cancelled along with the node, so they finish shortly
+        while running.join_next().await.is_some() {}
+    })
+}
+
+/// Runs the tasks within the messages concurrently until their deadlines, each in a free slot of
+/// the node. Tasks that find no free slot are added to `waiting`, and are dropped once admitted
+/// again after their deadline.
+fn spawn_tasks<H: TaskHandler>(
+    node: &Arc<DriaComputeNode>,
+    handler: &Arc<H>,
+    admission: &mut TaskAdmission,
+    running: &mut JoinSet<Option<()>>,
+    waiting: &mut VecDeque<WakuMessage>,
+    messages: Vec<WakuMessage>,
+) {
+    let topic = H::TOPIC;
+    for message in messages {
+        let (task, slot) = match admission.admit::<H::Input>(node, &message, topic) {
+            Ok(admitted) => admitted,
+            Err(Rejection::Capacity) => {
+                waiting.push_back(message);
+                continue;
+            }
+            Err(_) => continue,
+        };
+
+        let (task_node, task_handler) = (node.clone(), handler.clone());
+        node.executor.spawn_in_slot(
+            running,
+            slot,
+            task.task_id.clone(),
+            task.deadline,
+            &node.cancellation,
+            move |cancellation| async move {
+                let task_id = task.task_id.clone();
+                if let Err(e) =
+                    run_task(&task_node, task_handler.as_ref(), task, &cancellation).await
+                {
+                    log::error!("Error handling {} task {}: {}", topic, task_id, e);
+                }
+            },
+        );
+    }
+}
+
+/// Runs the handler for a task, and sends its signed and encrypted result to the Waku network.
+async fn run_task<H: TaskHandler>(
+    node: &DriaComputeNode,
+    handler: &H,
+    task: TaskRequestPayload<H::Input>,
+    cancellation: &CancellationToken,
+) -> NodeResult<()> {
+    let task_public_key = hex::decode(&task.public_key)?;
+    let result = handler.handle(task.input, cancellation).await?;
+
+    // create h||s||e payload
+    let payload = node.create_payload(result, &task_public_key)?;
+    let payload_str = payload.to_string()?;
+
+    // send result to Waku network
+    let message = WakuMessage::new(payload_str, &task.task_id);
+    node.send_message_once(message).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::workers::admission::tests::{filter_of, task_message, ADMIN_SECRET_KEY};
+
+    struct EchoHandler;
+
+    #[async_trait]
+    impl TaskHandler for EchoHandler {
+        const TOPIC: &'static str = "echo";
+        type Input = String;
+
+        async fn handle(&self, input: String, _: &CancellationToken) -> NodeResult<String> {
+            Ok(input)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_tasks_wait_for_slots() {
+        let node = Arc::new(DriaComputeNode::default());
+        let handler = Arc::new(EchoHandler);
+        let mut admission = TaskAdmission::default();
+        let (mut running, mut waiting) = (JoinSet::new(), VecDeque::new());
+
+        let deadline = get_current_time_nanos() + 60_000_000_000;
+        let filter = filter_of(&[&node.address()]);
+        let messages = vec![
+            task_message(ADMIN_SECRET_KEY, "first", deadline, filter.clone()),
+            task_message(ADMIN_SECRET_KEY, "second", deadline, filter),
+        ];
+
+        // every slot is taken by another worker
+        let taken: Vec<_> = std::iter::from_fn(|| node.status.try_acquire("search")).collect();
+        spawn_tasks(
+            &node,
+            &handler,
+            &mut admission,
+            &mut running,
+            &mut waiting,
+            messages,
+        );
+        assert!(running.is_empty());
+        assert_eq!(waiting.len(), 2);
+
+        // the tasks are admitted once the slots free up
+        drop(taken);
+        let messages = waiting.drain(..).collect();
+        spawn_tasks(
+            &node,
+            &handler,
+            &mut admission,
+            &mut running,
+            &mut waiting,
+            messages,
+        );
+        assert!(waiting.is_empty());
+        assert_eq!(running.len(), 2);
+        assert!(admission.rejections().is_empty());
+        node.cancellation.cancel();
+    }
+}
@@ -1,7 +1,15 @@
-use std::time::Duration;
 use std::sync::Arc;
 
-use crate::{node::DriaComputeNode, utils::crypto::sha256hash, waku::message::WakuMessage};
+use crate::{
+    compute::ollama::OllamaClient,
+    errors::NodeResult,
+    node::DriaComputeNode,
+    utils::{
+        capabilities::Capabilities, crypto::sha256hash, get_current_time_nanos, recent::RecentIds,
+        status::StatusSnapshot,
+    },
+    waku::{message::WakuMessage, stream::recv_batch},
+};
 
 use serde::{Deserialize, Serialize};
 
@@ -10,19 +18,77 @@ use serde::{Deserialize, Serialize};
 /// A heartbeat is a message sent by a node to indicate that it is alive. Dria nodes request
 /// a heartbeat with a unique identifier, and the requester node will sign the identifier and send the signature back to a topic
 /// identified with the `uuid`.
+///
+/// The `deadline` is given in seconds since the Unix epoch, unlike task deadlines which are in
+/// nanoseconds.
 #[derive(Serialize, Deserialize, Debug, Clone)]
 struct HeartbeatPayload {
     uuid: String,
     deadline: u128,
 }
 
+impl HeartbeatPayload {
+    /// Returns the deadline in nanoseconds since the Unix epoch.
+    #[inline]
+    fn deadline_nanos(&self) -> u128 {
+        self.deadline.saturating_mul(1_000_000_000)
+    }
+}
+
+/// Number of answered heartbeats that are remembered to reject replays.
+///
+/// Heartbeats have short deadlines, so a replay of a forgotten uuid is dropped as expired anyway.
+const MAX_SEEN_HEARTBEATS: usize = 1024;
+
+/// # Heartbeat Response
+///
+/// The body of a heartbeat reply, which carries the remaining capacity and the capabilities of
+/// the node.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+struct HeartbeatResponse {
+    uuid: String,
+    capacity: StatusSnapshot,
+    capabilities: Capabilities,
+}
+
+/// Creates the reply to a heartbeat, sent to the topic identified with its `uuid`, with the given
+/// Ollama models.
+///
+/// The payload starts with the signature over the SHA256 digest of the `uuid`, as older admins
+/// expect it. It is followed by a signed envelope of the [`HeartbeatResponse`], i.e. the signature
+/// over the SHA256 digest of the JSON body and then the body, the same as the messages of the admin
+/// node.
+fn heartbeat_reply(
+    node: &DriaComputeNode,
+    uuid: &str,
+    models: Vec<String>,
+) -> NodeResult<WakuMessage> {
+    let uuid_signature = node.sign_bytes(&sha256hash(uuid.as_bytes()));
+    let body = serde_json::to_string(&HeartbeatResponse {
+        uuid: uuid.to_string(),
+        capacity: node.status.snapshot(),
+        capabilities: node.capabilities(models),
+    })?;
+    let body_signature = node.sign_bytes(&sha256hash(body.as_bytes()));
+
+    Ok(WakuMessage::new(
+        format!("{}{}{}", uuid_signature, body_signature, body),
+        uuid,
+    ))
+}
+
+/// Spawns a worker that answers the heartbeats on the topic, advertising the models of the given
+/// Ollama server.
 pub fn heartbeat_worker(
     node: Arc<DriaComputeNode>,
     topic: &'static str,
-    sleep_amount: Duration,
+    ollama: OllamaClient,
 ) -> tokio::task::JoinHandle<()> {
     tokio::spawn(async move {
-        node.subscribe_topic(topic).await;
+        let mut seen = RecentIds::new(MAX_SEEN_HEARTBEATS);
+        let Some(mut receiver) = node.subscribe_topic(topic).await else {
+            return;
+        };
 
         loop {
             tokio::select! {
@@ -32,68 +98,125 @@ pub fn heartbeat_worker(
                     }
                     break;
                 }
-                _ = tokio::time::sleep(sleep_amount) => {
-                    let messages = match node.process_topic(topic, true).await {
-                        Ok(messages) => messages,
-                        Err(e) => {
-                            log::error!("Error processing topic {}: {}", topic, e);
-                            continue;
-                        }
+                messages = recv_batch(&mut receiver) => {
+                    let Some(messages) = messages else {
+                        log::warn!("Stopped receiving messages on {}", topic);
+                        break;
                     };
+                    let messages = node.process_messages(topic, messages, true);
 
-                    if node.is_busy() {
-                        log::debug!("Node is busy, skipping heartbeat.");
+                    let uuids = messages
+                        .iter()
+                        .filter_map(|message| admit_heartbeat(&node, &seen, message))
+                        .collect::<Vec<_>>();
+                    if uuids.is_empty() {
                         continue;
                     }
 
-                    // we only care about the latest heartbeat
-                    if let Some(message) = messages.last() {
-                        log::info!("Received: {}", message);
-
-                        let message = match message
-                        .parse_payload::<HeartbeatPayload>(true) {
-                            Ok(body) => {
-                                let uuid = body.uuid;
-                                let signature = node.sign_bytes(&sha256hash(uuid.as_bytes()));
-                                WakuMessage::new(signature, &uuid)
-                            }
-                            Err(e) => {
-                                log::error!("Error parsing payload: {}", e);
-                                continue;
-                            }
-                        };
-
-
-                        // send message
-                        if let Err(e) = node.send_message_once(message).await {
-                            log::error!("Error sending message: {}", e);
-                        }
+                    // a node without Ollama still responds, with no models
+                    let models = ollama.local_models().await.unwrap_or_else(|e| {
+                        log::warn!("Could not list Ollama models: {}", e);
+                        Vec::new()
+                    });
 
+                    for uuid in uuids {
+                        answer_heartbeat(&node, &mut seen, &uuid, &models).await;
                     }
-
-
-
                 }
             }
         }
     })
 }
 
+/// Sends the reply to a heartbeat, with the capabilities of the node.
+///
+/// The uuid is recorded as answered only once the reply is sent, so that the heartbeat is answered
+/// if it is broadcast again after a failure.
+async fn answer_heartbeat(
+    node: &DriaComputeNode,
+    seen: &mut RecentIds,
+    uuid: &str,
+    models: &[String],
+) {
+    // the same heartbeat may be received more than once within a batch
+    if seen.contains(uuid) {
+        node.heartbeats.record_replayed();
+        return;
+    }
+
+    let message = match heartbeat_reply(node, uuid, models.to_vec()) {
+        Ok(message) => message,
+        Err(e) => {
+            log::error!("Error creating heartbeat reply: {}", e);
+            return;
+        }
+    };
+    if let Err(e) = node.send_message_once(message).await {
+        log::error!("Error sending heartbeat reply: {}", e);
+        return;
+    }
+    seen.insert(uuid);
+}
+
+/// Parses the heartbeat within the message, and returns its uuid if it should be answered, i.e.
+/// it is before its deadline and has not been answered already.
+fn admit_heartbeat(
+    node: &DriaComputeNode,
+    seen: &RecentIds,
+    message: &WakuMessage,
+) -> Option<String> {
+    log::info!("Received: {}", message);
+
+    let heartbeat = match message.parse_payload::<HeartbeatPayload>(true) {
+        Ok(heartbeat) => heartbeat,
+        Err(e) => {
+            log::error!("Error parsing payload: {}", e);
+            node.heartbeats.record_dropped();
+            return None;
+        }
+    };
+
+    if get_current_time_nanos() >= heartbeat.deadline_nanos() {
+        log::debug!("Skipping heartbeat {} due to deadline.", heartbeat.uuid);
+        node.heartbeats.record_dropped();
+        return None;
+    }
+
+    if seen.contains(&heartbeat.uuid) {
+        log::warn!(
+            "Skipping heartbeat {} as it was already answered.",
+            heartbeat.uuid
+        );
+        node.heartbeats.record_replayed();
+        return None;
+    }
+
+    Some(heartbeat.uuid)
+}
+
 #[cfg(test)]
 mod tests {
     use crate::{
-        config::DEFAULT_DKN_ADMIN_PUBLIC_KEY,
+        config::{DriaComputeNodeConfig, DEFAULT_DKN_ADMIN_PUBLIC_KEY},
         node::DriaComputeNode,
         utils::{
             crypto::{sha256hash, to_address},
             filter::FilterPayload,
         },
-        waku::message::WakuMessage,
+        waku::{message::WakuMessage, WakuClient},
     };
     use fastbloom_rs::{FilterBuilder, Membership};
-    use libsecp256k1::{recover, Message, PublicKey};
+    use libsecp256k1::{recover, Message, PublicKey, RecoveryId, Signature};
+    use tokio_util::sync::CancellationToken;
 
-    use super::HeartbeatPayload;
+    use super::{
+        admit_heartbeat, answer_heartbeat, heartbeat_reply, HeartbeatPayload, HeartbeatResponse,
+        RecentIds,
+    };
+    use crate::{
+        utils::get_current_time_nanos,
+        waku::envelope::{SignedEnvelope, SIGNATURE_SIZE},
+    };
 
     #[test]
     fn test_heartbeat_payload() {
@@ -150,4 +273,133 @@ mod tests {
             "Node should be tasked"
         );
     }
+
+    #[test]
+    fn test_heartbeat_reply() {
+        let node = DriaComputeNode::default();
+        node.task_types
+            .insert("search", vec!["DDG Searcher".to_string()]);
+        let uuid = "81a63a34-96c6-4e5a-99b5-6b274d9de175";
+        let reply =
+            heartbeat_reply(&node, uuid, vec!["phi3".to_string()]).expect("Should create reply");
+        assert_eq!(reply.content_topic, WakuMessage::create_content_topic(uuid));
+
+        // an older admin reads the leading signature over the uuid
+        let payload = reply.decode_payload().expect("Should decode");
+        let (uuid_signature, envelope) = payload.split_at(SIGNATURE_SIZE);
+        let signature = hex::decode(uuid_signature).expect("Should be hex");
+        let recovered_public_key = recover(
+            &Message::parse(&sha256hash(uuid.as_bytes())),
+            &Signature::parse_standard_slice(&signature[..64]).expect("Should parse signature"),
+            &RecoveryId::parse(signature[64]).expect("Should parse recovery id"),
+        )
+        .expect("Should recover");
+        assert_eq!(recovered_public_key, node.config.DKN_WALLET_PUBLIC_KEY);
+
+        // followed by the capabilities as a signed envelope of the node
+        let envelope = SignedEnvelope::parse(envelope).expect("Should parse envelope");
+        assert!(envelope.is_signed_by(&node.config.DKN_WALLET_PUBLIC_KEY));
+        let response: HeartbeatResponse =
+            serde_json::from_slice(envelope.body()).expect("Should parse response");
+        assert_eq!(response.uuid, uuid);
+
+        let capabilities = response.capabilities;
+        assert_eq!(capabilities.version, env!("CARGO_PKG_VERSION"));
+        assert_eq!(capabilities.models, vec!["phi3"]);
+        assert_eq!(capabilities.tasks["search"], vec!["DDG Searcher"]);
+        assert_eq!(
+            capabilities.features.contains(&"search".to_string()),
+            cfg!(feature = "search")
+        );
+    }
+
+    #[test]
+    fn test_heartbeat_reply_capacity() {
+        let node = DriaComputeNode::default();
+        let _guard = node.status.try_acquire("search").expect("Should acquire");
+        let reply = heartbeat_reply(&node, "uuid", Vec::new()).expect("Should create reply");
+
+        // a busy node still replies, with its remaining capacity
+        let payload = reply.decode_payload().expect("Should decode");
+        let envelope =
+            SignedEnvelope::parse(&payload[SIGNATURE_SIZE..]).expect("Should parse envelope");
+        let response: HeartbeatResponse =
+            serde_json::from_slice(envelope.body()).expect("Should parse response");
+        assert_eq!(response.capacity.capacity, node.status.capacity());
+        assert_eq!(response.capacity.remaining, node.status.capacity() - 1);
+        assert_eq!(response.capacity.in_flight["search"], 1);
+    }
+
+    /// Creates a heartbeat message, signed by the node itself.
+    fn heartbeat_message(node: &DriaComputeNode, uuid: &str, deadline: u128) -> WakuMessage {
+        let payload = serde_json::to_string(&HeartbeatPayload {
+            uuid: uuid.to_string(),
+            deadline,
+        })
+        .expect("Should serialize");
+        let signature = node.sign_bytes(&sha256hash(payload.as_bytes()));
+        WakuMessage::new(format!("{}{}", signature, payload), "heartbeat")
+    }
+
+    #[test]
+    fn test_admit_heartbeat() {
+        let node = DriaComputeNode::default();
+        let mut seen = RecentIds::new(2);
+        let deadline = get_current_time_nanos() / 1_000_000_000 + 60;
+
+        // the fixture deadline is in seconds, and has passed
+        let expired = heartbeat_message(&node, "expired", 1714128792);
+        assert_eq!(admit_heartbeat(&node, &seen, &expired), None);
+        let passed = heartbeat_message(&node, "passed", deadline - 61);
+        assert_eq!(admit_heartbeat(&node, &seen, &passed), None);
+        assert_eq!(node.heartbeats.dropped(), 2);
+
+        let fresh = heartbeat_message(&node, "fresh", deadline);
+        assert_eq!(
+            admit_heartbeat(&node, &seen, &fresh),
+            Some("fresh".to_string())
+        );
+
+        // answered heartbeats are not answered again
+        seen.insert("fresh");
+        assert_eq!(admit_heartbeat(&node, &seen, &fresh), None);
+        assert_eq!(node.heartbeats.replayed(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_answer_heartbeat() {
+        let mut server = mockito::Server::new_async().await;
+        let mut node =
+            DriaComputeNode::new(DriaComputeNodeConfig::default(), CancellationToken::new());
+        node.waku = WakuClient::new(Some(server.url()));
+        let mut seen = RecentIds::new(2);
+
+        // the uuid is not recorded if the reply could not be sent
+        let failing = server
+            .mock("POST", mockito::Matcher::Any)
+            .with_status(503)
+            .create_async()
+            .await;
+        answer_heartbeat(&node, &mut seen, "uuid", &[]).await;
+        assert!(!seen.contains("uuid"));
+        failing.remove_async().await;
+
+        // so that the heartbeat is answered once it is broadcast again
+        let posts = server
+            .mock("POST", mockito::Matcher::Any)
+            .expect(2)
+            .create_async()
+            .await;
+        server
+            .mock("DELETE", mockito::Matcher::Any)
+            .create_async()
+            .await;
+        answer_heartbeat(&node, &mut seen, "uuid", &[]).await;
+        assert!(seen.contains("uuid"));
+
+        // but only once
+        answer_heartbeat(&node, &mut seen, "uuid", &[]).await;
+        assert_eq!(node.heartbeats.replayed(), 1);
+        posts.assert_async().await;
+    }
 }
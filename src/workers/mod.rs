@@ -1,4 +1,9 @@
+pub mod admission;
 pub mod diagnostic;
+pub mod handler;
 pub mod heartbeat;
+pub mod registry;
+#[cfg(feature = "search")]
+pub mod search;
+#[cfg(feature = "synthesis")]
 pub mod synthesis;
-pub mod search;
\ No newline at end of file
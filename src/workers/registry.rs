@@ -0,0 +1,123 @@
+use std::sync::Arc;
+use tokio_util::task::TaskTracker;
+
+use super::handler::{task_worker, TaskHandler};
+use crate::{
+    config::WorkerConfig,
+    errors::{NodeError, NodeResult},
+    node::DriaComputeNode,
+};
+
+type SpawnFn = Box<dyn FnOnce(Arc<DriaComputeNode>) -> tokio::task::JoinHandle<()> + Send>;
+
+struct Worker {
+    topic: &'static str,
+    tools: Vec<String>,
+    spawn: SpawnFn,
+}
+
+/// # Worker Registry
+///
+/// The task handlers of the node, each of which is run by its own worker once spawned.
+#[derive(Default)]
+pub struct WorkerRegistry {
+    workers: Vec<Worker>,
+}
+
+impl WorkerRegistry {
+    /// Creates an empty registry.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a registry with the handler of every task type enabled by the crate features, each
+    /// with the given config.
+    #[allow(unused_variables)]
+    pub fn new_from_features(config: &WorkerConfig) -> NodeResult<Self> {
+        #[allow(unused_mut)]
+        let mut registry = Self::new();
+
+        #[cfg(feature = "synthesis")]
+        registry.register(super::synthesis::SynthesisHandler::new(config))?;
+        #[cfg(feature = "search")]
+        registry.register(super::search::SearchHandler::new(config)?)?;
+
+        Ok(registry)
+    }
+
+    /// Registers a handler, where each topic can have a single handler.
+    pub fn register<H: TaskHandler>(&mut self, handler: H) -> NodeResult<&mut Self> {
+        if self.workers.iter().any(|worker| worker.topic == H::TOPIC) {
+            return Err(NodeError::Config(format!(
+                "A handler for {} is already registered.",
+                H::TOPIC
+            )));
+        }
+
+        self.workers.push(Worker {
+            topic: H::TOPIC,
+            tools: handler.tools(),
+            spawn: Box::new(move |node| task_worker(node, handler)),
+        });
+        Ok(self)
+    }
+
+    /// Returns the topics of the registered handlers, in order of registration.
+    pub fn topics(&self) -> Vec<&'static str> {
+        self.workers.iter().map(|worker| worker.topic).collect()
+    }
+
+    /// Spawns a worker for each handler within the tracker, and records its task type at the node.
+    pub fn spawn(self, node: &Arc<DriaComputeNode>, tracker: &TaskTracker) {
+        for worker in self.workers {
+            log::info!("Starting {} worker", worker.topic);
+            node.task_types.insert(worker.topic, worker.tools);
+            tracker.spawn((worker.spawn)(node.clone()));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_trait::async_trait;
+    use tokio_util::sync::CancellationToken;
+
+    struct EchoHandler;
+
+    #[async_trait]
+    impl TaskHandler for EchoHandler {
+        const TOPIC: &'static str = "echo";
+        type Input = String;
+
+        async fn handle(&self, input: String, _: &CancellationToken) -> NodeResult<String> {
+            Ok(input)
+        }
+    }
+
+    struct ReverseHandler;
+
+    #[async_trait]
+    impl TaskHandler for ReverseHandler {
+        const TOPIC: &'static str = "reverse";
+        type Input = String;
+
+        async fn handle(&self, input: String, _: &CancellationToken) -> NodeResult<String> {
+            Ok(input.chars().rev().collect())
+        }
+    }
+
+    #[test]
+    fn test_registry() {
+        let mut registry = WorkerRegistry::new();
+        registry
+            .register(EchoHandler)
+            .expect("Should register")
+            .register(ReverseHandler)
+            .expect("Should register");
+        assert_eq!(registry.topics(), vec!["echo", "reverse"]);
+
+        assert!(registry.register(EchoHandler).is_err());
+        assert_eq!(registry.topics().len(), 2);
+    }
+}
@@ -1,192 +1,129 @@
-use std::env;
-use std::time::Duration;
-use std::sync::Arc;
-use serde_json::Value;
-
-use langchain_rust::{
-    agent::{AgentExecutor, OpenAiToolAgentBuilder},
-    chain::{options::ChainCallOptions, Chain},
-    llm::openai::OpenAI,
-    llm::OpenAIConfig,
-    memory::SimpleMemory,
-    prompt_args,
-    tools::{CommandExecutor, Tool},
-};
+use async_trait::async_trait;
+use langchain_rust::{embedding::embedder_trait::Embedder, tools::Tool};
+use std::{path::PathBuf, sync::Arc};
+use tokio_util::sync::CancellationToken;
 
+use super::handler::TaskHandler;
 use crate::{
-    compute::{ollama::OllamaClient, payload::TaskRequestPayload},
-    compute::search::tools::{StockScraper, Scraper, DDGSearcher},
-    node::DriaComputeNode,
-    utils::get_current_time_nanos,
-    waku::message::WakuMessage,
-};
-
-use crate::compute::constants::{
-    DEFAULT_DKN_OLLAMA_HOST, DEFAULT_DKN_OLLAMA_MODEL, DEFAULT_DKN_OLLAMA_PORT,
+    compute::ollama::OllamaClient,
+    compute::search::{
+        agent::Agent,
+        config::PersonaPool,
+        tools::{DDGSearcher, Scraper, StockScraper},
+        utils::{
+            cache::ContentCache,
+            fetcher::{fetcher_from_config, CachedFetcher},
+            provider::provider_from_config,
+        },
+        vectorstore::Embeddings,
+    },
+    config::WorkerConfig,
+    errors::NodeResult,
 };
 
-
-/// # Search Payload
+/// # Search Handler
 ///
-/// A synthesis task is the task of putting a prompt to an LLM and obtaining many results, essentially growing the number of data points in a dataset,
-/// hence creating synthetic data.
-type SearchPayload = TaskRequestPayload<String>;
-
-pub fn search_worker(
-    node: Arc<DriaComputeNode>,
-    topic: &'static str,
-    tools: (Arc<Scraper>, Arc<StockScraper>, Arc<DDGSearcher>),
-    sleep_amount: Duration,
-) -> tokio::task::JoinHandle<()> {
-
-    let ollama = OllamaClient::new(None, None, None);
-
-    let host =  env::var("DKN_OLLAMA_HOST").unwrap_or(DEFAULT_DKN_OLLAMA_HOST.to_string());
+/// A search task is the task of answering a question by searching the web and reading the results,
+/// where the input is the question itself.
+pub struct SearchHandler {
+    ollama: OllamaClient,
+    tools: Vec<Arc<dyn Tool>>,
+    personas: PersonaPool,
+    /// Picks the same persona for every task if set, so that runs are reproducible.
+    persona_seed: Option<u64>,
+    /// Directory of prompts that override the built-in prompts.
+    prompts_dir: Option<PathBuf>,
+    cache: Arc<ContentCache>,
+    embeddings: Arc<Embeddings>,
+    /// Set once the embedding model is available.
+    embedder: Option<Arc<dyn Embedder>>,
+}
 
-    let port = env::var("DKN_OLLAMA_PORT")
-        .and_then(|port_str| {
-            port_str
-                .parse::<u16>()
-                .map_err(|_| env::VarError::NotPresent)
+impl SearchHandler {
+    /// Creates the handler with the configured tools, where pages and search results are shared
+    /// across tasks through the cache.
+    pub fn new(config: &WorkerConfig) -> NodeResult<Self> {
+        let search = &config.search;
+        let cache = Arc::new(ContentCache::new(search.cache.clone()));
+        let policy = search.policy.clone();
+        let provider = provider_from_config(&search.providers, &policy)?;
+        let fetcher = Arc::new(CachedFetcher::new(
+            fetcher_from_config(&search.fetcher, &policy)?,
+            cache.clone(),
+            policy.clone(),
+        ));
+        let tools: Vec<Arc<dyn Tool>> = vec![
+            Arc::new(Scraper::new(fetcher)),
+            Arc::new(StockScraper::new(policy)?.with_cache(cache.clone())),
+            Arc::new(DDGSearcher::new(provider).with_cache(cache.clone())),
+        ];
+
+        Ok(Self {
+            ollama: OllamaClient::new_from_config(&config.ollama),
+            tools,
+            personas: search.personas.clone(),
+            persona_seed: None,
+            prompts_dir: search.prompts_dir.clone(),
+            cache,
+            embeddings: Arc::new(Embeddings::new_from_config(
+                &config.ollama,
+                &search.embedding_model,
+            )),
+            embedder: None,
         })
-        .unwrap_or(DEFAULT_DKN_OLLAMA_PORT);
-
-    let ollama_url = format!("http://{}:{}/v1", host, port);
+    }
 
-    let llm = OpenAI::default()
-        .with_config(
-            OpenAIConfig::default()
-                .with_api_base(ollama_url)
-                .with_api_key("ollama"),
-        )
-        .with_model(env::var("DKN_OLLAMA_MODEL").unwrap_or("llama3".to_string()));
-
-    let memory = SimpleMemory::new();
-
-    //let command_executor = CommandExecutor::default();
-    let agent = OpenAiToolAgentBuilder::new()
-        .tools(&[
-            tools.0.clone(),
-            tools.1.clone(),
-            tools.2.clone(),
-        ])
-        .options(ChainCallOptions::new().with_max_tokens(4000))
-        .build(llm)
-        .unwrap();
+    /// Picks personas from the given seed instead of at random.
+    pub fn with_persona_seed(mut self, seed: u64) -> Self {
+        self.persona_seed = Some(seed);
+        self
+    }
+}
 
-    let executor = AgentExecutor::from_agent(agent).with_memory(memory.into());
+#[async_trait]
+impl TaskHandler for SearchHandler {
+    const TOPIC: &'static str = "search";
+    type Input = String;
 
-    tokio::spawn(async move {
-        if let Err(e) = ollama.setup(node.cancellation.clone()).await {
+    async fn setup(&mut self, cancellation: &CancellationToken) {
+        if let Err(e) = self.ollama.setup(cancellation.clone()).await {
             log::error!("Could not setup Ollama: {}", e);
         }
 
-        node.subscribe_topic(topic).await;
-
-        loop {
-            tokio::select! {
-                _ = node.cancellation.cancelled() => {
-                    if let Err(e) = node.unsubscribe_topic(topic).await {
-                        log::error!("Error unsubscribing from {}: {}\nContinuing anyway.", topic, e);
-                    }
-                    break;
-                }
-                _ = tokio::time::sleep(sleep_amount) => {
-                    let mut tasks = Vec::new();
-                    if let Ok(messages) = node.process_topic(topic, true).await {
-                        if messages.is_empty() {
-                            continue;
-                        }
-                        log::info!("Received {} synthesis tasks.", messages.len());
-
-                        for message in messages {
-                            match message.parse_payload::<SearchPayload>(true) {
-                                Ok(task) => {
-                                    // check deadline
-                                    if get_current_time_nanos() >= task.deadline {
-                                        log::debug!("{}", format!("Skipping {} due to deadline.", task.task_id));
-                                        continue;
-                                    }
-
-                                    // check task inclusion
-                                    match node.is_tasked(&task.filter) {
-                                        Ok(is_tasked) => {
-                                            if is_tasked {
-                                                log::debug!("{}", format!("Skipping {} due to filter.", task.task_id));
-                                                continue;
-                                            }
-                                        },
-                                        Err(e) => {
-                                            log::error!("Error checking task inclusion: {}", e);
-                                            continue;
-                                        }
-                                    }
-
-                                    tasks.push(task);
-                                },
-                                Err(e) => {
-                                    log::error!("Error parsing payload: {}", e);
-                                    continue;
-                                }
-                            }
-                        }
-                    }
-                    // Set node to busy
-                    node.set_busy(true);
-
-                    for task in tasks {
-                        // parse public key
-                        let task_public_key = match hex::decode(&task.public_key) {
-                            Ok(public_key) => public_key,
-                            Err(e) => {
-                                log::error!("Error parsing public key: {}", e);
-                                continue;
-                            }
-                        };
-
-                        let input_variables = prompt_args! {
-                                "input" => &task.input,
-                            };
-
-                        let search_result = match executor.invoke(input_variables).await {
-                                Ok(result) => result.replace("\n", " "),
-                                Err(e) => {
-                                log::error!("Error invoking LLMChain: {:?}", e);
-                                continue
-                                }
-                            };
-
-                        // create h||s||e payload
-                        let payload = match node.create_payload(search_result, &task_public_key) {
-                            Ok(payload) => payload,
-                            Err(e) => {
-                                log::error!("Error creating payload: {}", e);
-                                continue;
-                            }
-                        };
-
-                        // stringify payload
-                        let payload_str = match payload.to_string() {
-                            Ok(payload_str) => payload_str,
-                            Err(e) => {
-                                log::error!("Error stringifying payload: {}", e);
-                                continue;
-                            }
-                        };
+        // scraped pages are indexed only if the embedding model is available
+        let ollama = self.ollama.with_model(self.embeddings.model());
+        match ollama.setup(cancellation.clone()).await {
+            Ok(_) => self.embedder = Some(self.embeddings.clone()),
+            Err(e) => log::error!(
+                "Could not setup embedding model: {}\nScraped pages will not be indexed.",
+                e
+            ),
+        }
+    }
+
+    fn tools(&self) -> Vec<String> {
+        self.tools.iter().map(|tool| tool.name()).collect()
+    }
+
+    async fn handle(&self, input: String, cancellation: &CancellationToken) -> NodeResult<String> {
+        // run the agent with a random persona, unless a seed is set
+        let mut agent = Agent::new_from_pool(
+            self.ollama.clone(),
+            self.tools.clone(),
+            &self.personas,
+            self.persona_seed,
+        );
+        if let Some(embedder) = &self.embedder {
+            agent = agent.with_embedder(embedder.clone());
+        }
+        if let Some(dir) = &self.prompts_dir {
+            agent = agent.with_prompts_dir(dir.clone());
+        }
 
-                        // send result to Waku network
-                        let message = WakuMessage::new(payload_str, &task.task_id);
-                        if let Err(e) = node.send_message_once(message)
-                            .await {
-                                log::error!("Error sending message: {}", e);
-                                continue;
-                            }
-                    }
+        let output = agent.run(&input, cancellation).await;
+        log::debug!("Search cache: {}", self.cache.stats());
 
-                    // Set node to not busy
-                    node.set_busy(false);
-                }
-            }
-        }
-    })
+        // the result is the answer along with its citations and the tool trace, as JSON
+        Ok(serde_json::to_string(&output?)?)
+    }
 }
@@ -1,134 +1,98 @@
-use std::time::Duration;
-use std::sync::Arc;
+use async_trait::async_trait;
+use std::path::PathBuf;
+use tokio_util::sync::CancellationToken;
 
+use super::handler::TaskHandler;
 use crate::{
-    compute::{ollama::OllamaClient, payload::TaskRequestPayload},
-    node::DriaComputeNode,
-    utils::get_current_time_nanos,
-    waku::message::WakuMessage,
+    compute::{
+        ollama::OllamaClient,
+        search::{config::PersonaPool, utils::prompt::create_synthesis_prompt},
+    },
+    config::WorkerConfig,
+    errors::NodeResult,
 };
 
-/// # Synthesis Payload
+/// # Synthesis Handler
 ///
 /// A synthesis task is the task of putting a prompt to an LLM and obtaining many results, essentially growing the number of data points in a dataset,
 /// hence creating synthetic data.
-type SynthesisPayload = TaskRequestPayload<String>;
-
-pub fn synthesis_worker(
-    node: Arc<DriaComputeNode>,
-    topic: &'static str,
-    sleep_amount: Duration,
-) -> tokio::task::JoinHandle<()> {
-    let ollama = OllamaClient::new(None, None, None);
+///
+/// The task is put to the LLM within the synthesis prompt, as written by a persona picked at random.
+pub struct SynthesisHandler {
+    ollama: OllamaClient,
+    personas: PersonaPool,
+    /// Directory of prompts that override the built-in prompts.
+    prompts_dir: Option<PathBuf>,
+}
 
-    tokio::spawn(async move {
-        if let Err(e) = ollama.setup(node.cancellation.clone()).await {
-            log::error!("Could not setup Ollama: {}", e);
+impl SynthesisHandler {
+    /// Creates the handler with the configured Ollama client, personas and prompts.
+    pub fn new(config: &WorkerConfig) -> Self {
+        Self {
+            ollama: OllamaClient::new_from_config(&config.ollama),
+            personas: config.search.personas.clone(),
+            prompts_dir: config.search.prompts_dir.clone(),
         }
+    }
+}
 
-        node.subscribe_topic(topic).await;
-
-        loop {
-            tokio::select! {
-                _ = node.cancellation.cancelled() => {
-                    if let Err(e) = node.unsubscribe_topic(topic).await {
-                        log::error!("Error unsubscribing from {}: {}\nContinuing anyway.", topic, e);
-                    }
-                    break;
-                }
-                _ = tokio::time::sleep(sleep_amount) => {
-                    let mut tasks = Vec::new();
-                    if let Ok(messages) = node.process_topic(topic, true).await {
-                        if messages.is_empty() {
-                            continue;
-                        }
-                        log::info!("Received {} synthesis tasks.", messages.len());
-
-                        for message in messages {
-                            match message.parse_payload::<SynthesisPayload>(true) {
-                                Ok(task) => {
-                                    // check deadline
-                                    if get_current_time_nanos() >= task.deadline {
-                                        log::debug!("{}", format!("Skipping {} due to deadline.", task.task_id));
-                                        continue;
-                                    }
-
-                                    // check task inclusion
-                                    match node.is_tasked(&task.filter) {
-                                        Ok(is_tasked) => {
-                                            if is_tasked {
-                                                log::debug!("{}", format!("Skipping {} due to filter.", task.task_id));
-                                                continue;
-                                            }
-                                        },
-                                        Err(e) => {
-                                            log::error!("Error checking task inclusion: {}", e);
-                                            continue;
-                                        }
-                                    }
+#[async_trait]
+impl TaskHandler for SynthesisHandler {
+    const TOPIC: &'static str = "synthesis";
+    type Input = String;
 
-                                    tasks.push(task);
-                                },
-                                Err(e) => {
-                                    log::error!("Error parsing payload: {}", e);
-                                    continue;
-                                }
-                            }
-                        }
-                    }
-                    // Set node to busy
-                    node.set_busy(true);
+    async fn setup(&mut self, cancellation: &CancellationToken) {
+        if let Err(e) = self.ollama.setup(cancellation.clone()).await {
+            log::error!("Could not setup Ollama: {}", e);
+        }
+    }
 
-                    for task in tasks {
-                        // parse public key
-                        let task_public_key = match hex::decode(&task.public_key) {
-                            Ok(public_key) => public_key,
-                            Err(e) => {
-                                log::error!("Error parsing public key: {}", e);
-                                continue;
-                            }
-                        };
+    async fn handle(&self, input: String, _: &CancellationToken) -> NodeResult<String> {
+        let persona = self
+            .personas
+            .get_random_agent()
+            .cloned()
+            .unwrap_or_default();
+        let prompt = create_synthesis_prompt(self.prompts_dir.as_deref(), &input, None, &persona);
 
-                        // get prompt result from Ollama
-                        let llm_result = match ollama.generate(task.input).await {
-                            Ok(result) => result,
-                            Err(e) => {
-                                log::error!("Error generating prompt result: {}", e);
-                                continue;
-                            }
-                        };
+        // get prompt result from Ollama
+        let llm_result = self.ollama.generate(prompt).await?;
+        Ok(llm_result.response)
+    }
+}
 
-                        // create h||s||e payload
-                        let payload = match node.create_payload(llm_result.response, &task_public_key) {
-                            Ok(payload) => payload,
-                            Err(e) => {
-                                log::error!("Error creating payload: {}", e);
-                                continue;
-                            }
-                        };
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-                        // stringify payload
-                        let payload_str = match payload.to_string() {
-                            Ok(payload_str) => payload_str,
-                            Err(e) => {
-                                log::error!("Error stringifying payload: {}", e);
-                                continue;
-                            }
-                        };
+    #[tokio::test]
+    async fn test_synthesis_prompt() {
+        let mut server = mockito::Server::new_async().await;
+        let generation = server
+            .mock("POST", "/api/generate")
+            .match_body(mockito::Matcher::Regex(
+                "generating synthetic data for the following task:\\\\nwrite a haiku".to_string(),
+            ))
+            .with_body(r#"{"model": "phi3", "created_at": "2024-05-01T00:00:00Z", "response": "an old silent pond", "done": true}"#)
+            .create_async()
+            .await;
 
-                        // send result to Waku network
-                        let message = WakuMessage::new(payload_str, &task.task_id);
-                        if let Err(e) = node.send_message_once(message)
-                            .await {
-                                log::error!("Error sending message: {}", e);
-                                continue;
-                            }
-                    }
+        let handler = SynthesisHandler {
+            ollama: OllamaClient::new(
+                Some("http://127.0.0.1".to_string()),
+                Some(server.socket_address().port()),
+                Some("phi3".to_string()),
+            ),
+            personas: PersonaPool::default(),
+            prompts_dir: None,
+        };
 
-                    // Set node to not busy
-                    node.set_busy(false);
-                }
-            }
-        }
-    })
+        // the task is given to the model within the synthesis prompt
+        let output = handler
+            .handle("write a haiku".to_string(), &CancellationToken::new())
+            .await
+            .expect("Should generate");
+        assert_eq!(output, "an old silent pond");
+        generation.assert_async().await;
+    }
 }
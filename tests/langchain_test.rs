@@ -26,7 +26,7 @@ mod langchain_test {
 
     #[tokio::test]
     async fn test_scraping_tool(){
-        let scraper = Scraper {};
+        let scraper = Scraper::default();
         let sentences = scraper.run(json!({"website":"http://example.com"})).await.unwrap();
         println!("{}", sentences);
 
@@ -34,7 +34,7 @@ mod langchain_test {
 
     #[tokio::test]
     async fn test_web_search_tool(){
-        let searcher = DDGSearcher::new();
+        let searcher = DDGSearcher::default();
         let res = searcher.search("Who built Llama3?").await.unwrap();
         for result in res {
             println!("{:?}", result);
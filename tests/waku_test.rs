@@ -1,10 +1,19 @@
 #[cfg_attr(test, cfg(feature = "waku_test"))]
 mod waku_tests {
-    use dkn_compute::{node::DriaComputeNode, waku::message::WakuMessage};
+    use dkn_compute::{
+        config::DriaComputeNodeConfig, node::DriaComputeNode, waku::message::WakuMessage,
+    };
+    use tokio_util::sync::CancellationToken;
+
+    /// Creates a node with the config in the environment.
+    fn node() -> DriaComputeNode {
+        let config = DriaComputeNodeConfig::new_from_env().expect("Should read config");
+        DriaComputeNode::new(config, CancellationToken::default())
+    }
 
     #[tokio::test]
     async fn test_base_waku() {
-        let waku = DriaComputeNode::default().waku;
+        let waku = node().waku;
 
         let version = waku.version().await.expect("Should get version");
         assert_eq!("v0.27.0", version);
@@ -20,7 +29,7 @@ mod waku_tests {
     #[tokio::test]
     async fn test_heartbeat_message() {
         const TOPIC: &str = "heartbeat";
-        let waku = DriaComputeNode::default().waku;
+        let waku = node().waku;
 
         waku.relay.subscribe(TOPIC).await.expect("Should subscribe");
         waku.relay
@@ -36,12 +45,12 @@ mod waku_tests {
     async fn test_message_send_and_receive() {
         let _ = env_logger::try_init();
 
-        let node = DriaComputeNode::default();
+        let node = node();
         let topic = "test-topic-msr";
 
-        node.subscribe_topic(topic).await.expect("Should subscribe");
+        let mut receiver = node.subscribe_topic(topic).await.expect("Should subscribe");
 
-        let message = WakuMessage::new("hello world".to_string(), topic);
+        let message = WakuMessage::new("hello world", topic);
 
         node.send_message(message)
             .await
@@ -50,11 +59,16 @@ mod waku_tests {
         // wait a bit for the message
         tokio::time::sleep(tokio::time::Duration::from_secs(1)).await;
 
-        let messages = node
-            .process_topic(topic, false)
+        node.messages
+            .dispatch_once()
             .await
-            .expect("Should receive");
+            .expect("Should dispatch");
+        let mut messages = Vec::new();
+        while let Ok(message) = receiver.try_recv() {
+            messages.push(message);
+        }
+        let messages = node.process_messages(topic, messages, false);
 
-        assert!(messages.len() > 0, "Should have received message");
+        assert!(!messages.is_empty(), "Should have received message");
     }
 }